@@ -191,18 +191,17 @@ fn hash_path(args: &Args, path: &Path) -> anyhow::Result<blake3::OutputReader> {
     Ok(output_reader)
 }
 
-fn write_hex_output(mut output: blake3::OutputReader, args: &Args) -> anyhow::Result<()> {
-    // Encoding multiples of the 64 bytes is most efficient.
-    // TODO: This computes each output block twice when the --seek argument isn't a multiple of 64.
-    // We'll refactor all of this soon anyway, once SIMD optimizations are available for the XOF.
+fn write_hex_output(output: blake3::OutputReader, args: &Args) -> anyhow::Result<()> {
+    // The buffered reader computes each output block once, even when the --seek argument isn't a
+    // multiple of 64.
+    let mut output = blake3::BufferedOutputReader::new(output);
     let mut len = args.len();
     let mut block = [0; blake3::BLOCK_LEN];
     while len > 0 {
-        output.fill(&mut block);
-        let hex_str = hex::encode(&block[..]);
-        let take_bytes = cmp::min(len, block.len() as u64);
-        print!("{}", &hex_str[..2 * take_bytes as usize]);
-        len -= take_bytes;
+        let take_bytes = cmp::min(len, block.len() as u64) as usize;
+        output.fill(&mut block[..take_bytes]);
+        print!("{}", hex::encode(&block[..take_bytes]));
+        len -= take_bytes as u64;
     }
     Ok(())
 }
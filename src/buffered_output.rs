@@ -0,0 +1,233 @@
+//! A buffering wrapper around [`OutputReader`].
+
+use crate::platform::MAX_SIMD_DEGREE;
+use crate::{BLOCK_LEN, Output, OutputReader, ROOT};
+use core::{cmp, fmt};
+
+/// An [`OutputReader`] that keeps a buffer of output blocks, so that short, unaligned, and
+/// backwards reads don't compute the same block more than once.
+///
+/// `OutputReader` doesn't buffer anything. Each call to [`fill`](OutputReader::fill) computes
+/// every output block that it touches, so reading a 64-byte block in 10-byte pieces performs about
+/// seven compressions instead of one. That's what happens when an `OutputReader` is driven through
+/// [`std::io::copy`], a [`BufReader`](std::io::BufReader) with an unaligned capacity, or any other
+/// caller that reads in odd sizes.
+///
+/// `BufferedOutputReader` computes output in batches of whole blocks, as many as the current
+/// platform's SIMD implementation handles in parallel, and serves reads from that batch until the
+/// position leaves it. Reads that are block-aligned and at least a batch long skip the buffer and
+/// write directly to the caller's slice. Seeking within the current batch, including backwards,
+/// doesn't discard it. The result is that any pattern of sequential reads costs about one
+/// compression per 64 bytes of output.
+///
+/// With the `std` Cargo feature (enabled by default), this type implements [`std::io::Read`],
+/// [`std::io::BufRead`], and [`std::io::Seek`].
+///
+/// # Example
+///
+/// ```
+/// let mut reader = blake3::BufferedOutputReader::new(blake3::Hasher::new().finalize_xof());
+/// let mut output = [0; 100];
+/// for piece in output.chunks_mut(7) {
+///     reader.fill(piece);
+/// }
+/// let mut expected = [0; 100];
+/// blake3::Hasher::new().finalize_xof().fill(&mut expected);
+/// assert_eq!(output, expected);
+/// ```
+#[derive(Clone)]
+pub struct BufferedOutputReader {
+    inner: Output,
+    buf: [u8; MAX_SIMD_DEGREE * BLOCK_LEN],
+    // The position of buf[0] in the output stream. This is always a multiple of BLOCK_LEN.
+    buf_start: u64,
+    buf_len: usize,
+    position: u64,
+}
+
+impl BufferedOutputReader {
+    /// Wrap an [`OutputReader`]. Reading starts from the reader's current position.
+    pub fn new(reader: OutputReader) -> Self {
+        let position = reader.position();
+        Self {
+            inner: reader.inner,
+            buf: [0; MAX_SIMD_DEGREE * BLOCK_LEN],
+            buf_start: 0,
+            buf_len: 0,
+            position,
+        }
+    }
+
+    /// Unwrap the underlying [`OutputReader`], with its position set to the current position of
+    /// this reader. Any buffered output is discarded.
+    pub fn into_inner(self) -> OutputReader {
+        let mut reader = OutputReader::new(self.inner);
+        reader.set_position(self.position);
+        reader
+    }
+
+    // The buffered bytes at and after the current position, or an empty slice if the current
+    // position isn't in the buffer.
+    fn buffered(&self) -> &[u8] {
+        match self.position.checked_sub(self.buf_start) {
+            Some(offset) if offset < self.buf_len as u64 => {
+                &self.buf[offset as usize..self.buf_len]
+            }
+            _ => &[],
+        }
+    }
+
+    fn batch_len(&self) -> usize {
+        self.inner.platform.simd_degree() * BLOCK_LEN
+    }
+
+    // Replace the buffer with one batch of blocks, starting with the block that contains the
+    // current position.
+    fn refill(&mut self) {
+        let counter = self.position / BLOCK_LEN as u64;
+        let batch_len = self.batch_len();
+        xof(&self.inner, counter, &mut self.buf[..batch_len]);
+        self.buf_start = counter * BLOCK_LEN as u64;
+        self.buf_len = batch_len;
+    }
+
+    /// Fill a buffer with output bytes and advance the position. This is equivalent to
+    /// [`OutputReader::fill`], but it only computes each output block once for any sequence of
+    /// reads.
+    ///
+    /// The maximum output size of BLAKE3 is 2<sup>64</sup>-1 bytes. If you try to extract more
+    /// than that, for example by seeking near the end and reading further, the behavior is
+    /// unspecified.
+    pub fn fill(&mut self, mut buf: &mut [u8]) {
+        while !buf.is_empty() {
+            let buffered = self.buffered();
+            let offset_in_block = self.position % BLOCK_LEN as u64;
+            if !buffered.is_empty() {
+                let take = cmp::min(buf.len(), buffered.len());
+                buf[..take].copy_from_slice(&buffered[..take]);
+                self.position += take as u64;
+                buf = &mut buf[take..];
+            } else if offset_in_block == 0 && buf.len() >= self.batch_len() {
+                // Large aligned reads bypass the buffer.
+                let direct_len = buf.len() - buf.len() % BLOCK_LEN;
+                xof(
+                    &self.inner,
+                    self.position / BLOCK_LEN as u64,
+                    &mut buf[..direct_len],
+                );
+                self.position += direct_len as u64;
+                buf = &mut buf[direct_len..];
+            } else {
+                self.refill();
+            }
+        }
+    }
+
+    /// XOR output bytes into a buffer and advance the position. This is equivalent to
+    /// [`OutputReader::xor_into`], but as with [`fill`](BufferedOutputReader::fill), it only
+    /// computes each output block once for any sequence of calls.
+    pub fn xor_into(&mut self, mut buf: &mut [u8]) {
+        while !buf.is_empty() {
+            let buffered = self.buffered();
+            if buffered.is_empty() {
+                self.refill();
+                continue;
+            }
+            let take = cmp::min(buf.len(), buffered.len());
+            crate::xor_bytes(&mut buf[..take], &buffered[..take]);
+            self.position += take as u64;
+            buf = &mut buf[take..];
+        }
+    }
+
+    /// Return the current read position in the output stream. See [`OutputReader::position`].
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Seek to a new read position in the output stream. See [`OutputReader::set_position`].
+    ///
+    /// The buffer isn't discarded, so seeking to a position that's already buffered doesn't
+    /// compute anything.
+    pub fn set_position(&mut self, position: u64) {
+        self.position = position;
+    }
+}
+
+// Write whole output blocks, starting from block number `counter`.
+fn xof(output: &Output, counter: u64, out: &mut [u8]) {
+    output.platform.xof_many(
+        &output.input_chaining_value,
+        &output.block,
+        output.block_len,
+        counter,
+        output.flags | ROOT,
+        out,
+    );
+}
+
+impl From<OutputReader> for BufferedOutputReader {
+    fn from(reader: OutputReader) -> Self {
+        Self::new(reader)
+    }
+}
+
+// Don't derive(Debug), because the state may be secret.
+impl fmt::Debug for BufferedOutputReader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BufferedOutputReader")
+            .field("position", &self.position)
+            .finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Read for BufferedOutputReader {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.fill(buf);
+        Ok(buf.len())
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::BufRead for BufferedOutputReader {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.buffered().is_empty() {
+            self.refill();
+        }
+        Ok(self.buffered())
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.position += amt as u64;
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Seek for BufferedOutputReader {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.set_position(crate::seek_target(self.position, pos)?);
+        Ok(self.position)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for BufferedOutputReader {
+    fn zeroize(&mut self) {
+        // Destructuring to trigger compile error as a reminder to update this impl.
+        let Self {
+            inner,
+            buf,
+            buf_start,
+            buf_len,
+            position,
+        } = self;
+
+        inner.zeroize();
+        buf.zeroize();
+        buf_start.zeroize();
+        buf_len.zeroize();
+        position.zeroize();
+    }
+}
@@ -0,0 +1,193 @@
+//! A keyed [`core::hash::BuildHasher`] for hash tables.
+
+use crate::platform::Platform;
+use crate::{BLOCK_LEN, CHUNK_END, CHUNK_START, CVWords, KEY_LEN, KEYED_HASH, Output, platform};
+use core::fmt;
+
+/// A keyed [`core::hash::BuildHasher`], for using BLAKE3 in [`HashMap`] and [`HashSet`].
+///
+/// Hash tables that hash attacker-controlled keys need a keyed hash function, or else an attacker
+/// can choose keys that all land in the same bucket. The standard library uses SipHash for this.
+/// `BuildHasher` uses the BLAKE3 [keyed mode](crate::keyed_hash) instead, which makes it a
+/// pseudorandom function with a 256-bit key. The hash of a value is the first 8 bytes of the
+/// `keyed_hash` of all the bytes that the value's [`Hash`](core::hash::Hash) implementation
+/// writes, interpreted as a little-endian `u64`.
+///
+/// With the `getrandom` Cargo feature, [`random`](BuildHasher::random) and the `Default`
+/// implementation use a random key from the operating system. Without it, or without an operating
+/// system, use [`new`](BuildHasher::new) with a key you've generated yourself. This type doesn't
+/// need `std` or `alloc`.
+///
+/// Most hash table keys write less than 64 bytes in total. For those, [`TableHasher`] buffers all
+/// the writes and performs a single compression in [`finish`](core::hash::Hasher::finish). Longer
+/// inputs fall back to a regular [`Hasher`](crate::Hasher).
+///
+/// [`HashMap`]: https://doc.rust-lang.org/std/collections/struct.HashMap.html
+/// [`HashSet`]: https://doc.rust-lang.org/std/collections/struct.HashSet.html
+///
+/// # Example
+///
+/// ```
+/// # #[cfg(feature = "std")] {
+/// use std::collections::HashMap;
+///
+/// let mut map = HashMap::with_hasher(blake3::BuildHasher::new(&[42; 32]));
+/// map.insert("foo", 1);
+/// assert_eq!(map["foo"], 1);
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct BuildHasher {
+    key: CVWords,
+    platform: Platform,
+}
+
+impl BuildHasher {
+    /// Construct a new `BuildHasher` with the given key. The key should be secret and random, or
+    /// else it doesn't protect against collision attacks.
+    pub fn new(key: &[u8; KEY_LEN]) -> Self {
+        Self {
+            key: platform::words_from_le_bytes_32(key),
+            platform: Platform::detect(),
+        }
+    }
+
+    /// Construct a new `BuildHasher` with a random key from [`getrandom`].
+    ///
+    /// This requires the `getrandom` Cargo feature, which is disabled by default.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `getrandom` fails. (The standard library's `RandomState` also panics in this
+    /// case.) Use [`new`](BuildHasher::new) if you need to handle that error.
+    ///
+    /// [`getrandom`]: https://docs.rs/getrandom
+    #[cfg(feature = "getrandom")]
+    pub fn random() -> Self {
+        let mut key = [0; KEY_LEN];
+        getrandom::fill(&mut key).expect("getrandom failed");
+        Self::new(&key)
+    }
+}
+
+/// This is equivalent to [`BuildHasher::random`], and it requires the `getrandom` Cargo feature.
+#[cfg(feature = "getrandom")]
+impl Default for BuildHasher {
+    fn default() -> Self {
+        Self::random()
+    }
+}
+
+impl core::hash::BuildHasher for BuildHasher {
+    type Hasher = TableHasher;
+
+    #[inline]
+    fn build_hasher(&self) -> TableHasher {
+        TableHasher {
+            key: self.key,
+            platform: self.platform,
+            buf: [0; BLOCK_LEN],
+            buf_len: 0,
+            hasher: None,
+        }
+    }
+}
+
+// Don't derive(Debug), because the state may be secret.
+impl fmt::Debug for BuildHasher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BuildHasher").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for BuildHasher {
+    fn zeroize(&mut self) {
+        // Destructuring to trigger compile error as a reminder to update this impl.
+        let Self { key, platform: _ } = self;
+
+        key.zeroize();
+    }
+}
+
+/// The [`core::hash::Hasher`] created by [`BuildHasher`].
+///
+/// Writes are buffered until they exceed one 64-byte block, so hashing a short value costs one
+/// compression no matter how many `write_*` calls its `Hash` implementation makes.
+#[derive(Clone)]
+pub struct TableHasher {
+    key: CVWords,
+    platform: Platform,
+    buf: [u8; BLOCK_LEN],
+    buf_len: u8,
+    // Only used once the input is longer than one block. Otherwise the whole input is in buf.
+    hasher: Option<crate::Hasher>,
+}
+
+impl core::hash::Hasher for TableHasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(bytes);
+            return;
+        }
+        let buf_len = self.buf_len as usize;
+        if bytes.len() <= BLOCK_LEN - buf_len {
+            self.buf[buf_len..][..bytes.len()].copy_from_slice(bytes);
+            self.buf_len += bytes.len() as u8;
+            return;
+        }
+        let mut hasher = crate::Hasher::new_internal(&self.key, KEYED_HASH);
+        hasher.update(&self.buf[..buf_len]);
+        hasher.update(bytes);
+        self.hasher = Some(hasher);
+    }
+
+    #[inline]
+    fn finish(&self) -> u64 {
+        let root_hash = if let Some(hasher) = &self.hasher {
+            hasher.finalize()
+        } else {
+            // The bytes after buf_len are always zero, as the compression function expects.
+            Output {
+                input_chaining_value: self.key,
+                block: self.buf,
+                block_len: self.buf_len,
+                counter: 0,
+                flags: KEYED_HASH | CHUNK_START | CHUNK_END,
+                platform: self.platform,
+            }
+            .root_hash()
+        };
+        u64::from_le_bytes(*arrayref::array_ref!(root_hash.as_bytes(), 0, 8))
+    }
+}
+
+// Don't derive(Debug), because the state may be secret.
+impl fmt::Debug for TableHasher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TableHasher").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for TableHasher {
+    fn zeroize(&mut self) {
+        // Destructuring to trigger compile error as a reminder to update this impl.
+        let Self {
+            key,
+            platform: _,
+            buf,
+            buf_len,
+            hasher,
+        } = self;
+
+        key.zeroize();
+        buf.zeroize();
+        buf_len.zeroize();
+        if let Some(hasher) = hasher {
+            hasher.zeroize();
+        }
+        *hasher = None;
+    }
+}
@@ -0,0 +1,366 @@
+//! Exporting and importing the internal state of a [`Hasher`].
+//!
+//! A [`Checkpoint`] is a versioned byte encoding of everything a `Hasher` needs to continue where
+//! it left off: the key words, the current [`ChunkState`] (its chaining value, buffered block, and
+//! counters), the initial chunk counter set by
+//! [`set_input_offset`](crate::hazmat::HasherExt::set_input_offset), and the lazily merged CV
+//! stack. The `Platform` isn't included, and it's detected again on import.
+//!
+//! Version 1 of the encoding is laid out as follows. All integers are little-endian.
+//!
+//! ```text
+//! offset  len   field
+//! 0       1     version (1)
+//! 1       1     mode flags (0, KEYED_HASH, or DERIVE_KEY_MATERIAL)
+//! 2       32    key words
+//! 34      8     initial chunk counter
+//! 42      8     chunk counter
+//! 50      32    chunk chaining value
+//! 82      1     blocks compressed
+//! 83      1     buffer length
+//! 84      64    buffer
+//! 148     1     CV stack length (N)
+//! 149     32*N  CV stack, bottom first
+//! ```
+
+use crate::platform::{self, Platform};
+use crate::{
+    BLOCK_LEN, CHUNK_LEN, ChunkState, DERIVE_KEY_MATERIAL, Hasher, IV, KEY_LEN, KEYED_HASH,
+    MAX_DEPTH, OUT_LEN,
+};
+use arrayref::array_ref;
+use arrayvec::ArrayVec;
+use core::fmt;
+
+const VERSION: u8 = 1;
+
+const HEADER_LEN: usize = 1 + 1 + KEY_LEN + 8 + 8 + OUT_LEN + 1 + 1 + BLOCK_LEN + 1;
+
+const MAX_LEN: usize = HEADER_LEN + (MAX_DEPTH + 1) * OUT_LEN;
+
+/// A serialized snapshot of a [`Hasher`], returned by [`Hasher::to_checkpoint`].
+///
+/// A `Checkpoint` lets you save the progress of a long-running hash, for example to disk, and
+/// resume it later with [`Hasher::from_checkpoint`], possibly in a different process or on a
+/// different machine. Resuming from a checkpoint and then hashing the rest of the input gives the
+/// same result as hashing the whole input with one `Hasher`.
+///
+/// The byte encoding returned by [`as_bytes`](Checkpoint::as_bytes) is versioned and stable.
+/// [`from_bytes`](Checkpoint::from_bytes) checks that the encoded state is one that a `Hasher`
+/// could actually be in, so a corrupt or truncated checkpoint returns an error instead of silently
+/// producing a wrong hash. Note that this is not an integrity check. A checkpoint that's been
+/// deliberately modified can still decode successfully, and callers who store checkpoints in
+/// untrusted places need to authenticate them some other way.
+///
+/// With the `serde` Cargo feature, this type implements `Serialize` and `Deserialize`, using the
+/// same byte encoding and the same validation.
+///
+/// # Security notes
+///
+/// A checkpoint contains the key of a keyed `Hasher`, and it may contain buffered input bytes. It
+/// should be handled with the same care as both of those.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), blake3::CheckpointError> {
+/// let mut hasher = blake3::Hasher::new();
+/// hasher.update(b"foo");
+/// let saved: Vec<u8> = hasher.to_checkpoint().as_bytes().to_vec();
+///
+/// // Later...
+/// let checkpoint = blake3::Checkpoint::from_bytes(&saved)?;
+/// let mut resumed = blake3::Hasher::from_checkpoint(&checkpoint);
+/// resumed.update(b"bar");
+/// assert_eq!(resumed.finalize(), blake3::hash(b"foobar"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Checkpoint {
+    bytes: ArrayVec<u8, MAX_LEN>,
+}
+
+impl Checkpoint {
+    /// The encoded bytes of this checkpoint.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Decode a checkpoint from the bytes returned by [`as_bytes`](Checkpoint::as_bytes).
+    ///
+    /// This returns an error if the version is unknown, if the length is wrong, or if the encoded
+    /// state is inconsistent, for example if the length of the CV stack doesn't match the chunk
+    /// counter.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CheckpointError> {
+        decode(bytes)?;
+        let mut checkpoint = Self {
+            bytes: ArrayVec::new(),
+        };
+        // decode() checked the length.
+        checkpoint.bytes.try_extend_from_slice(bytes).unwrap();
+        Ok(checkpoint)
+    }
+}
+
+// Don't derive(Debug), because the state may be secret.
+impl fmt::Debug for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Checkpoint")
+            .field("version", &self.bytes[0])
+            .field("len", &self.bytes.len())
+            .finish()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Checkpoint {
+    fn zeroize(&mut self) {
+        // Destructuring to trigger compile error as a reminder to update this impl.
+        let Self { bytes } = self;
+
+        bytes.zeroize();
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Checkpoint {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.as_bytes())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Checkpoint {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CheckpointVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for CheckpointVisitor {
+            type Value = Checkpoint;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("BLAKE3 checkpoint bytes")
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Checkpoint, E> {
+                Checkpoint::from_bytes(bytes).map_err(E::custom)
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Checkpoint, A::Error> {
+                let mut bytes = ArrayVec::<u8, MAX_LEN>::new();
+                while let Some(byte) = seq.next_element()? {
+                    if bytes.try_push(byte).is_err() {
+                        return Err(serde::de::Error::custom(CheckpointError(
+                            CheckpointErrorInner::InvalidLen(MAX_LEN + 1),
+                        )));
+                    }
+                }
+                Checkpoint::from_bytes(&bytes).map_err(serde::de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_bytes(CheckpointVisitor)
+    }
+}
+
+/// The error type for [`Checkpoint::from_bytes`].
+///
+/// The `.to_string()` representation of this error currently describes which check failed. This
+/// is to help with logging and debugging, but it isn't a stable API detail, and it may change at
+/// any time.
+#[derive(Clone, Debug)]
+pub struct CheckpointError(CheckpointErrorInner);
+
+#[derive(Clone, Debug)]
+enum CheckpointErrorInner {
+    UnsupportedVersion(u8),
+    InvalidLen(usize),
+    InvalidFlags(u8),
+    InvalidKey,
+    InvalidChunkState,
+    InvalidChunkCounter,
+    InvalidStackLen { stack_len: usize, chunks: u64 },
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            CheckpointErrorInner::UnsupportedVersion(version) => {
+                write!(f, "unsupported checkpoint version: {}", version)
+            }
+            CheckpointErrorInner::InvalidLen(len) => {
+                write!(f, "invalid checkpoint length: {}", len)
+            }
+            CheckpointErrorInner::InvalidFlags(flags) => {
+                write!(f, "invalid checkpoint flags: 0x{:x}", flags)
+            }
+            CheckpointErrorInner::InvalidKey => {
+                write!(f, "checkpoint key doesn't match its mode")
+            }
+            CheckpointErrorInner::InvalidChunkState => {
+                write!(f, "invalid checkpoint chunk state")
+            }
+            CheckpointErrorInner::InvalidChunkCounter => {
+                write!(f, "invalid checkpoint chunk counter")
+            }
+            CheckpointErrorInner::InvalidStackLen { stack_len, chunks } => {
+                write!(
+                    f,
+                    "checkpoint CV stack length {} is inconsistent with {} chunks",
+                    stack_len, chunks,
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CheckpointError {}
+
+fn err<T>(inner: CheckpointErrorInner) -> Result<T, CheckpointError> {
+    Err(CheckpointError(inner))
+}
+
+// Parse and validate a checkpoint. Every state that passes these checks is one that
+// Hasher::update() and Hasher::finalize() handle correctly.
+fn decode(bytes: &[u8]) -> Result<Hasher, CheckpointError> {
+    if bytes.is_empty() {
+        return err(CheckpointErrorInner::InvalidLen(0));
+    }
+    if bytes[0] != VERSION {
+        return err(CheckpointErrorInner::UnsupportedVersion(bytes[0]));
+    }
+    if bytes.len() < HEADER_LEN {
+        return err(CheckpointErrorInner::InvalidLen(bytes.len()));
+    }
+    let stack_len = bytes[HEADER_LEN - 1] as usize;
+    if stack_len > MAX_DEPTH + 1 || bytes.len() != HEADER_LEN + stack_len * OUT_LEN {
+        return err(CheckpointErrorInner::InvalidLen(bytes.len()));
+    }
+
+    let flags = bytes[1];
+    let key = platform::words_from_le_bytes_32(array_ref!(bytes, 2, KEY_LEN));
+    let initial_chunk_counter = u64::from_le_bytes(*array_ref!(bytes, 34, 8));
+    let chunk_counter = u64::from_le_bytes(*array_ref!(bytes, 42, 8));
+    let cv = platform::words_from_le_bytes_32(array_ref!(bytes, 50, OUT_LEN));
+    let blocks_compressed = bytes[82];
+    let buf_len = bytes[83];
+    let buf = *array_ref!(bytes, 84, BLOCK_LEN);
+
+    // The mode. Only the regular hash mode is tied to a specific key.
+    match flags {
+        0 => {
+            if key != *IV {
+                return err(CheckpointErrorInner::InvalidKey);
+            }
+        }
+        KEYED_HASH | DERIVE_KEY_MATERIAL => {}
+        _ => return err(CheckpointErrorInner::InvalidFlags(flags)),
+    }
+
+    // The chunk state. ChunkState always keeps the last block of input in its buffer, and it
+    // zeroes the buffer after compressing it, so compressed blocks imply buffered bytes, and
+    // there are never any nonzero bytes past buf_len. Also, the chaining value only changes
+    // when a block is compressed.
+    let chunk_len = BLOCK_LEN * blocks_compressed as usize + buf_len as usize;
+    if buf_len as usize > BLOCK_LEN
+        || chunk_len > CHUNK_LEN
+        || (blocks_compressed > 0 && buf_len == 0)
+        || buf[buf_len as usize..].iter().any(|&b| b != 0)
+        || (blocks_compressed == 0 && cv != key)
+    {
+        return err(CheckpointErrorInner::InvalidChunkState);
+    }
+
+    // The counters. The total input length in bytes has to fit in a u64, and a subtree that
+    // starts at a non-zero offset can't grow past its maximum length.
+    const MAX_CHUNKS: u64 = u64::MAX / CHUNK_LEN as u64;
+    if chunk_counter < initial_chunk_counter || chunk_counter > MAX_CHUNKS {
+        return err(CheckpointErrorInner::InvalidChunkCounter);
+    }
+    let chunks = chunk_counter - initial_chunk_counter;
+    let total_len = chunks
+        .checked_mul(CHUNK_LEN as u64)
+        .and_then(|len| len.checked_add(chunk_len as u64));
+    let max_len = crate::hazmat::max_subtree_len(initial_chunk_counter * CHUNK_LEN as u64);
+    match (total_len, max_len) {
+        (Some(len), Some(max)) if len <= max => {}
+        (Some(_), None) => {}
+        _ => return err(CheckpointErrorInner::InvalidChunkCounter),
+    }
+
+    // The CV stack. If there are bytes in the chunk state (or no input at all), then update()
+    // has already merged the stack, and there's one CV for each 1-bit in the number of chunks. If
+    // the last update() ended on a chunk boundary, the last subtree it pushed might not be merged
+    // yet. That subtree was at least two CVs (see compress_subtree_to_parent_node), and its size
+    // can't be larger than the largest power of two dividing the number of chunks, so there can
+    // be at most trailing_zeros(chunks) unmerged CVs.
+    let merged_len = chunks.count_ones() as usize;
+    let stack_len_ok = if chunk_len > 0 || chunks == 0 {
+        stack_len == merged_len
+    } else {
+        stack_len >= 2
+            && stack_len >= merged_len
+            && stack_len <= merged_len + chunks.trailing_zeros() as usize
+    };
+    if !stack_len_ok {
+        return err(CheckpointErrorInner::InvalidStackLen { stack_len, chunks });
+    }
+
+    let platform = Platform::detect();
+    let mut cv_stack = ArrayVec::new();
+    for cv_bytes in bytes[HEADER_LEN..].chunks_exact(OUT_LEN) {
+        cv_stack.push(*array_ref!(cv_bytes, 0, OUT_LEN));
+    }
+    Ok(Hasher {
+        key,
+        chunk_state: ChunkState {
+            cv,
+            chunk_counter,
+            buf,
+            buf_len,
+            blocks_compressed,
+            flags,
+            platform,
+        },
+        initial_chunk_counter,
+        cv_stack,
+    })
+}
+
+impl Hasher {
+    /// Export the internal state of this `Hasher` as a [`Checkpoint`].
+    ///
+    /// See [`from_checkpoint`](Hasher::from_checkpoint) and the [`Checkpoint`] docs.
+    pub fn to_checkpoint(&self) -> Checkpoint {
+        let mut bytes = ArrayVec::<u8, MAX_LEN>::new();
+        bytes.push(VERSION);
+        bytes.push(self.chunk_state.flags);
+        let mut extend = |slice: &[u8]| bytes.try_extend_from_slice(slice).unwrap();
+        extend(&platform::le_bytes_from_words_32(&self.key));
+        extend(&self.initial_chunk_counter.to_le_bytes());
+        extend(&self.chunk_state.chunk_counter.to_le_bytes());
+        extend(&platform::le_bytes_from_words_32(&self.chunk_state.cv));
+        extend(&[self.chunk_state.blocks_compressed, self.chunk_state.buf_len]);
+        extend(&self.chunk_state.buf);
+        extend(&[self.cv_stack.len() as u8]);
+        for cv in &self.cv_stack {
+            extend(cv);
+        }
+        debug_assert!(decode(&bytes).is_ok());
+        Checkpoint { bytes }
+    }
+
+    /// Construct a `Hasher` from a [`Checkpoint`] returned by
+    /// [`to_checkpoint`](Hasher::to_checkpoint).
+    ///
+    /// The new `Hasher` continues exactly where the original left off. Checkpoints are validated
+    /// when they're decoded with [`Checkpoint::from_bytes`] (or deserialized with the `serde`
+    /// feature), so this function can't fail.
+    pub fn from_checkpoint(checkpoint: &Checkpoint) -> Self {
+        decode(&checkpoint.bytes).expect("checkpoints are validated on construction")
+    }
+}
@@ -0,0 +1,145 @@
+//! A `const fn` implementation of BLAKE3, for computing hashes at compile time.
+//!
+//! This is a straightforward version of the tree hashing algorithm, similar to the reference
+//! implementation, built on the portable compression function (which is also `const`). It doesn't
+//! use SIMD or multithreading, so it's much slower than [`hash`](crate::hash) for long inputs at
+//! runtime. It's intended for short inputs like [`derive_key`](crate::derive_key) context strings.
+
+use crate::{
+    BLOCK_LEN, CHUNK_END, CHUNK_LEN, CHUNK_START, CVWords, MAX_DEPTH, OUT_LEN, PARENT, ROOT,
+    platform, portable,
+};
+
+// The const equivalent of crate::Output.
+struct ConstOutput {
+    input_chaining_value: CVWords,
+    block: [u8; BLOCK_LEN],
+    block_len: u8,
+    counter: u64,
+    flags: u8,
+}
+
+impl ConstOutput {
+    const fn chaining_value(&self) -> CVWords {
+        let mut cv = self.input_chaining_value;
+        portable::compress_in_place(
+            &mut cv,
+            &self.block,
+            self.block_len,
+            self.counter,
+            self.flags,
+        );
+        cv
+    }
+
+    const fn root_hash(&self) -> [u8; OUT_LEN] {
+        debug_assert!(self.counter == 0);
+        let mut cv = self.input_chaining_value;
+        portable::compress_in_place(&mut cv, &self.block, self.block_len, 0, self.flags | ROOT);
+        platform::le_bytes_from_words_32(&cv)
+    }
+}
+
+// Compress every block of a chunk but the last, and return the Output for the last one. The chunk
+// must be no longer than CHUNK_LEN, and it's only empty if the whole input is empty.
+const fn chunk_output(chunk: &[u8], key: &CVWords, chunk_counter: u64, flags: u8) -> ConstOutput {
+    debug_assert!(chunk.len() <= CHUNK_LEN);
+    let mut cv = *key;
+    let mut block_flags = flags | CHUNK_START;
+    let mut rest = chunk;
+    while rest.len() > BLOCK_LEN {
+        let (block, tail) = rest.split_at(BLOCK_LEN);
+        let Some(block) = block.first_chunk::<BLOCK_LEN>() else {
+            unreachable!();
+        };
+        portable::compress_in_place(&mut cv, block, BLOCK_LEN as u8, chunk_counter, block_flags);
+        block_flags = flags;
+        rest = tail;
+    }
+    let mut block = [0; BLOCK_LEN];
+    let mut i = 0;
+    while i < rest.len() {
+        block[i] = rest[i];
+        i += 1;
+    }
+    ConstOutput {
+        input_chaining_value: cv,
+        block,
+        block_len: rest.len() as u8,
+        counter: chunk_counter,
+        flags: block_flags | CHUNK_END,
+    }
+}
+
+const fn parent_output(
+    left_child: &CVWords,
+    right_child: &CVWords,
+    key: &CVWords,
+    flags: u8,
+) -> ConstOutput {
+    let left_bytes = platform::le_bytes_from_words_32(left_child);
+    let right_bytes = platform::le_bytes_from_words_32(right_child);
+    let mut block = [0; BLOCK_LEN];
+    let mut i = 0;
+    while i < OUT_LEN {
+        block[i] = left_bytes[i];
+        block[OUT_LEN + i] = right_bytes[i];
+        i += 1;
+    }
+    ConstOutput {
+        input_chaining_value: *key,
+        block,
+        block_len: BLOCK_LEN as u8,
+        counter: 0,
+        flags: flags | PARENT,
+    }
+}
+
+// The compression function on byte chaining values, for hazmat::const_compress().
+pub(crate) const fn compress(
+    cv: &[u8; OUT_LEN],
+    block: &[u8; BLOCK_LEN],
+    block_len: u8,
+    counter: u64,
+    flags: u8,
+) -> [u8; OUT_LEN] {
+    let mut cv_words = platform::words_from_le_bytes_32(cv);
+    portable::compress_in_place(&mut cv_words, block, block_len, counter, flags);
+    platform::le_bytes_from_words_32(&cv_words)
+}
+
+// The const equivalent of crate::hash_all_at_once(), returning the root hash.
+pub(crate) const fn hash_all_at_once(input: &[u8], key: &CVWords, flags: u8) -> [u8; OUT_LEN] {
+    // Like the reference implementation, keep a stack of subtree chaining values, and merge
+    // completed subtrees as soon as we know they aren't the root. A chunk is only known not to be
+    // the root when there's more input after it.
+    let mut cv_stack = [[0; 8]; MAX_DEPTH];
+    let mut cv_stack_len = 0;
+    let mut chunk_counter = 0;
+    let mut rest = input;
+    while rest.len() > CHUNK_LEN {
+        let (chunk, tail) = rest.split_at(CHUNK_LEN);
+        let mut cv = chunk_output(chunk, key, chunk_counter, flags).chaining_value();
+        chunk_counter += 1;
+        let mut total_chunks = chunk_counter;
+        while total_chunks & 1 == 0 {
+            cv_stack_len -= 1;
+            cv = parent_output(&cv_stack[cv_stack_len], &cv, key, flags).chaining_value();
+            total_chunks >>= 1;
+        }
+        cv_stack[cv_stack_len] = cv;
+        cv_stack_len += 1;
+        rest = tail;
+    }
+    let mut output = chunk_output(rest, key, chunk_counter, flags);
+    while cv_stack_len > 0 {
+        cv_stack_len -= 1;
+        output = parent_output(
+            &cv_stack[cv_stack_len],
+            &output.chaining_value(),
+            key,
+            flags,
+        );
+    }
+    output.root_hash()
+}
@@ -0,0 +1,308 @@
+//! Base64url, base32, and multibase encodings for [`Hash`].
+//!
+//! All of these are unpadded. A 32-byte hash doesn't fill a whole number of base64 or base32
+//! characters, so the final character carries some zero padding bits. Decoding rejects inputs
+//! where those bits are nonzero, so that every `Hash` has exactly one valid encoding in each
+//! alphabet.
+
+use crate::{Hash, OUT_LEN};
+use arrayvec::ArrayString;
+use core::fmt;
+
+/// The length of [`Hash::to_base64url`] output.
+const BASE64URL_LEN: usize = (8 * OUT_LEN).div_ceil(6);
+
+/// The length of [`Hash::to_base32`] output.
+const BASE32_LEN: usize = (8 * OUT_LEN).div_ceil(5);
+
+const BASE64URL_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+#[derive(Clone, Copy, Debug)]
+enum Encoding {
+    Base64Url,
+    Base32,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Base64Url => "base64url",
+            Encoding::Base32 => "base32",
+        }
+    }
+
+    fn bits_per_char(self) -> u32 {
+        match self {
+            Encoding::Base64Url => 6,
+            Encoding::Base32 => 5,
+        }
+    }
+
+    fn encoded_len(self) -> usize {
+        match self {
+            Encoding::Base64Url => BASE64URL_LEN,
+            Encoding::Base32 => BASE32_LEN,
+        }
+    }
+
+    fn char_val(self, byte: u8) -> Option<u32> {
+        let val = match (self, byte) {
+            (Encoding::Base64Url, b'A'..=b'Z') => byte - b'A',
+            (Encoding::Base64Url, b'a'..=b'z') => byte - b'a' + 26,
+            (Encoding::Base64Url, b'0'..=b'9') => byte - b'0' + 52,
+            (Encoding::Base64Url, b'-') => 62,
+            (Encoding::Base64Url, b'_') => 63,
+            (Encoding::Base32, b'A'..=b'Z') => byte - b'A',
+            (Encoding::Base32, b'a'..=b'z') => byte - b'a',
+            (Encoding::Base32, b'2'..=b'7') => byte - b'2' + 26,
+            _ => return None,
+        };
+        Some(val as u32)
+    }
+}
+
+fn encode<const N: usize>(bytes: &[u8; OUT_LEN], alphabet: &[u8], bits: u32) -> ArrayString<N> {
+    let mask = (1 << bits) - 1;
+    let mut s = ArrayString::new();
+    // Bits above acc_bits are garbage and get masked off.
+    let mut acc: u32 = 0;
+    let mut acc_bits = 0;
+    for &byte in bytes {
+        acc = (acc << 8) | byte as u32;
+        acc_bits += 8;
+        while acc_bits >= bits {
+            acc_bits -= bits;
+            s.push(alphabet[((acc >> acc_bits) & mask) as usize] as char);
+        }
+    }
+    if acc_bits > 0 {
+        s.push(alphabet[((acc << (bits - acc_bits)) & mask) as usize] as char);
+    }
+    s
+}
+
+fn decode(input: &[u8], encoding: Encoding) -> Result<Hash, DecodeError> {
+    if input.len() != encoding.encoded_len() {
+        return Err(DecodeError(DecodeErrorInner::InvalidLen {
+            encoding: encoding.name(),
+            expected: encoding.encoded_len(),
+            len: input.len(),
+        }));
+    }
+    let bits = encoding.bits_per_char();
+    let mut hash_bytes = [0; OUT_LEN];
+    let mut i = 0;
+    let mut acc: u32 = 0;
+    let mut acc_bits = 0;
+    for &byte in input {
+        let Some(val) = encoding.char_val(byte) else {
+            return Err(DecodeError(DecodeErrorInner::InvalidByte {
+                encoding: encoding.name(),
+                byte,
+            }));
+        };
+        acc = (acc << bits) | val;
+        acc_bits += bits;
+        if acc_bits >= 8 {
+            acc_bits -= 8;
+            hash_bytes[i] = (acc >> acc_bits) as u8;
+            i += 1;
+        }
+    }
+    debug_assert_eq!(i, OUT_LEN);
+    if acc & ((1 << acc_bits) - 1) != 0 {
+        return Err(DecodeError(DecodeErrorInner::NonCanonical {
+            encoding: encoding.name(),
+        }));
+    }
+    Ok(Hash::from_bytes(hash_bytes))
+}
+
+/// The bases supported by [`Hash::to_multibase`] and [`Hash::from_multibase`].
+///
+/// See the [multibase spec](https://github.com/multiformats/multibase) for the prefix characters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Multibase {
+    /// Lowercase hexadecimal, the same as [`Hash::to_hex`], with the prefix `f`.
+    Base16,
+    /// Lowercase unpadded base32, the same as [`Hash::to_base32`], with the prefix `b`.
+    Base32,
+    /// Unpadded base64url, the same as [`Hash::to_base64url`], with the prefix `u`.
+    Base64Url,
+}
+
+impl Hash {
+    /// Encode a `Hash` in unpadded base64url, the URL- and filename-safe alphabet from RFC 4648.
+    ///
+    /// The output is always 43 characters. Like [`to_hex`](Hash::to_hex), the returned
+    /// [`ArrayString`] doesn't allocate memory on the heap and doesn't provide constant-time
+    /// equality checking.
+    ///
+    /// [`ArrayString`]: https://docs.rs/arrayvec/0.7/arrayvec/struct.ArrayString.html
+    pub fn to_base64url(&self) -> ArrayString<43> {
+        encode::<BASE64URL_LEN>(self.as_bytes(), BASE64URL_ALPHABET, 6)
+    }
+
+    /// Decode a `Hash` from unpadded base64url.
+    ///
+    /// Padding characters, characters from the standard base64 alphabet (`+` and `/`), and any
+    /// input length other than 43 result in an error. So do nonzero padding bits in the final
+    /// character.
+    pub fn from_base64url(input: impl AsRef<[u8]>) -> Result<Self, DecodeError> {
+        decode(input.as_ref(), Encoding::Base64Url)
+    }
+
+    /// Encode a `Hash` in lowercase unpadded base32, using the alphabet from RFC 4648.
+    ///
+    /// The output is always 52 characters. Like [`to_hex`](Hash::to_hex), the returned
+    /// [`ArrayString`] doesn't allocate memory on the heap and doesn't provide constant-time
+    /// equality checking.
+    ///
+    /// [`ArrayString`]: https://docs.rs/arrayvec/0.7/arrayvec/struct.ArrayString.html
+    pub fn to_base32(&self) -> ArrayString<52> {
+        encode::<BASE32_LEN>(self.as_bytes(), BASE32_ALPHABET, 5)
+    }
+
+    /// Decode a `Hash` from unpadded base32. Both uppercase and lowercase ASCII bytes are
+    /// supported.
+    ///
+    /// Padding characters and any input length other than 52 result in an error. So do nonzero
+    /// padding bits in the final character.
+    pub fn from_base32(input: impl AsRef<[u8]>) -> Result<Self, DecodeError> {
+        decode(input.as_ref(), Encoding::Base32)
+    }
+
+    /// Encode a `Hash` in the given base, with a [multibase](https://github.com/multiformats/multibase)
+    /// prefix character.
+    ///
+    /// The returned [`ArrayString`] has room for the longest encoding, hex.
+    ///
+    /// [`ArrayString`]: https://docs.rs/arrayvec/0.7/arrayvec/struct.ArrayString.html
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use blake3::Multibase;
+    ///
+    /// let hash = blake3::hash(b"foo");
+    /// let encoded = hash.to_multibase(Multibase::Base64Url);
+    /// assert_eq!(encoded.as_str(), "uBOC7OfMLGj_rifU2yTvhUFVILfdIZ0sA0m5adXd3Auk");
+    /// assert_eq!(blake3::Hash::from_multibase(encoded.as_str())?, hash);
+    /// # Ok::<(), blake3::DecodeError>(())
+    /// ```
+    pub fn to_multibase(&self, base: Multibase) -> ArrayString<{ 1 + 2 * OUT_LEN }> {
+        let mut s = ArrayString::new();
+        match base {
+            Multibase::Base16 => {
+                s.push('f');
+                s.push_str(&self.to_hex());
+            }
+            Multibase::Base32 => {
+                s.push('b');
+                s.push_str(&self.to_base32());
+            }
+            Multibase::Base64Url => {
+                s.push('u');
+                s.push_str(&self.to_base64url());
+            }
+        }
+        s
+    }
+
+    /// Decode a `Hash` from a [multibase](https://github.com/multiformats/multibase) string.
+    ///
+    /// The supported prefixes are `f` and `F` (hex), `b` and `B` (base32), and `u` (base64url).
+    /// As with [`from_hex`](Hash::from_hex) and [`from_base32`](Hash::from_base32), the case of
+    /// hex and base32 characters isn't checked against the case of the prefix. Any other prefix
+    /// results in an error.
+    pub fn from_multibase(input: impl AsRef<[u8]>) -> Result<Self, DecodeError> {
+        let input: &[u8] = input.as_ref();
+        match input.split_first() {
+            Some((b'f' | b'F', rest)) => Hash::from_hex(rest).map_err(DecodeError::from),
+            Some((b'b' | b'B', rest)) => Hash::from_base32(rest),
+            Some((b'u', rest)) => Hash::from_base64url(rest),
+            Some((&prefix, _)) => Err(DecodeError(DecodeErrorInner::InvalidMultibasePrefix(
+                prefix,
+            ))),
+            None => Err(DecodeError(DecodeErrorInner::MissingMultibasePrefix)),
+        }
+    }
+}
+
+/// The error type for [`Hash::from_base64url`], [`Hash::from_base32`], and
+/// [`Hash::from_multibase`].
+///
+/// The `.to_string()` representation of this error currently distinguishes between bad length
+/// errors, bad character errors, and bad prefix errors. This is to help with logging and
+/// debugging, but it isn't a stable API detail, and it may change at any time.
+#[derive(Clone, Debug)]
+pub struct DecodeError(DecodeErrorInner);
+
+#[derive(Clone, Debug)]
+enum DecodeErrorInner {
+    InvalidByte {
+        encoding: &'static str,
+        byte: u8,
+    },
+    InvalidLen {
+        encoding: &'static str,
+        expected: usize,
+        len: usize,
+    },
+    NonCanonical {
+        encoding: &'static str,
+    },
+    InvalidMultibasePrefix(u8),
+    MissingMultibasePrefix,
+    Hex(crate::HexError),
+}
+
+impl From<crate::HexError> for DecodeError {
+    fn from(e: crate::HexError) -> Self {
+        DecodeError(DecodeErrorInner::Hex(e))
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.0 {
+            &DecodeErrorInner::InvalidByte { encoding, byte } => {
+                if byte < 128 {
+                    write!(f, "invalid {} character: {:?}", encoding, byte as char)
+                } else {
+                    write!(f, "invalid {} character: 0x{:x}", encoding, byte)
+                }
+            }
+            &DecodeErrorInner::InvalidLen {
+                encoding,
+                expected,
+                len,
+            } => {
+                write!(
+                    f,
+                    "expected {} {} bytes, received {}",
+                    expected, encoding, len
+                )
+            }
+            &DecodeErrorInner::NonCanonical { encoding } => {
+                write!(f, "nonzero padding bits in {} input", encoding)
+            }
+            &DecodeErrorInner::InvalidMultibasePrefix(byte) => {
+                if byte < 128 {
+                    write!(f, "unsupported multibase prefix: {:?}", byte as char)
+                } else {
+                    write!(f, "unsupported multibase prefix: 0x{:x}", byte)
+                }
+            }
+            DecodeErrorInner::MissingMultibasePrefix => write!(f, "empty multibase input"),
+            DecodeErrorInner::Hex(e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecodeError {}
@@ -6,8 +6,8 @@ pub use crate::{BLOCK_LEN, CHUNK_LEN};
 pub struct ChunkState(crate::ChunkState);
 
 impl ChunkState {
-    // Currently this type only supports the regular hash mode. If an
-    // incremental user needs keyed_hash or derive_key, we can add that.
+    // Currently this type only supports the regular hash mode. For keyed_hash
+    // or derive_key, use hazmat::ChunkHasher instead.
     pub fn new(chunk_counter: u64) -> Self {
         Self(crate::ChunkState::new(
             crate::IV,
@@ -38,8 +38,8 @@ impl ChunkState {
     }
 }
 
-// As above, this currently assumes the regular hash mode. If an incremental
-// user needs keyed_hash or derive_key, we can add that.
+// As above, this currently assumes the regular hash mode. For keyed_hash or
+// derive_key, use hazmat::merge_subtrees_non_root and merge_subtrees_root.
 pub fn parent_cv(
     left_child: &crate::Hash,
     right_child: &crate::Hash,
@@ -33,7 +33,13 @@
 //! [`finalize_non_root`](HasherExt::finalize_non_root) methods. These let you compute the chaining
 //! values of individual chunks or subtrees. You then combine these chaining values into larger
 //! subtrees using [`merge_subtrees_non_root`] and finally (once at the very top)
-//! [`merge_subtrees_root`] or [`merge_subtrees_root_xof`].
+//! [`merge_subtrees_root`] or [`merge_subtrees_root_xof`]. If you have a whole level of sibling
+//! chaining values at once, [`merge_level_non_root`] and [`merge_levels_root`] merge them using
+//! SIMD parallelism. For callers who work one chunk at a time, [`ChunkHasher`] hashes a single
+//! chunk in any mode and at any position, and [`hash_chunks`] hashes many whole chunks in
+//! parallel. Below that, [`compress`], [`compress_xof`], and [`hash_many`] expose the raw
+//! compression function, together with flag constants like [`CHUNK_START`] and [`ROOT`], for
+//! building other constructions on top of it.
 //!
 //! # Examples
 //!
@@ -141,16 +147,35 @@
 //! # }
 //! ```
 //!
+//! When offsets and lengths come from somewhere untrusted, like the network, a panic is a denial
+//! of service. The `try_` methods of [`HasherExt`], like
+//! [`try_set_input_offset`](HasherExt::try_set_input_offset) and
+//! [`try_update`](HasherExt::try_update), check the same rules and return an [`Error`] instead.
+//!
 //! For more on valid tree structures, see the docs for and [`left_subtree_len`] and
 //! [`max_subtree_len`], and see section 2.1 of [the BLAKE3
 //! paper](https://github.com/BLAKE3-team/BLAKE3-specs/blob/master/blake3.pdf). Note that the
 //! merging functions ([`merge_subtrees_root`] and friends) don't know the shape of the left and
 //! right subtrees you're giving them, and they can't help you catch mistakes. The best way to
 //! catch mistakes with these is to compare your root output to the [`blake3::hash`](crate::hash)
-//! of the same input.
+//! of the same input. If your subtrees arrive out of order, or from different places, the
+//! [`TreeBuilder`] type (which requires the `std` Cargo feature) can keep track of them for you.
+//! It checks each subtree's position and size, merges neighbors as they arrive, and tells you
+//! which ranges are still missing.
+//!
+//! To ship subtree chaining values between machines, [`SubtreeResult`] records each one with its
+//! position and mode, and it has a compact binary encoding as well as `serde` support.
+//! [`combine_subtrees`] checks that a set of results fits together and returns the root hash.
+
+use crate::platform::{MAX_SIMD_DEGREE, MAX_SIMD_DEGREE_OR_2, Platform};
+use crate::{BLOCK_LEN, CHUNK_LEN, CVWords, Hasher, IV, KEY_LEN, OUT_LEN};
+use core::cmp;
 
-use crate::platform::Platform;
-use crate::{CHUNK_LEN, CVWords, Hasher, IV, KEY_LEN, OUT_LEN};
+pub use crate::subtree_result::{
+    SubtreeError, SubtreeResult, combine_subtrees, combine_subtrees_xof,
+};
+#[cfg(feature = "std")]
+pub use crate::tree_builder::{TreeBuilder, TreeError};
 
 /// Extension methods for [`Hasher`]. This is the main entrypoint to the `hazmat` module.
 pub trait HasherExt {
@@ -206,9 +231,37 @@ pub trait HasherExt {
     /// never correct to use a non-zero input offset with [`finalize`](Hasher::finalize) or
     /// [`finalize_xof`](Hasher::finalize_xof). The `offset` must also be a multiple of
     /// `CHUNK_LEN`. Violating either of these rules will currently fail an assertion and panic,
-    /// but this is not guaranteed.
+    /// but this is not guaranteed. If the offset comes from somewhere untrusted, use
+    /// [`try_set_input_offset`](HasherExt::try_set_input_offset) instead.
     fn set_input_offset(&mut self, offset: u64) -> &mut Self;
 
+    /// Like [`set_input_offset`](HasherExt::set_input_offset), but return an [`Error`] instead of
+    /// panicking if the `Hasher` has already accepted input or if `offset` isn't a multiple of
+    /// [`CHUNK_LEN`]. The `Hasher` is unchanged after an error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use blake3::Hasher;
+    /// use blake3::hazmat::HasherExt;
+    ///
+    /// let mut hasher = Hasher::new();
+    /// assert!(hasher.try_set_input_offset(1000).is_err());
+    /// hasher.try_set_input_offset(1024)?;
+    /// assert!(hasher.try_update(&[0; 1025]).is_err());
+    /// hasher.try_update(&[0; 1024])?;
+    /// assert!(hasher.try_finalize().is_err());
+    /// let chaining_value = hasher.try_finalize_non_root()?;
+    /// # Ok::<(), blake3::hazmat::Error>(())
+    /// ```
+    fn try_set_input_offset(&mut self, offset: u64) -> Result<&mut Self, Error>;
+
+    /// Like [`Hasher::update`], but return an [`Error`] instead of panicking if the input doesn't
+    /// fit in the subtree that started at the offset from
+    /// [`set_input_offset`](HasherExt::set_input_offset). See [`max_subtree_len`]. The `Hasher`
+    /// is unchanged after an error.
+    fn try_update(&mut self, input: &[u8]) -> Result<&mut Self, Error>;
+
     /// Finalize the non-root hash ("chaining value") of the current chunk or subtree.
     ///
     /// Afterwards you can merge subtree chaining values into parent nodes using
@@ -218,7 +271,24 @@ pub trait HasherExt {
     ///
     /// See the [module level examples](index.html#examples), particularly the discussion of valid
     /// tree structures.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the `Hasher` hasn't accepted any input. Empty subtrees are never
+    /// valid. See [`try_finalize_non_root`](HasherExt::try_finalize_non_root).
     fn finalize_non_root(&self) -> ChainingValue;
+
+    /// Like [`finalize_non_root`](HasherExt::finalize_non_root), but return an [`Error`] instead
+    /// of panicking if the `Hasher` hasn't accepted any input.
+    fn try_finalize_non_root(&self) -> Result<ChainingValue, Error>;
+
+    /// Like [`Hasher::finalize`], but return an [`Error`] instead of panicking if the `Hasher` was
+    /// configured with a non-zero [`set_input_offset`](HasherExt::set_input_offset).
+    fn try_finalize(&self) -> Result<crate::Hash, Error>;
+
+    /// Like [`Hasher::finalize_xof`], but return an [`Error`] instead of panicking if the `Hasher`
+    /// was configured with a non-zero [`set_input_offset`](HasherExt::set_input_offset).
+    fn try_finalize_xof(&self) -> Result<crate::OutputReader, Error>;
 }
 
 impl HasherExt for Hasher {
@@ -228,29 +298,129 @@ impl HasherExt for Hasher {
     }
 
     fn set_input_offset(&mut self, offset: u64) -> &mut Hasher {
-        assert_eq!(self.count(), 0, "hasher has already accepted input");
-        assert_eq!(
-            offset % CHUNK_LEN as u64,
-            0,
-            "offset ({offset}) must be a chunk boundary (divisible by {CHUNK_LEN})",
-        );
+        match self.try_set_input_offset(offset) {
+            Ok(hasher) => hasher,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    fn try_set_input_offset(&mut self, offset: u64) -> Result<&mut Hasher, Error> {
+        if self.count() != 0 {
+            return Err(Error(ErrorInner::AlreadyAcceptedInput));
+        }
+        let offset_in_chunk = offset % CHUNK_LEN as u64;
+        if offset_in_chunk != 0 {
+            return Err(Error(ErrorInner::Unaligned { offset }));
+        }
         let counter = offset / CHUNK_LEN as u64;
         self.chunk_state.chunk_counter = counter;
         self.initial_chunk_counter = counter;
-        self
+        Ok(self)
+    }
+
+    fn try_update(&mut self, input: &[u8]) -> Result<&mut Hasher, Error> {
+        check_subtree_len(self, input.len() as u64)?;
+        Ok(self.update(input))
     }
 
     fn finalize_non_root(&self) -> ChainingValue {
-        assert_ne!(self.count(), 0, "empty subtrees are never valid");
-        self.final_output().chaining_value()
+        match self.try_finalize_non_root() {
+            Ok(chaining_value) => chaining_value,
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    fn try_finalize_non_root(&self) -> Result<ChainingValue, Error> {
+        if self.count() == 0 {
+            return Err(Error(ErrorInner::Empty));
+        }
+        Ok(self.final_output().chaining_value())
+    }
+
+    fn try_finalize(&self) -> Result<crate::Hash, Error> {
+        check_root(self)?;
+        Ok(self.final_output().root_hash())
+    }
+
+    fn try_finalize_xof(&self) -> Result<crate::OutputReader, Error> {
+        check_root(self)?;
+        Ok(crate::OutputReader::new(self.final_output()))
+    }
+}
+
+// Check that `input_len` more bytes fit in the subtree, if this is a subtree hasher from
+// set_input_offset.
+pub(crate) fn check_subtree_len(hasher: &Hasher, input_len: u64) -> Result<(), Error> {
+    let offset = hasher.initial_chunk_counter * CHUNK_LEN as u64;
+    match max_subtree_len(offset) {
+        Some(max) if input_len > max - hasher.count() => Err(Error(ErrorInner::TooLong {
+            offset,
+            max,
+            len: input_len,
+        })),
+        _ => Ok(()),
+    }
+}
+
+// Check that the hasher wasn't configured with a non-zero input offset, before finalizing the
+// root.
+pub(crate) fn check_root(hasher: &Hasher) -> Result<(), Error> {
+    if hasher.initial_chunk_counter != 0 {
+        return Err(Error(ErrorInner::NonRootOffset {
+            offset: hasher.initial_chunk_counter * CHUNK_LEN as u64,
+        }));
     }
+    Ok(())
 }
 
+/// The error type for the fallible [`HasherExt`] methods, like
+/// [`try_set_input_offset`](HasherExt::try_set_input_offset)
+///
+/// Each error corresponds to a tree invariant that the infallible methods check with a panic.
+/// The `.to_string()` representation of this error currently describes which invariant was
+/// broken, with the same text as those panics. This is to help with logging and debugging, but it
+/// isn't a stable API detail, and it may change at any time.
+#[derive(Clone, Debug)]
+pub struct Error(ErrorInner);
+
+#[derive(Clone, Debug)]
+enum ErrorInner {
+    AlreadyAcceptedInput,
+    Unaligned { offset: u64 },
+    TooLong { offset: u64, max: u64, len: u64 },
+    Empty,
+    NonRootOffset { offset: u64 },
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self.0 {
+            ErrorInner::AlreadyAcceptedInput => write!(f, "hasher has already accepted input"),
+            ErrorInner::Unaligned { offset } => write!(
+                f,
+                "offset ({offset}) must be a chunk boundary (divisible by {CHUNK_LEN})",
+            ),
+            ErrorInner::TooLong { offset, max, len } => write!(
+                f,
+                "the subtree starting at {offset} contains at most {max} bytes (found {len})",
+            ),
+            ErrorInner::Empty => write!(f, "empty subtrees are never valid"),
+            ErrorInner::NonRootOffset { offset } => write!(
+                f,
+                "set_input_offset must be used with finalize_non_root (offset {offset})",
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
 /// The maximum length of a subtree in bytes, given its starting offset in bytes
 ///
 /// If you try to hash more than this many bytes as one subtree, you'll end up merging parent nodes
 /// that shouldn't be merged, and your output will be garbage. [`Hasher::update`] will currently
-/// panic in this case, but this is not guaranteed.
+/// panic in this case, but this is not guaranteed. [`HasherExt::try_update`] returns an error.
 ///
 /// For input offset zero (the default), there is no maximum length, and this function returns
 /// `None`. For all other offsets it returns `Some`. Note that valid offsets must be a multiple of
@@ -411,7 +581,16 @@ pub enum Mode<'a> {
 }
 
 impl<'a> Mode<'a> {
-    fn key_words(&self) -> CVWords {
+    /// Which kind of mode this is, without the key. See [`ModeKind`].
+    pub fn kind(&self) -> ModeKind {
+        match self {
+            Mode::Hash => ModeKind::Hash,
+            Mode::KeyedHash(_) => ModeKind::KeyedHash,
+            Mode::DeriveKeyMaterial(_) => ModeKind::DeriveKeyMaterial,
+        }
+    }
+
+    pub(crate) fn key_words(&self) -> CVWords {
         match self {
             Mode::Hash => *IV,
             Mode::KeyedHash(key) => crate::platform::words_from_le_bytes_32(key),
@@ -419,7 +598,7 @@ impl<'a> Mode<'a> {
         }
     }
 
-    fn flags_byte(&self) -> u8 {
+    pub(crate) fn flags_byte(&self) -> u8 {
         match self {
             Mode::Hash => 0,
             Mode::KeyedHash(_) => crate::KEYED_HASH,
@@ -428,6 +607,21 @@ impl<'a> Mode<'a> {
     }
 }
 
+/// The kind of a [`Mode`], without its key, returned by [`Mode::kind`]
+///
+/// This is what a [`SubtreeResult`] records, so that results computed in the wrong mode can be
+/// caught without sending keys around.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub enum ModeKind {
+    /// [`Mode::Hash`]
+    Hash,
+    /// [`Mode::KeyedHash`]
+    KeyedHash,
+    /// [`Mode::DeriveKeyMaterial`]
+    DeriveKeyMaterial,
+}
+
 /// "Chaining value" is the academic term for a non-root or non-final hash.
 ///
 /// Besides just sounding fancy, it turns out there are [security
@@ -532,6 +726,437 @@ pub fn merge_subtrees_root_xof(
     crate::OutputReader::new(merge_subtrees_inner(left_child, right_child, mode))
 }
 
+/// Merge a whole level of sibling chaining values in place, and return the length of the next
+/// level up.
+///
+/// Each pair of chaining values, `chaining_values[2 * i]` and `chaining_values[2 * i + 1]`, is
+/// merged into a parent chaining value, which is written to `chaining_values[i]`. If there's an
+/// odd chaining value left over at the end, it moves up to the next level unchanged, so the
+/// returned length is `chaining_values.len().div_ceil(2)`. The rest of the slice after that is
+/// left in an unspecified state. This gives the same results as calling
+/// [`merge_subtrees_non_root`] on each pair, but it compresses as many parent nodes in parallel as
+/// the SIMD instruction set allows, which is much faster for long levels.
+///
+/// As with [`merge_subtrees_non_root`], it's up to you to make sure that the chaining values are
+/// siblings in a valid tree. That's the case if they're all subtrees of the same power-of-two
+/// number of chunks, in order, except that the last one can be shorter. (See
+/// [`merge_levels_root`] for an example.) It's also up to you to make sure that a level of two
+/// chaining values isn't merged here, because their parent is the root.
+pub fn merge_level_non_root(chaining_values: &mut [ChainingValue], mode: Mode) -> usize {
+    let key = mode.key_words();
+    let flags = mode.flags_byte();
+    let platform = Platform::detect();
+    let mut parents = [0; MAX_SIMD_DEGREE_OR_2 * OUT_LEN];
+    let mut next_len = 0;
+    // Parents are always written behind the children we've already read, so this works in place.
+    // Each batch is as many children as compress_parents_parallel accepts.
+    while 2 * next_len < chaining_values.len() {
+        let children = &chaining_values[2 * next_len..];
+        let children = &children[..cmp::min(children.len(), 2 * MAX_SIMD_DEGREE_OR_2)];
+        if children.len() == 1 {
+            // The odd child out moves up.
+            chaining_values[next_len] = chaining_values[2 * next_len];
+            next_len += 1;
+            continue;
+        }
+        let num_parents = crate::compress_parents_parallel(
+            children.as_flattened(),
+            &key,
+            flags,
+            platform,
+            &mut parents,
+        );
+        for parent in parents.chunks_exact(OUT_LEN).take(num_parents) {
+            chaining_values[next_len].copy_from_slice(parent);
+            next_len += 1;
+        }
+    }
+    next_len
+}
+
+fn merge_levels_inner(chaining_values: &mut [ChainingValue], mode: Mode) -> crate::Output {
+    assert!(
+        chaining_values.len() >= 2,
+        "the root is a parent node, with at least two children",
+    );
+    let mut len = chaining_values.len();
+    while len > 2 {
+        len = merge_level_non_root(&mut chaining_values[..len], mode);
+    }
+    merge_subtrees_inner(&chaining_values[0], &chaining_values[1], mode)
+}
+
+/// Merge a list of sibling chaining values all the way up to the root hash.
+///
+/// This calls [`merge_level_non_root`] repeatedly, until there are only two chaining values left,
+/// and then it merges those with [`merge_subtrees_root`]. The chaining values must be subtrees of
+/// the same power-of-two number of chunks, in order, except that the last one can be shorter, and
+/// together they must cover the whole input. The slice is used as scratch space, and its contents
+/// are unspecified afterwards.
+///
+/// # Panics
+///
+/// Panics if there are fewer than two chaining values. Inputs of [`CHUNK_LEN`] or less don't
+/// produce any parent nodes, and their root hash has to come from [`Hasher::finalize`].
+///
+/// # Example
+///
+/// ```
+/// use blake3::hazmat::{merge_levels_root, ChainingValue, HasherExt, Mode};
+/// use blake3::{Hasher, CHUNK_LEN};
+///
+/// // Hash an input in groups of 4 chunks, as separate workers might.
+/// let input = vec![42; 1_000_000];
+/// let group_len = 4 * CHUNK_LEN;
+/// let mut chaining_values: Vec<ChainingValue> = input
+///     .chunks(group_len)
+///     .enumerate()
+///     .map(|(i, group)| {
+///         Hasher::new()
+///             .set_input_offset((i * group_len) as u64)
+///             .update(group)
+///             .finalize_non_root()
+///     })
+///     .collect();
+///
+/// let root_hash = merge_levels_root(&mut chaining_values, Mode::Hash);
+/// assert_eq!(root_hash, blake3::hash(&input));
+/// ```
+pub fn merge_levels_root(chaining_values: &mut [ChainingValue], mode: Mode) -> crate::Hash {
+    merge_levels_inner(chaining_values, mode).root_hash()
+}
+
+/// Like [`merge_levels_root`], but returns an [`OutputReader`](crate::OutputReader) for extended
+/// output.
+pub fn merge_levels_root_xof(
+    chaining_values: &mut [ChainingValue],
+    mode: Mode,
+) -> crate::OutputReader {
+    crate::OutputReader::new(merge_levels_inner(chaining_values, mode))
+}
+
+/// An incremental hasher for a single chunk, in any [`Mode`] and at any chunk counter
+///
+/// Most callers should hash chunks with [`HasherExt::set_input_offset`] and
+/// [`finalize_non_root`](HasherExt::finalize_non_root), which work for whole subtrees too.
+/// `ChunkHasher` is for callers who manage their own layout one chunk at a time, and it replaces
+/// the deprecated `guts::ChunkState`, which only supported [`Mode::Hash`]. The chunk counter is the
+/// chunk's index in the whole input, so the chunk at input offset `n * CHUNK_LEN` has counter `n`.
+/// To hash many whole chunks at once with SIMD parallelism, use [`hash_chunks`].
+///
+/// A chunk hasher always produces a chaining value. An input of one chunk or less has no parent
+/// nodes, and its root hash has to come from [`Hasher::finalize`].
+///
+/// # Example
+///
+/// ```
+/// use blake3::hazmat::{ChunkHasher, HasherExt, Mode};
+/// use blake3::{CHUNK_LEN, Hasher};
+///
+/// let key = [7; 32];
+/// let chunk = [42; CHUNK_LEN];
+/// let mut chunk_hasher = ChunkHasher::new(Mode::KeyedHash(&key), 5);
+/// chunk_hasher.update(&chunk[..100]);
+/// chunk_hasher.update(&chunk[100..]);
+/// let expected = Hasher::new_keyed(&key)
+///     .set_input_offset(5 * CHUNK_LEN as u64)
+///     .update(&chunk)
+///     .finalize_non_root();
+/// assert_eq!(chunk_hasher.finalize_non_root(), expected);
+/// ```
+#[derive(Clone)]
+pub struct ChunkHasher {
+    state: crate::ChunkState,
+}
+
+impl ChunkHasher {
+    /// Construct a new `ChunkHasher` for the chunk with index `chunk_counter`, in the given mode.
+    pub fn new(mode: Mode, chunk_counter: u64) -> Self {
+        Self {
+            state: crate::ChunkState::new(
+                &mode.key_words(),
+                chunk_counter,
+                mode.flags_byte(),
+                Platform::detect(),
+            ),
+        }
+    }
+
+    /// The number of bytes hashed so far.
+    pub fn len(&self) -> usize {
+        self.state.count()
+    }
+
+    /// Returns `true` if no bytes have been hashed yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add input bytes to the chunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the total input would be longer than [`CHUNK_LEN`].
+    pub fn update(&mut self, input: &[u8]) -> &mut Self {
+        assert!(
+            input.len() <= CHUNK_LEN - self.len(),
+            "a chunk contains at most {CHUNK_LEN} bytes",
+        );
+        self.state.update(input);
+        self
+    }
+
+    /// Return the chaining value of the chunk.
+    ///
+    /// This method is idempotent, and calling it multiple times will give the same result.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the chunk is empty. An empty chunk is only valid as the root of an empty input.
+    pub fn finalize_non_root(&self) -> ChainingValue {
+        assert!(!self.is_empty(), "empty chunks below the root");
+        self.state.output().chaining_value()
+    }
+}
+
+// Don't derive(Debug), because the state may be secret.
+impl core::fmt::Debug for ChunkHasher {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        f.debug_struct("ChunkHasher")
+            .field("len", &self.len())
+            .field("chunk_counter", &self.state.chunk_counter)
+            .finish()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for ChunkHasher {
+    fn zeroize(&mut self) {
+        // Destructuring to trigger compile error as a reminder to update this impl.
+        let Self { state } = self;
+        state.zeroize();
+    }
+}
+
+/// Hash whole chunks with SIMD parallelism, and write their chaining values to `out`
+///
+/// `chunks[i]` is the chunk with index `chunk_counter + i` in the whole input, and its chaining
+/// value goes in `out[i]`. This gives the same results as a [`ChunkHasher`] for each chunk, but
+/// it hashes as many chunks at once as the SIMD instruction set allows (16 with AVX-512). Any
+/// number of chunks is allowed, and they're hashed in batches of that size. A short final
+/// chunk has to go through [`ChunkHasher`].
+///
+/// # Panics
+///
+/// Panics if `out` isn't the same length as `chunks`.
+///
+/// # Example
+///
+/// ```
+/// use blake3::hazmat::{ChainingValue, ChunkHasher, Mode, hash_chunks};
+/// use blake3::CHUNK_LEN;
+///
+/// let input = [42; 8 * CHUNK_LEN];
+/// let chunks: Vec<&[u8; CHUNK_LEN]> = input
+///     .chunks_exact(CHUNK_LEN)
+///     .map(|chunk| chunk.try_into().unwrap())
+///     .collect();
+/// let mut cvs = [[0; 32]; 8];
+/// hash_chunks(&chunks, 100, Mode::Hash, &mut cvs);
+///
+/// let last: ChainingValue = ChunkHasher::new(Mode::Hash, 107)
+///     .update(&input[7 * CHUNK_LEN..])
+///     .finalize_non_root();
+/// assert_eq!(cvs[7], last);
+/// ```
+pub fn hash_chunks(
+    chunks: &[&[u8; CHUNK_LEN]],
+    chunk_counter: u64,
+    mode: Mode,
+    out: &mut [ChainingValue],
+) {
+    assert_eq!(chunks.len(), out.len(), "one chaining value per chunk");
+    let key = mode.key_words();
+    let platform = Platform::detect();
+    for (i, (chunks, out)) in chunks
+        .chunks(MAX_SIMD_DEGREE)
+        .zip(out.chunks_mut(MAX_SIMD_DEGREE))
+        .enumerate()
+    {
+        platform.hash_many(
+            chunks,
+            &key,
+            chunk_counter + (i * MAX_SIMD_DEGREE) as u64,
+            crate::IncrementCounter::Yes,
+            mode.flags_byte(),
+            crate::CHUNK_START,
+            crate::CHUNK_END,
+            out.as_flattened_mut(),
+        );
+    }
+}
+
+/// The domain separation flag for the first block of a chunk. See [`compress`].
+pub const CHUNK_START: u8 = crate::CHUNK_START;
+/// The domain separation flag for the last block of a chunk. See [`compress`].
+pub const CHUNK_END: u8 = crate::CHUNK_END;
+/// The domain separation flag for parent nodes. See [`compress`].
+pub const PARENT: u8 = crate::PARENT;
+/// The domain separation flag for the root node, chunk or parent. See [`compress`].
+pub const ROOT: u8 = crate::ROOT;
+/// The domain separation flag for every compression in the [`keyed_hash`](crate::keyed_hash)
+/// mode. See [`compress`].
+pub const KEYED_HASH: u8 = crate::KEYED_HASH;
+/// The domain separation flag for hashing the context string in the
+/// [`derive_key`](crate::derive_key) mode. See [`compress`].
+pub const DERIVE_KEY_CONTEXT: u8 = crate::DERIVE_KEY_CONTEXT;
+/// The domain separation flag for hashing the key material in the
+/// [`derive_key`](crate::derive_key) mode. See [`compress`].
+pub const DERIVE_KEY_MATERIAL: u8 = crate::DERIVE_KEY_MATERIAL;
+
+/// The BLAKE3 compression function, returning the new chaining value
+///
+/// This compresses one 64-byte `block`, of which the first `block_len` bytes are input and the
+/// rest must be zero, into the chaining value `cv`. `counter` is the chunk counter for chunk
+/// blocks, or zero for parent nodes, and `flags` is any combination of the flag constants in this
+/// module: [`CHUNK_START`], [`CHUNK_END`], [`PARENT`], [`ROOT`], and the mode flags
+/// [`KEYED_HASH`], [`DERIVE_KEY_CONTEXT`], and [`DERIVE_KEY_MATERIAL`]. The compression
+/// function is dispatched to the fastest implementation that the current CPU supports, like the
+/// rest of this crate.
+///
+/// This is the lowest level of the BLAKE3 tree, and nothing here checks that the inputs form a
+/// valid tree. It's useful for building new constructions on top of the compression function. To
+/// hash chunks and subtrees of a standard BLAKE3 tree, use [`HasherExt`], [`ChunkHasher`], and the
+/// `merge_subtrees_*` functions instead. The `block_len` must be at most [`BLOCK_LEN`], or the
+/// output will be garbage that no other BLAKE3 implementation agrees with.
+///
+/// # Example
+///
+/// ```
+/// use blake3::hazmat::{CHUNK_END, CHUNK_START, ROOT, compress};
+///
+/// // The IV, the first 8 words of SHA-256's IV, is the key for the default hash mode.
+/// let iv_words: [u32; 8] = [
+///     0x6A09E667, 0xBB67AE85, 0x3C6EF372, 0xA54FF53A, 0x510E527F, 0x9B05688C, 0x1F83D9AB,
+///     0x5BE0CD19,
+/// ];
+/// let mut iv = [0; 32];
+/// for (bytes, word) in iv.chunks_exact_mut(4).zip(iv_words) {
+///     bytes.copy_from_slice(&word.to_le_bytes());
+/// }
+///
+/// // The hash of a one-block input is the root compression of that block.
+/// let mut block = [0; 64];
+/// block[..3].copy_from_slice(b"foo");
+/// let hash = compress(&iv, &block, 3, 0, CHUNK_START | CHUNK_END | ROOT);
+/// assert_eq!(hash, *blake3::hash(b"foo").as_bytes());
+/// ```
+pub fn compress(
+    cv: &ChainingValue,
+    block: &[u8; BLOCK_LEN],
+    block_len: u8,
+    counter: u64,
+    flags: u8,
+) -> ChainingValue {
+    let mut cv_words = crate::platform::words_from_le_bytes_32(cv);
+    Platform::detect().compress_in_place(&mut cv_words, block, block_len, counter, flags);
+    crate::platform::le_bytes_from_words_32(&cv_words)
+}
+
+/// Like [`compress`], but return the full 64-byte output of the compression function
+///
+/// The first 32 bytes are the same as [`compress`]. The root node of a BLAKE3 tree produces
+/// extended output by compressing it repeatedly with [`ROOT`] and an incrementing `counter`, and
+/// concatenating these 64-byte outputs.
+pub fn compress_xof(
+    cv: &ChainingValue,
+    block: &[u8; BLOCK_LEN],
+    block_len: u8,
+    counter: u64,
+    flags: u8,
+) -> [u8; 2 * OUT_LEN] {
+    let cv_words = crate::platform::words_from_le_bytes_32(cv);
+    Platform::detect().compress_xof(&cv_words, block, block_len, counter, flags)
+}
+
+/// A `const fn` version of [`compress`], for use at compile time
+///
+/// This gives the same result as [`compress`], but it always uses the portable implementation,
+/// so at runtime prefer [`compress`].
+///
+/// # Example
+///
+/// ```
+/// use blake3::BLOCK_LEN;
+/// use blake3::hazmat::{CHUNK_START, ChainingValue, compress, const_compress};
+///
+/// const CV: ChainingValue = const_compress(&[0; 32], &[0; BLOCK_LEN], 0, 0, CHUNK_START);
+/// assert_eq!(CV, compress(&[0; 32], &[0; BLOCK_LEN], 0, 0, CHUNK_START));
+/// ```
+pub const fn const_compress(
+    cv: &ChainingValue,
+    block: &[u8; BLOCK_LEN],
+    block_len: u8,
+    counter: u64,
+    flags: u8,
+) -> ChainingValue {
+    crate::const_fn::compress(cv, block, block_len, counter, flags)
+}
+
+/// Compress many inputs of the same whole number of blocks in parallel, and write their chaining
+/// values to `out`
+///
+/// Each input is compressed one 64-byte block at a time, starting from the chaining value `key`,
+/// with `flags` on every block, `flags | flags_start` on the first block, and
+/// `flags | flags_end` on the last block. If `increment_counter` is true, `inputs[i]` uses the
+/// counter `counter + i`, which is what chunks need. Otherwise every input uses `counter`, which
+/// is what parent nodes need (with a counter of zero). The chaining value of `inputs[i]` goes in
+/// `out[i]`.
+///
+/// For example, [`hash_chunks`] is this function with chunk-sized inputs, [`CHUNK_START`], and
+/// [`CHUNK_END`], and [`merge_level_non_root`] is this function with 64-byte parent nodes and
+/// [`PARENT`]. The inputs are compressed with as much SIMD parallelism as the current CPU
+/// supports (16 at a time with AVX-512). As with [`compress`], nothing here checks that the
+/// inputs form a valid tree.
+///
+/// # Panics
+///
+/// Panics if `N` is zero or isn't a multiple of [`BLOCK_LEN`], or if `out` isn't the same length
+/// as `inputs`.
+#[allow(clippy::too_many_arguments)]
+pub fn hash_many<const N: usize>(
+    inputs: &[&[u8; N]],
+    key: &ChainingValue,
+    counter: u64,
+    increment_counter: bool,
+    flags: u8,
+    flags_start: u8,
+    flags_end: u8,
+    out: &mut [ChainingValue],
+) {
+    let offset_in_block = N % BLOCK_LEN;
+    assert!(
+        N != 0 && offset_in_block == 0,
+        "inputs must be one or more whole blocks",
+    );
+    assert_eq!(inputs.len(), out.len(), "one chaining value per input");
+    let increment_counter = if increment_counter {
+        crate::IncrementCounter::Yes
+    } else {
+        crate::IncrementCounter::No
+    };
+    Platform::detect().hash_many(
+        inputs,
+        &crate::platform::words_from_le_bytes_32(key),
+        counter,
+        increment_counter,
+        flags,
+        flags_start,
+        flags_end,
+        out.as_flattened_mut(),
+    );
+}
+
 /// An alias to distinguish [`hash_derive_key_context`] outputs from other keys.
 pub type ContextKey = [u8; KEY_LEN];
 
@@ -544,6 +1169,9 @@ pub type ContextKey = [u8; KEY_LEN];
 /// [`new_from_context_key`](HasherExt::new_from_context_key) and [`Mode::DeriveKeyMaterial`]
 /// (together with the merge subtree functions).
 ///
+/// To compute the [`ContextKey`] at compile time instead, see [`const_hash_derive_key_context`]
+/// and the [`context_key!`](crate::context_key) macro.
+///
 /// # Example
 ///
 /// ```
@@ -558,15 +1186,89 @@ pub type ContextKey = [u8; KEY_LEN];
 /// assert_eq!(derived_key, blake3::derive_key("foo", b"bar"));
 /// ```
 pub fn hash_derive_key_context(context: &str) -> ContextKey {
-    crate::hash_all_at_once::<crate::join::SerialJoin>(
+    crate::hash_all_at_once(
         context.as_bytes(),
         IV,
         crate::DERIVE_KEY_CONTEXT,
+        &crate::join::SerialJoin,
     )
     .root_hash()
     .0
 }
 
+/// A `const fn` version of [`hash_derive_key_context`], for computing a [`ContextKey`] at compile
+/// time.
+///
+/// This gives the same result as [`hash_derive_key_context`], and you can hardcode it in a
+/// `const` item. The [`context_key!`](crate::context_key) macro does that for a string literal,
+/// and it also checks the context string at compile time. Like [`const_hash`](crate::const_hash),
+/// this function only uses the portable implementation, which is fine for context strings of
+/// typical length.
+///
+/// # Example
+///
+/// ```
+/// use blake3::Hasher;
+/// use blake3::hazmat::{ContextKey, HasherExt};
+///
+/// const CONTEXT_KEY: ContextKey = blake3::hazmat::const_hash_derive_key_context("foo");
+/// let mut hasher = Hasher::new_from_context_key(&CONTEXT_KEY);
+/// hasher.update(b"bar");
+/// let derived_key = *hasher.finalize().as_bytes();
+///
+/// assert_eq!(derived_key, blake3::derive_key("foo", b"bar"));
+/// ```
+pub const fn const_hash_derive_key_context(context: &str) -> ContextKey {
+    crate::const_fn::hash_all_at_once(context.as_bytes(), IV, crate::DERIVE_KEY_CONTEXT)
+}
+
+/// Hash a [`derive_key`](crate::derive_key) context string at compile time, and return the
+/// [`ContextKey`].
+///
+/// The argument must be a string literal, which enforces the requirement that the context string
+/// is hardcoded. An empty context string is a compile error. The result is a constant, so this is
+/// equivalent to assigning
+/// [`const_hash_derive_key_context`](crate::hazmat::const_hash_derive_key_context) to a `const`
+/// item yourself.
+///
+/// # Example
+///
+/// ```
+/// use blake3::Hasher;
+/// use blake3::hazmat::HasherExt;
+///
+/// let context_key = blake3::context_key!("example.com 2019-12-25 16:18:03 session tokens v1");
+/// let derived_key = Hasher::new_from_context_key(&context_key)
+///     .update(b"key material, not a password")
+///     .finalize();
+/// assert_eq!(
+///     *derived_key.as_bytes(),
+///     blake3::derive_key(
+///         "example.com 2019-12-25 16:18:03 session tokens v1",
+///         b"key material, not a password",
+///     ),
+/// );
+/// ```
+///
+/// ```compile_fail
+/// let context_key = blake3::context_key!("");
+/// ```
+///
+/// [`ContextKey`]: crate::hazmat::ContextKey
+#[macro_export]
+macro_rules! context_key {
+    ($context:literal) => {{
+        const CONTEXT: &str = $context;
+        const _: () = assert!(
+            !CONTEXT.is_empty(),
+            "the derive_key context string must not be empty",
+        );
+        const CONTEXT_KEY: $crate::hazmat::ContextKey =
+            $crate::hazmat::const_hash_derive_key_context(CONTEXT);
+        CONTEXT_KEY
+    }};
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -611,6 +1313,56 @@ mod test {
             .finalize_xof();
     }
 
+    #[test]
+    fn test_try_methods() {
+        let mut hasher = Hasher::new();
+        let _err = hasher.try_set_input_offset(1).unwrap_err();
+        #[cfg(feature = "std")]
+        assert_eq!(
+            _err.to_string(),
+            "offset (1) must be a chunk boundary (divisible by 1024)",
+        );
+        let _err = hasher.try_finalize_non_root().unwrap_err();
+        #[cfg(feature = "std")]
+        assert_eq!(_err.to_string(), "empty subtrees are never valid");
+
+        hasher.try_set_input_offset(2 * CHUNK_LEN as u64).unwrap();
+        let _err = hasher.try_update(&[0; 2 * CHUNK_LEN + 1]).unwrap_err();
+        #[cfg(feature = "std")]
+        assert_eq!(
+            _err.to_string(),
+            "the subtree starting at 2048 contains at most 2048 bytes (found 2049)",
+        );
+        // The failed update didn't change anything.
+        assert_eq!(hasher.count(), 0);
+        hasher.try_update(&[0; CHUNK_LEN]).unwrap();
+        let _err = hasher.try_set_input_offset(0).unwrap_err();
+        #[cfg(feature = "std")]
+        assert_eq!(_err.to_string(), "hasher has already accepted input");
+        assert!(hasher.try_update(&[0; CHUNK_LEN + 1]).is_err());
+        hasher.try_update(&[0; CHUNK_LEN]).unwrap();
+
+        let _err = hasher.try_finalize().unwrap_err();
+        #[cfg(feature = "std")]
+        assert_eq!(
+            _err.to_string(),
+            "set_input_offset must be used with finalize_non_root (offset 2048)",
+        );
+        assert!(hasher.try_finalize_xof().is_err());
+        let expected = Hasher::new()
+            .set_input_offset(2 * CHUNK_LEN as u64)
+            .update(&[0; 2 * CHUNK_LEN])
+            .finalize_non_root();
+        assert_eq!(hasher.try_finalize_non_root().unwrap(), expected);
+
+        let mut root_hasher = Hasher::new();
+        root_hasher.try_update(b"foo").unwrap();
+        assert_eq!(root_hasher.try_finalize().unwrap(), crate::hash(b"foo"));
+        let mut xof = [0; 32];
+        root_hasher.try_finalize_xof().unwrap().fill(&mut xof);
+        assert_eq!(xof, *crate::hash(b"foo").as_bytes());
+    }
+
     #[test]
     fn test_grouped_hash() {
         const MAX_CHUNKS: usize = (crate::test::TEST_CASES_MAX + 1) / CHUNK_LEN;
@@ -642,6 +1394,7 @@ mod test {
                     chaining_values.push(subtree_cv);
                     subtree_offset += take;
                 }
+                let mut leaves = chaining_values.clone();
 
                 // Compress all the chaining_values together, layer by layer.
                 assert!(chaining_values.len() >= 2);
@@ -666,10 +1419,47 @@ mod test {
                 let root_hash =
                     merge_subtrees_root(&chaining_values[0], &chaining_values[1], Mode::Hash);
                 assert_eq!(expected_hash, root_hash);
+                assert_eq!(expected_hash, merge_levels_root(&mut leaves, Mode::Hash));
             }
         }
     }
 
+    #[test]
+    fn test_merge_level() {
+        // Enough chaining values for several full batches, plus an odd one out.
+        const LEN: usize = 6 * MAX_SIMD_DEGREE_OR_2 + 1;
+        let key = &[44; 32];
+        let cx_key = hash_derive_key_context("foo");
+        for mode in [
+            Mode::Hash,
+            Mode::KeyedHash(key),
+            Mode::DeriveKeyMaterial(&cx_key),
+        ] {
+            for len in [0, 1, 2, 3, 2 * MAX_SIMD_DEGREE_OR_2, LEN - 1, LEN] {
+                let mut level = [[0; OUT_LEN]; LEN];
+                for (i, cv) in level.iter_mut().enumerate() {
+                    *cv = Hasher::new().update(&i.to_le_bytes()).finalize_non_root();
+                }
+                let mut expected = level;
+                for i in 0..len / 2 {
+                    expected[i] = merge_subtrees_non_root(&level[2 * i], &level[2 * i + 1], mode);
+                }
+                if len % 2 == 1 {
+                    expected[len / 2] = level[len - 1];
+                }
+                let next_len = merge_level_non_root(&mut level[..len], mode);
+                assert_eq!(next_len, len.div_ceil(2));
+                assert_eq!(level[..next_len], expected[..next_len]);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_merge_levels_root_one_cv_should_panic() {
+        merge_levels_root(&mut [[0; OUT_LEN]], Mode::Hash);
+    }
+
     #[test]
     fn test_keyed_hash_xof() {
         let group0 = &[42; 4096];
@@ -693,6 +1483,10 @@ mod test {
             .finalize_non_root();
         merge_subtrees_root_xof(&left, &right, Mode::KeyedHash(&key)).fill(&mut hazmat_output);
         assert_eq!(expected_output, hazmat_output);
+
+        let mut levels_output = [0; 100];
+        merge_levels_root_xof(&mut [left, right], Mode::KeyedHash(key)).fill(&mut levels_output);
+        assert_eq!(expected_output, levels_output);
     }
 
     #[test]
@@ -713,4 +1507,156 @@ mod test {
         let derived_key = merge_subtrees_root(&left, &right, Mode::DeriveKeyMaterial(&cx_key)).0;
         assert_eq!(expected, derived_key);
     }
+
+    #[test]
+    fn test_chunk_hasher() {
+        let mut input = [0; CHUNK_LEN];
+        crate::test::paint_test_input(&mut input);
+        let cx_key = hash_derive_key_context("test_chunk_hasher");
+        for mode in [
+            Mode::Hash,
+            Mode::KeyedHash(&[7; 32]),
+            Mode::DeriveKeyMaterial(&cx_key),
+        ] {
+            for counter in [0, 1, 6, 1 << 40] {
+                for len in [1, 63, 64, 65, 500, CHUNK_LEN] {
+                    let expected = Hasher::new_internal(&mode.key_words(), mode.flags_byte())
+                        .set_input_offset(counter * CHUNK_LEN as u64)
+                        .update(&input[..len])
+                        .finalize_non_root();
+                    let mut chunk_hasher = ChunkHasher::new(mode, counter);
+                    assert!(chunk_hasher.is_empty());
+                    chunk_hasher.update(&input[..len / 2]);
+                    chunk_hasher.update(&input[len / 2..len]);
+                    assert_eq!(chunk_hasher.len(), len);
+                    assert_eq!(chunk_hasher.finalize_non_root(), expected);
+                    if let Mode::Hash = mode {
+                        #[allow(deprecated)]
+                        let guts_cv = crate::guts::ChunkState::new(counter)
+                            .update(&input[..len])
+                            .finalize(false);
+                        assert_eq!(guts_cv, expected);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_hash_chunks() {
+        const MAX_CHUNKS: usize = 2 * MAX_SIMD_DEGREE + 1;
+        let mut input = [0; MAX_CHUNKS * CHUNK_LEN];
+        crate::test::paint_test_input(&mut input);
+        let chunks: [&[u8; CHUNK_LEN]; MAX_CHUNKS] =
+            core::array::from_fn(|i| arrayref::array_ref!(input, i * CHUNK_LEN, CHUNK_LEN));
+        let key = [8; 32];
+        for mode in [Mode::Hash, Mode::KeyedHash(&key)] {
+            for num_chunks in 0..=MAX_CHUNKS {
+                let mut cvs = [[0; OUT_LEN]; MAX_CHUNKS];
+                hash_chunks(&chunks[..num_chunks], 3, mode, &mut cvs[..num_chunks]);
+                for i in 0..num_chunks {
+                    let expected = ChunkHasher::new(mode, 3 + i as u64)
+                        .update(chunks[i])
+                        .finalize_non_root();
+                    assert_eq!(cvs[i], expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_chunk_hasher_too_long_should_panic() {
+        ChunkHasher::new(Mode::Hash, 0)
+            .update(&[0; CHUNK_LEN])
+            .update(&[0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_chunk_hasher_empty_should_panic() {
+        ChunkHasher::new(Mode::Hash, 1).finalize_non_root();
+    }
+
+    #[test]
+    fn test_compress() {
+        let mut block = [0; BLOCK_LEN];
+        crate::test::paint_test_input(&mut block);
+        let cv = [7; OUT_LEN];
+        let cv_words = crate::platform::words_from_le_bytes_32(&cv);
+        for flags in [
+            0,
+            CHUNK_START | CHUNK_END | ROOT,
+            PARENT | KEYED_HASH,
+            DERIVE_KEY_MATERIAL,
+        ] {
+            let mut expected_words = cv_words;
+            crate::portable::compress_in_place(&mut expected_words, &block, 42, 5, flags);
+            let expected = crate::platform::le_bytes_from_words_32(&expected_words);
+            assert_eq!(compress(&cv, &block, 42, 5, flags), expected);
+            assert_eq!(const_compress(&cv, &block, 42, 5, flags), expected);
+            let expected_xof = crate::portable::compress_xof(&cv_words, &block, 42, 5, flags);
+            let xof = compress_xof(&cv, &block, 42, 5, flags);
+            assert_eq!(xof, expected_xof);
+            assert_eq!(xof[..OUT_LEN], expected);
+        }
+
+        // Rebuild the keyed hash of a three-chunk input from compressions.
+        let key = [8; KEY_LEN];
+        let mut input = [0; 3 * CHUNK_LEN];
+        crate::test::paint_test_input(&mut input);
+        let chunks: [&[u8; CHUNK_LEN]; 3] =
+            core::array::from_fn(|i| arrayref::array_ref!(input, i * CHUNK_LEN, CHUNK_LEN));
+        let mut chunk_cvs = [[0; OUT_LEN]; 3];
+        hash_many(
+            &chunks,
+            &key,
+            0,
+            true,
+            KEYED_HASH,
+            CHUNK_START,
+            CHUNK_END,
+            &mut chunk_cvs,
+        );
+        let parent = |left: &ChainingValue, right: &ChainingValue, flags| {
+            let mut block = [0; BLOCK_LEN];
+            block[..OUT_LEN].copy_from_slice(left);
+            block[OUT_LEN..].copy_from_slice(right);
+            compress(
+                &key,
+                &block,
+                BLOCK_LEN as u8,
+                0,
+                KEYED_HASH | PARENT | flags,
+            )
+        };
+        let left_cv = parent(&chunk_cvs[0], &chunk_cvs[1], 0);
+        let root = parent(&left_cv, &chunk_cvs[2], ROOT);
+        assert_eq!(root, *crate::keyed_hash(&key, &input).as_bytes());
+
+        // Parent nodes don't increment the counter.
+        let mut parents = [[0; BLOCK_LEN]; 3];
+        crate::test::paint_test_input(parents.as_flattened_mut());
+        let parent_refs: [&[u8; BLOCK_LEN]; 3] = core::array::from_fn(|i| &parents[i]);
+        let mut parent_cvs = [[0; OUT_LEN]; 3];
+        hash_many(&parent_refs, &key, 0, false, PARENT, 0, 0, &mut parent_cvs);
+        for i in 0..3 {
+            assert_eq!(
+                parent_cvs[i],
+                compress(&key, &parents[i], BLOCK_LEN as u8, 0, PARENT),
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_hash_many_uneven_blocks_should_panic() {
+        hash_many(&[&[0; 65]], &[0; 32], 0, false, 0, 0, 0, &mut [[0; 32]]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_hash_many_empty_inputs_should_panic() {
+        hash_many(&[&[0; 0]], &[0; 32], 0, false, 0, 0, 0, &mut [[0; 32]]);
+    }
 }
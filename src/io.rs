@@ -1,11 +1,293 @@
-//! Helper functions for efficient IO.
+//! `Read` and `Write` adapters that hash data as it passes through.
+//!
+//! [`HashingReader`] and [`HashingWriter`] wrap another reader or writer, and they feed every
+//! byte that goes through them to a [`Hasher`]. They're useful when the data is going somewhere
+//! else anyway, like an upload, a decompressor, or an archive extractor, and you want its hash
+//! without reading it twice. [`VerifyingReader`] does the same and also checks the hash at EOF,
+//! returning an error if it doesn't match.
+//!
+//! This module requires the `std` Cargo feature, which is enabled by default.
+//!
+//! # Example
+//!
+//! ```
+//! # fn main() -> std::io::Result<()> {
+//! use blake3::io::{HashingReader, VerifyingReader};
+//! use std::io::prelude::*;
+//!
+//! let mut reader = HashingReader::new(&b"some input"[..]);
+//! let mut output = Vec::new();
+//! reader.read_to_end(&mut output)?;
+//! let hash = reader.hasher().finalize();
+//! assert_eq!(hash, blake3::hash(b"some input"));
+//!
+//! // A VerifyingReader only succeeds at EOF if the input matches.
+//! let mut reader = VerifyingReader::new(&b"some input"[..], hash);
+//! reader.read_to_end(&mut output)?;
+//! let mut reader = VerifyingReader::new(&b"other input"[..], hash);
+//! let error = reader.read_to_end(&mut output).unwrap_err();
+//! assert!(blake3::io::HashMismatch::is_mismatch(&error));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{Hash, Hasher};
+use core::fmt;
+use std::io;
+
+/// A reader that hashes all the bytes read through it.
+///
+/// Every successful [`read`](io::Read::read) passes the bytes it returns to the wrapped
+/// [`Hasher`]. Reading from the inner reader directly, through [`get_mut`](HashingReader::get_mut),
+/// skips the hasher.
+///
+/// See the [module level docs](index.html) for an example.
+#[derive(Clone, Debug)]
+pub struct HashingReader<R> {
+    inner: R,
+    hasher: Hasher,
+}
+
+impl<R> HashingReader<R> {
+    /// Wrap a reader with a new default [`Hasher`].
+    pub fn new(inner: R) -> Self {
+        Self::with_hasher(inner, Hasher::new())
+    }
+
+    /// Wrap a reader with the given [`Hasher`], for example one from [`Hasher::new_keyed`] or
+    /// [`Hasher::new_derive_key`], or one that's already hashed some input.
+    pub fn with_hasher(inner: R, hasher: Hasher) -> Self {
+        Self { inner, hasher }
+    }
+
+    /// The running [`Hasher`], which has seen all the bytes read so far.
+    pub fn hasher(&self) -> &Hasher {
+        &self.hasher
+    }
+
+    /// A reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// A mutable reference to the inner reader. Bytes read through this reference aren't hashed.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwrap this `HashingReader`, returning the inner reader and the [`Hasher`].
+    pub fn into_parts(self) -> (R, Hasher) {
+        (self.inner, self.hasher)
+    }
+}
+
+impl<R: io::Read> io::Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// A writer that hashes all the bytes written through it.
+///
+/// Every successful [`write`](io::Write::write) passes the bytes that the inner writer accepted to
+/// the wrapped [`Hasher`]. Bytes that the inner writer didn't accept aren't hashed, so the hash
+/// always matches what was actually written.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> std::io::Result<()> {
+/// use blake3::io::HashingWriter;
+/// use std::io::prelude::*;
+///
+/// let mut writer = HashingWriter::new(Vec::new());
+/// writer.write_all(b"some output")?;
+/// let (output, hasher) = writer.into_parts();
+/// assert_eq!(output, b"some output");
+/// assert_eq!(hasher.finalize(), blake3::hash(b"some output"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct HashingWriter<W> {
+    inner: W,
+    hasher: Hasher,
+}
+
+impl<W> HashingWriter<W> {
+    /// Wrap a writer with a new default [`Hasher`].
+    pub fn new(inner: W) -> Self {
+        Self::with_hasher(inner, Hasher::new())
+    }
+
+    /// Wrap a writer with the given [`Hasher`], for example one from [`Hasher::new_keyed`] or
+    /// [`Hasher::new_derive_key`], or one that's already hashed some input.
+    pub fn with_hasher(inner: W, hasher: Hasher) -> Self {
+        Self { inner, hasher }
+    }
+
+    /// The running [`Hasher`], which has seen all the bytes written so far.
+    pub fn hasher(&self) -> &Hasher {
+        &self.hasher
+    }
+
+    /// A reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// A mutable reference to the inner writer. Bytes written through this reference aren't
+    /// hashed.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwrap this `HashingWriter`, returning the inner writer and the [`Hasher`].
+    pub fn into_parts(self) -> (W, Hasher) {
+        (self.inner, self.hasher)
+    }
+}
+
+impl<W: io::Write> io::Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A reader that hashes all the bytes read through it and checks the hash at EOF.
+///
+/// When the inner reader reaches EOF, `VerifyingReader` finalizes its [`Hasher`] and compares
+/// the result to the expected hash, in constant time. If they don't match, the read that would've
+/// returned EOF returns an error of kind [`InvalidData`](io::ErrorKind::InvalidData) instead,
+/// with a [`HashMismatch`] as its inner error. Every later read at EOF returns the same error.
+///
+/// **Bytes read before EOF aren't verified yet.** The caller has to be prepared to discard
+/// everything it's read if the final read fails. In particular, a reader that's dropped before
+/// EOF doesn't verify anything. If you need to verify data incrementally, you need a tree
+/// encoding like [Bao](https://github.com/oconnor663/bao).
+///
+/// To verify a keyed hash (a MAC), use [`with_hasher`](VerifyingReader::with_hasher) with a
+/// [`Hasher::new_keyed`].
+///
+/// See the [module level docs](index.html) for an example.
+#[derive(Clone, Debug)]
+pub struct VerifyingReader<R> {
+    inner: HashingReader<R>,
+    expected: Hash,
+}
+
+impl<R> VerifyingReader<R> {
+    /// Wrap a reader with a new default [`Hasher`], expecting the given hash.
+    pub fn new(inner: R, expected: Hash) -> Self {
+        Self::with_hasher(inner, Hasher::new(), expected)
+    }
+
+    /// Wrap a reader with the given [`Hasher`], expecting the given hash.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # fn main() -> std::io::Result<()> {
+    /// use blake3::io::VerifyingReader;
+    /// use std::io::prelude::*;
+    ///
+    /// let key = [42; 32];
+    /// let tag = blake3::keyed_hash(&key, b"message");
+    /// let hasher = blake3::Hasher::new_keyed(&key);
+    /// let mut reader = VerifyingReader::with_hasher(&b"message"[..], hasher, tag);
+    /// let mut message = Vec::new();
+    /// reader.read_to_end(&mut message)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_hasher(inner: R, hasher: Hasher, expected: Hash) -> Self {
+        Self {
+            inner: HashingReader::with_hasher(inner, hasher),
+            expected,
+        }
+    }
+
+    /// The running [`Hasher`], which has seen all the bytes read so far.
+    pub fn hasher(&self) -> &Hasher {
+        self.inner.hasher()
+    }
+
+    /// A reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        self.inner.get_ref()
+    }
+
+    /// A mutable reference to the inner reader. Bytes read through this reference aren't hashed.
+    pub fn get_mut(&mut self) -> &mut R {
+        self.inner.get_mut()
+    }
+
+    /// Unwrap this `VerifyingReader`, returning the inner reader. Nothing is verified.
+    pub fn into_inner(self) -> R {
+        self.inner.inner
+    }
+}
+
+impl<R: io::Read> io::Read for VerifyingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n == 0 && !buf.is_empty() && self.inner.hasher().finalize() != self.expected {
+            return Err(HashMismatch::new_error());
+        }
+        Ok(n)
+    }
+}
+
+/// The inner error of the [`io::Error`] that a [`VerifyingReader`] returns at EOF when the hash
+/// doesn't match. A [`verified::Decoder`](crate::verified::Decoder) returns the same error when
+/// any part of an encoding doesn't match.
+///
+/// The error kind is [`InvalidData`](io::ErrorKind::InvalidData), which other readers use too. Use
+/// [`is_mismatch`](HashMismatch::is_mismatch) to tell this error apart.
+#[derive(Clone, Debug)]
+pub struct HashMismatch(());
+
+impl HashMismatch {
+    /// Return true if `error` came from a [`VerifyingReader`] or
+    /// [`verified::Decoder`](crate::verified::Decoder) hash mismatch.
+    pub fn is_mismatch(error: &io::Error) -> bool {
+        error
+            .get_ref()
+            .is_some_and(|inner| inner.is::<HashMismatch>())
+    }
+
+    pub(crate) fn new_error() -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, HashMismatch(()))
+    }
+}
+
+impl fmt::Display for HashMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BLAKE3 hash mismatch")
+    }
+}
+
+impl std::error::Error for HashMismatch {}
+
+// What follows are crate-private helper functions for efficient IO.
+
+// The buffer size used by copy_wide() and its async equivalents. This needs to be large enough for
+// all of this crate's SIMD implementations.
+const WIDE_BUF_LEN: usize = 65536;
 
-#[cfg(feature = "std")]
 pub(crate) fn copy_wide(
     mut reader: impl std::io::Read,
     hasher: &mut crate::Hasher,
 ) -> std::io::Result<u64> {
-    let mut buffer = [0; 65536];
+    let mut buffer = [0; WIDE_BUF_LEN];
     let mut total = 0;
     loop {
         match reader.read(&mut buffer) {
@@ -21,6 +303,124 @@ pub(crate) fn copy_wide(
     }
 }
 
+// The size and number of buffers used by copy_parallel(). Hashing a buffer with multiple threads
+// is only worth it if the buffer is large, and each buffer is a power of two, so that every call
+// to update_with_join() can hash the whole buffer as one subtree. Three buffers let the reader
+// thread get up to two buffers ahead when reads are bursty.
+const PARALLEL_BUF_LEN: usize = 1 << 21;
+const PARALLEL_NUM_BUFS: usize = 3;
+
+// Fill a buffer from a reader, stopping early only at EOF. Return the number of bytes read.
+fn read_full(reader: &mut impl std::io::Read, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            // see test_update_reader_interrupted
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+// The multithreaded equivalent of copy_wide(). A scoped reader thread fills large buffers and
+// sends them to the calling thread, which hashes each one with update_with_join() and sends it
+// back to be refilled, so that reading and hashing overlap.
+pub(crate) fn copy_parallel<J: crate::join::Join>(
+    reader: impl std::io::Read + Send,
+    hasher: &mut crate::Hasher,
+    join: &J,
+) -> std::io::Result<u64> {
+    use std::sync::mpsc;
+
+    std::thread::scope(|scope| {
+        // These channels are dropped before the scope joins the reader thread, so if the hashing
+        // side panics, the reader thread doesn't block forever.
+        let (full_sender, full_receiver) = mpsc::sync_channel(PARALLEL_NUM_BUFS);
+        let (empty_sender, empty_receiver) = mpsc::sync_channel(PARALLEL_NUM_BUFS);
+        for _ in 0..PARALLEL_NUM_BUFS {
+            empty_sender.send(std::vec![0; PARALLEL_BUF_LEN]).unwrap();
+        }
+        scope.spawn(move || {
+            let mut reader = reader;
+            while let Ok(mut buffer) = empty_receiver.recv() {
+                let result = read_full(&mut reader, &mut buffer);
+                // A short read means EOF, and an error means we're done too.
+                let done = !matches!(result, Ok(PARALLEL_BUF_LEN));
+                let result = result.map(|n| {
+                    buffer.truncate(n);
+                    buffer
+                });
+                if full_sender.send(result).is_err() || done {
+                    return;
+                }
+            }
+        });
+        let mut total = 0;
+        for result in &full_receiver {
+            let buffer: std::vec::Vec<u8> = result?;
+            hasher.update_with_join(&buffer, join);
+            total += buffer.len() as u64;
+            // The reader thread might have finished already.
+            _ = empty_sender.send(buffer);
+        }
+        Ok(total)
+    })
+}
+
+// The async equivalents of copy_wide(). The buffer goes on the heap, to keep it out of the future.
+#[cfg(feature = "tokio")]
+pub(crate) async fn copy_wide_tokio(
+    mut reader: impl tokio::io::AsyncRead + Unpin,
+    hasher: &mut crate::Hasher,
+) -> std::io::Result<u64> {
+    let mut buffer = std::vec![0; WIDE_BUF_LEN];
+    let mut total = 0;
+    loop {
+        let result = core::future::poll_fn(|cx| {
+            let mut read_buf = tokio::io::ReadBuf::new(&mut buffer);
+            core::pin::Pin::new(&mut reader)
+                .poll_read(cx, &mut read_buf)
+                .map_ok(|()| read_buf.filled().len())
+        })
+        .await;
+        match result {
+            Ok(0) => return Ok(total),
+            Ok(n) => {
+                hasher.update(&buffer[..n]);
+                total += n as u64;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(feature = "futures-io")]
+pub(crate) async fn copy_wide_futures(
+    mut reader: impl futures_io::AsyncRead + Unpin,
+    hasher: &mut crate::Hasher,
+) -> std::io::Result<u64> {
+    let mut buffer = std::vec![0; WIDE_BUF_LEN];
+    let mut total = 0;
+    loop {
+        let result =
+            core::future::poll_fn(|cx| core::pin::Pin::new(&mut reader).poll_read(cx, &mut buffer))
+                .await;
+        match result {
+            Ok(0) => return Ok(total),
+            Ok(n) => {
+                hasher.update(&buffer[..n]);
+                total += n as u64;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
 // Mmap a file, if it looks like a good idea. Return None in cases where we know mmap will fail, or
 // if the file is short enough that mmapping isn't worth it. However, if we do try to mmap and it
 // fails, return the error.
@@ -62,3 +462,288 @@ pub(crate) fn maybe_mmap_file(file: &std::fs::File) -> std::io::Result<Option<me
         Ok(Some(map))
     }
 }
+
+// A positioned read, which doesn't use or (on Unix) modify the file's seek position. Windows
+// doesn't have a true positioned read, and seek_read moves the seek position, but callers in this
+// crate always open the file themselves. On other platforms this falls back to seeking, which
+// isn't safe to do from multiple threads at once.
+pub(crate) fn read_at(file: &std::fs::File, buf: &mut [u8], offset: u64) -> std::io::Result<usize> {
+    cfg_if::cfg_if! {
+        if #[cfg(unix)] {
+            std::os::unix::fs::FileExt::read_at(file, buf, offset)
+        } else if #[cfg(windows)] {
+            std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+        } else {
+            use std::io::{Read, Seek};
+            let mut file = file;
+            file.seek(std::io::SeekFrom::Start(offset))?;
+            file.read(buf)
+        }
+    }
+}
+
+#[cfg(feature = "mmap")]
+fn range_too_long() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::UnexpectedEof,
+        "range extends past the end of the file",
+    )
+}
+
+// The range equivalent of copy_wide(), using positioned reads. Reaching EOF before the end of the
+// range is an error.
+#[cfg(feature = "mmap")]
+pub(crate) fn copy_range_wide(
+    file: &std::fs::File,
+    mut offset: u64,
+    len: u64,
+    hasher: &mut crate::Hasher,
+) -> std::io::Result<()> {
+    let mut buffer = [0; WIDE_BUF_LEN];
+    let end = offset.checked_add(len).ok_or_else(range_too_long)?;
+    while offset < end {
+        let want = core::cmp::min(end - offset, WIDE_BUF_LEN as u64) as usize;
+        match read_at(file, &mut buffer[..want], offset) {
+            Ok(0) => return Err(range_too_long()),
+            Ok(n) => {
+                hasher.update(&buffer[..n]);
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+// The range equivalent of maybe_mmap_file(). This maps only the requested window of the file, and
+// it returns None under the same conditions, based on the length of the window rather than the
+// length of the file. A range that extends past the end of a regular file is an error, rather than
+// a SIGBUS when we get there. The SAFETY and PARANOIA notes above apply here too.
+#[cfg(feature = "mmap")]
+pub(crate) fn maybe_mmap_file_range(
+    file: &std::fs::File,
+    offset: u64,
+    len: u64,
+) -> std::io::Result<Option<memmap2::Mmap>> {
+    let metadata = file.metadata()?;
+    if !metadata.is_file() {
+        // Not a real file. Positioned reads might still work, for example on a block device.
+        return Ok(None);
+    }
+    let end = offset.checked_add(len).ok_or_else(range_too_long)?;
+    if end > metadata.len() {
+        return Err(range_too_long());
+    }
+    let Ok(map_len) = usize::try_from(len) else {
+        // Too large to map on a 32-bit platform.
+        return Ok(None);
+    };
+    if map_len < 16 * 1024 {
+        Ok(None)
+    } else {
+        let map = unsafe {
+            memmap2::MmapOptions::new()
+                .offset(offset)
+                .len(map_len)
+                .map(file)?
+        };
+        Ok(Some(map))
+    }
+}
+
+// Whether read_at() is safe to call on the same file from multiple threads at once.
+pub(crate) const POSITIONED_READS: bool = cfg!(any(unix, windows));
+
+// The length of each positioned read in hash_file_pread(), and so the smallest subtree that's
+// handed to a separate task.
+const PREAD_LEAF_LEN: u64 = 1 << 20;
+
+fn read_exact_at(file: &std::fs::File, mut buf: &mut [u8], mut offset: u64) -> std::io::Result<()> {
+    while !buf.is_empty() {
+        match read_at(file, buf, offset) {
+            Ok(0) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "file shrank while it was being hashed",
+                ));
+            }
+            Ok(n) => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+            // see test_update_reader_interrupted
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+// The file equivalent of compress_subtree_wide(). Split the subtree the same way, and at the
+// leaves, read PREAD_LEAF_LEN bytes or fewer into a buffer and hash them with
+// compress_subtree_wide(). Each leaf produces the full SIMD degree of CVs (at least 2), because
+// it's much longer than simd_degree chunks, so the left side of every split here does too.
+#[allow(clippy::too_many_arguments)]
+fn compress_file_subtree_wide<J: crate::join::Join>(
+    file: &std::fs::File,
+    offset: u64,
+    len: u64,
+    key: &crate::CVWords,
+    chunk_counter: u64,
+    flags: u8,
+    platform: crate::Platform,
+    join: &J,
+    out: &mut [u8],
+) -> std::io::Result<usize> {
+    use crate::join::Join;
+    use crate::platform::MAX_SIMD_DEGREE_OR_2;
+    use crate::{CHUNK_LEN, OUT_LEN};
+
+    if len <= PREAD_LEAF_LEN {
+        let mut buffer = std::vec![0; len as usize];
+        read_exact_at(file, &mut buffer, offset)?;
+        return Ok(crate::compress_subtree_wide(
+            &buffer,
+            key,
+            chunk_counter,
+            flags,
+            platform,
+            &crate::join::SerialJoin,
+            out,
+        ));
+    }
+
+    let left_len = crate::hazmat::left_subtree_len(len);
+    let right_chunk_counter = chunk_counter + left_len / CHUNK_LEN as u64;
+    let mut cv_array = [0; 2 * MAX_SIMD_DEGREE_OR_2 * OUT_LEN];
+    let degree = core::cmp::max(platform.simd_degree(), 2);
+    let (left_out, right_out) = cv_array.split_at_mut(degree * OUT_LEN);
+    let left_op = || {
+        compress_file_subtree_wide(
+            file,
+            offset,
+            left_len,
+            key,
+            chunk_counter,
+            flags,
+            platform,
+            join,
+            left_out,
+        )
+    };
+    let right_op = || {
+        compress_file_subtree_wide(
+            file,
+            offset + left_len,
+            len - left_len,
+            key,
+            right_chunk_counter,
+            flags,
+            platform,
+            join,
+            right_out,
+        )
+    };
+    let (left_n, right_n) = if len <= join.min_subtree_len() as u64 {
+        crate::join::SerialJoin.join(left_op, right_op)
+    } else {
+        join.join(left_op, right_op)
+    };
+    let (left_n, right_n) = (left_n?, right_n?);
+    debug_assert_eq!(left_n, degree);
+    debug_assert!(right_n >= 1 && right_n <= left_n);
+    Ok(crate::compress_parents_parallel(
+        &cv_array[..(left_n + right_n) * OUT_LEN],
+        key,
+        flags,
+        platform,
+        out,
+    ))
+}
+
+// Hash `len` bytes of a file, from offset zero, using positioned reads from multiple threads. This
+// follows the same steps as Hasher::update_with_join(), except that each step reads its input from
+// the file. Short steps, including the partial chunk at the start and the end, are read into a
+// buffer and passed to Hasher::update(). Steps that are whole subtrees longer than PREAD_LEAF_LEN
+// are split across threads by compress_file_subtree_wide(), and their CVs are pushed onto the CV
+// stack directly.
+pub(crate) fn hash_file_pread<J: crate::join::Join>(
+    file: &std::fs::File,
+    len: u64,
+    hasher: &mut crate::Hasher,
+    join: &J,
+) -> std::io::Result<()> {
+    use crate::platform::MAX_SIMD_DEGREE_OR_2;
+    use crate::{CHUNK_LEN, OUT_LEN};
+    use arrayref::array_ref;
+
+    hasher.assert_subtree_len(len);
+    let mut buffer = [0; WIDE_BUF_LEN];
+    let mut offset = 0;
+    while offset < len {
+        let remaining = len - offset;
+        // A full chunk_state means we're at a chunk boundary, and there's more input coming.
+        if hasher.chunk_state.count() == CHUNK_LEN {
+            hasher.push_full_chunk();
+        }
+        if hasher.chunk_state.count() > 0 {
+            // Finish the partial chunk.
+            let take = core::cmp::min(remaining, (CHUNK_LEN - hasher.chunk_state.count()) as u64);
+            read_exact_at(file, &mut buffer[..take as usize], offset)?;
+            hasher.update(&buffer[..take as usize]);
+            offset += take;
+            continue;
+        }
+        // As in update_with_join(), find the largest power-of-2 subtree that evenly divides the
+        // count so far.
+        let count_so_far = hasher.chunk_state.chunk_counter * CHUNK_LEN as u64;
+        let mut subtree_len = 1 << (63 - remaining.leading_zeros());
+        while (subtree_len - 1) & count_so_far != 0 {
+            subtree_len /= 2;
+        }
+        if subtree_len <= PREAD_LEAF_LEN {
+            let take = core::cmp::min(subtree_len, WIDE_BUF_LEN as u64) as usize;
+            let take = if remaining <= CHUNK_LEN as u64 {
+                remaining as usize
+            } else {
+                take
+            };
+            read_exact_at(file, &mut buffer[..take], offset)?;
+            hasher.update(&buffer[..take]);
+            offset += take as u64;
+            continue;
+        }
+        let mut cv_array = [0; MAX_SIMD_DEGREE_OR_2 * OUT_LEN];
+        let num_cvs = compress_file_subtree_wide(
+            file,
+            offset,
+            subtree_len,
+            &hasher.key,
+            hasher.chunk_state.chunk_counter,
+            hasher.chunk_state.flags,
+            hasher.chunk_state.platform,
+            join,
+            &mut cv_array,
+        )?;
+        let cv_pair = crate::condense_cvs_to_parent_node(
+            &mut cv_array,
+            num_cvs,
+            &hasher.key,
+            hasher.chunk_state.flags,
+            hasher.chunk_state.platform,
+        );
+        // As in update_with_join(), push the two CVs separately, so that lazy merging never
+        // merges the root.
+        let subtree_chunks = subtree_len / CHUNK_LEN as u64;
+        let chunk_counter = hasher.chunk_state.chunk_counter;
+        hasher.push_cv(array_ref!(cv_pair, 0, OUT_LEN), chunk_counter);
+        hasher.push_cv(
+            array_ref!(cv_pair, OUT_LEN, OUT_LEN),
+            chunk_counter + subtree_chunks / 2,
+        );
+        hasher.chunk_state.chunk_counter += subtree_chunks;
+        offset += subtree_len;
+    }
+    Ok(())
+}
@@ -1,31 +1,65 @@
-//! The multi-threading abstractions used by `Hasher::update_with_join`.
+//! The multi-threading abstractions used by [`Hasher::update_with_join`] and
+//! [`OutputReader::fill_with_join`].
 //!
-//! Different implementations of the `Join` trait determine whether
-//! `Hasher::update_with_join` performs multi-threading on sufficiently large
-//! inputs. The `SerialJoin` implementation is single-threaded, and the
-//! `RayonJoin` implementation (gated by the `rayon` feature) is multi-threaded.
-//! Interfaces other than `Hasher::update_with_join`, like [`hash`](crate::hash)
-//! and [`Hasher::update`](crate::Hasher::update), always use `SerialJoin`
-//! internally.
+//! Different implementations of the [`Join`] trait determine whether
+//! [`Hasher::update_with_join`] performs multi-threading on sufficiently large
+//! inputs, and whether [`OutputReader::fill_with_join`] does the same for
+//! large outputs. The [`SerialJoin`] implementation is single-threaded, the
+//! [`RayonJoin`] implementation (gated by the `rayon` feature) runs on the
+//! global Rayon thread pool, and the [`ThreadJoin`] implementation (gated by
+//! the `std` feature) uses [`std::thread::scope`] and doesn't need Rayon.
+//! Other interfaces, like [`hash`](crate::hash), [`Hasher::update`], and
+//! [`OutputReader::fill`], always use `SerialJoin` internally.
 //!
-//! The `Join` trait is an almost exact copy of the [`rayon::join`] API, and
-//! `RayonJoin` is the only non-trivial implementation. Previously this trait
-//! was public, but currently it's been re-privatized, as it's both 1) of no
-//! value to most callers and 2) a pretty big implementation detail to commit
-//! to.
+//! The `Join` trait is an almost exact copy of the [`rayon::join`] API, except
+//! that it takes `&self`, so that implementations can carry configuration.
+//! Callers who run their own executor or thread pool can implement it
+//! themselves. The recursion that calls `join` splits the input along BLAKE3
+//! subtree boundaries, or the output along block boundaries, so the two
+//! closures passed to `join` are always independent of each other.
 //!
+//! [`Hasher::update_with_join`]: crate::Hasher::update_with_join
+//! [`Hasher::update`]: crate::Hasher::update
+//! [`OutputReader::fill_with_join`]: crate::OutputReader::fill_with_join
+//! [`OutputReader::fill`]: crate::OutputReader::fill
 //! [`rayon::join`]: https://docs.rs/rayon/1.3.0/rayon/fn.join.html
+//!
+//! # Example
+//!
+//! ```
+//! # #[cfg(feature = "std")] {
+//! use blake3::join::ThreadJoin;
+//!
+//! let input = vec![42; 1 << 20];
+//! let join = ThreadJoin::new().max_threads(4).min_subtree_len(128 * 1024);
+//! let mut hasher = blake3::Hasher::new();
+//! hasher.update_with_join(&input, &join);
+//! assert_eq!(hasher.finalize(), blake3::hash(&input));
+//! # }
+//! ```
 
 /// The trait that abstracts over single-threaded and multi-threaded recursion.
 ///
 /// See the [`join` module docs](index.html) for more details.
-pub trait Join {
-    fn join<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
+pub trait Join: Sync {
+    /// Run `oper_a` and `oper_b`, potentially in parallel, and return both
+    /// results.
+    fn join<A, B, RA, RB>(&self, oper_a: A, oper_b: B) -> (RA, RB)
     where
         A: FnOnce() -> RA + Send,
         B: FnOnce() -> RB + Send,
         RA: Send,
         RB: Send;
+
+    /// Subtrees of this many bytes or fewer are hashed serially on the
+    /// current thread, instead of being split with [`join`](Join::join).
+    ///
+    /// The default is zero, which means that every split goes through
+    /// [`join`](Join::join). Implementations with expensive task startup
+    /// costs should return something larger.
+    fn min_subtree_len(&self) -> usize {
+        0
+    }
 }
 
 /// The trivial, serial implementation of `Join`. The left and right sides are
@@ -34,11 +68,12 @@ pub trait Join {
 /// internally.
 ///
 /// See the [`join` module docs](index.html) for more details.
-pub enum SerialJoin {}
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SerialJoin;
 
 impl Join for SerialJoin {
     #[inline]
-    fn join<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
+    fn join<A, B, RA, RB>(&self, oper_a: A, oper_b: B) -> (RA, RB)
     where
         A: FnOnce() -> RA + Send,
         B: FnOnce() -> RB + Send,
@@ -55,12 +90,13 @@ impl Join for SerialJoin {
 ///
 /// See the [`join` module docs](index.html) for more details.
 #[cfg(feature = "rayon")]
-pub enum RayonJoin {}
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RayonJoin;
 
 #[cfg(feature = "rayon")]
 impl Join for RayonJoin {
     #[inline]
-    fn join<A, B, RA, RB>(oper_a: A, oper_b: B) -> (RA, RB)
+    fn join<A, B, RA, RB>(&self, oper_a: A, oper_b: B) -> (RA, RB)
     where
         A: FnOnce() -> RA + Send,
         B: FnOnce() -> RB + Send,
@@ -71,6 +107,112 @@ impl Join for RayonJoin {
     }
 }
 
+/// An implementation of `Join` based on [`std::thread::scope`], which doesn't
+/// need Rayon. This implementation is gated by the `std` feature, which is on
+/// by default.
+///
+/// Each call to [`join`](Join::join) runs the right side on a new scoped
+/// thread, as long as fewer than [`max_threads`](ThreadJoin::max_threads)
+/// extra threads are already running. Otherwise both sides run on the calling
+/// thread. Spawning a thread is much more expensive than spawning a Rayon task,
+/// so subtrees shorter than [`min_subtree_len`](ThreadJoin::min_subtree_len)
+/// aren't split at all. The default minimum is currently 1 MiB, and the
+/// default maximum number of extra threads is one less than
+/// [`std::thread::available_parallelism`]. Both defaults might change at any
+/// time.
+///
+/// A `ThreadJoin` can be reused for any number of calls, including concurrent
+/// calls from different threads, which share its thread budget.
+///
+/// See the [`join` module docs](index.html) for more details.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct ThreadJoin {
+    available_threads: std::sync::atomic::AtomicUsize,
+    min_subtree_len: usize,
+}
+
+#[cfg(feature = "std")]
+impl ThreadJoin {
+    /// Construct a new `ThreadJoin` with the default settings.
+    pub fn new() -> Self {
+        let max_threads = std::thread::available_parallelism()
+            .map(|n| n.get() - 1)
+            .unwrap_or(0);
+        Self {
+            available_threads: max_threads.into(),
+            min_subtree_len: 1 << 20,
+        }
+    }
+
+    /// Set the maximum number of threads to spawn at the same time, not
+    /// counting the calling thread. Zero disables multithreading.
+    pub fn max_threads(self, max_threads: usize) -> Self {
+        Self {
+            available_threads: max_threads.into(),
+            ..self
+        }
+    }
+
+    /// Set the length in bytes of the smallest subtree that's split between
+    /// two threads. See [`Join::min_subtree_len`].
+    pub fn min_subtree_len(self, min_subtree_len: usize) -> Self {
+        Self {
+            min_subtree_len,
+            ..self
+        }
+    }
+
+    fn try_acquire_thread(&self) -> bool {
+        use std::sync::atomic::Ordering::Relaxed;
+        self.available_threads
+            .fetch_update(Relaxed, Relaxed, |n| n.checked_sub(1))
+            .is_ok()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for ThreadJoin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Join for ThreadJoin {
+    fn join<A, B, RA, RB>(&self, oper_a: A, oper_b: B) -> (RA, RB)
+    where
+        A: FnOnce() -> RA + Send,
+        B: FnOnce() -> RB + Send,
+        RA: Send,
+        RB: Send,
+    {
+        if !self.try_acquire_thread() {
+            return (oper_a(), oper_b());
+        }
+        // Give the thread back even if one of the closures panics.
+        struct Release<'a>(&'a std::sync::atomic::AtomicUsize);
+        impl Drop for Release<'_> {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+        let _release = Release(&self.available_threads);
+        std::thread::scope(|scope| {
+            let handle = scope.spawn(oper_b);
+            let result_a = oper_a();
+            match handle.join() {
+                Ok(result_b) => (result_a, result_b),
+                Err(panic) => std::panic::resume_unwind(panic),
+            }
+        })
+    }
+
+    fn min_subtree_len(&self) -> usize {
+        self.min_subtree_len
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -79,7 +221,7 @@ mod test {
     fn test_serial_join() {
         let oper_a = || 1 + 1;
         let oper_b = || 2 + 2;
-        assert_eq!((2, 4), SerialJoin::join(oper_a, oper_b));
+        assert_eq!((2, 4), SerialJoin.join(oper_a, oper_b));
     }
 
     #[test]
@@ -87,6 +229,35 @@ mod test {
     fn test_rayon_join() {
         let oper_a = || 1 + 1;
         let oper_b = || 2 + 2;
-        assert_eq!((2, 4), RayonJoin::join(oper_a, oper_b));
+        assert_eq!((2, 4), RayonJoin.join(oper_a, oper_b));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_thread_join() {
+        let oper_a = || 1 + 1;
+        let oper_b = || std::thread::current().id();
+        let join = ThreadJoin::new().max_threads(1);
+        let (a, b_thread) = join.join(oper_a, oper_b);
+        assert_eq!(a, 2);
+        assert_ne!(b_thread, std::thread::current().id());
+
+        // With no threads available, both sides run on the calling thread.
+        let join = ThreadJoin::new().max_threads(0);
+        let (a, b_thread) = join.join(oper_a, oper_b);
+        assert_eq!(a, 2);
+        assert_eq!(b_thread, std::thread::current().id());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_thread_join_panic_releases_thread() {
+        let join = ThreadJoin::new().max_threads(1);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            join.join(|| (), || panic!("oops"))
+        }));
+        assert!(result.is_err());
+        let (_, b_thread) = join.join(|| (), || std::thread::current().id());
+        assert_ne!(b_thread, std::thread::current().id());
     }
 }
@@ -34,19 +34,38 @@
 //!
 //! The `std` feature (the only feature enabled by default) enables the
 //! [`Write`] implementation and the [`update_reader`](Hasher::update_reader)
-//! method for [`Hasher`], and also the [`Read`] and [`Seek`] implementations
-//! for [`OutputReader`].
+//! method for [`Hasher`], the [`Read`] and [`Seek`] implementations for
+//! [`OutputReader`], the [`ThreadJoin`](join::ThreadJoin) implementation
+//! for multithreading with [`update_with_join`](Hasher::update_with_join),
+//! the hashing IO adapters in the [`io`] module, and verified streaming in the
+//! [`verified`] module.
 //!
 //! The `rayon` feature (disabled by default, but enabled for [docs.rs]) adds
 //! the [`update_rayon`](Hasher::update_rayon) and (in combination with `mmap`
 //! below) [`update_mmap_rayon`](Hasher::update_mmap_rayon) methods for
-//! multithreaded hashing. However, even if this feature is enabled, all other
+//! multithreaded hashing, as well as
+//! [`update_pread_rayon`](Hasher::update_pread_rayon) for files that can't be
+//! memory mapped and [`update_reader_rayon`](Hasher::update_reader_rayon) for
+//! other readers. However, even if this feature is enabled, all other
 //! APIs remain single-threaded.
 //!
 //! The `mmap` feature (disabled by default, but enabled for [docs.rs]) adds the
 //! [`update_mmap`](Hasher::update_mmap) and (in combination with `rayon` above)
 //! [`update_mmap_rayon`](Hasher::update_mmap_rayon) helper methods for
-//! memory-mapped IO.
+//! memory-mapped IO, along with [`update_mmap_range`](Hasher::update_mmap_range)
+//! and [`update_mmap_range_rayon`](Hasher::update_mmap_range_rayon) for hashing
+//! a region of a file.
+//!
+//! The `tokio` and `futures-io` features (disabled by default, but enabled for
+//! [docs.rs]) add async IO support for those two ecosystems. They add the
+//! [`update_tokio_reader`](Hasher::update_tokio_reader) and
+//! [`update_futures_reader`](Hasher::update_futures_reader) methods and
+//! `AsyncWrite` implementations for [`Hasher`], and `AsyncRead` and
+//! `AsyncSeek` implementations for [`OutputReader`].
+//!
+//! The `getrandom` feature (disabled by default, but enabled for [docs.rs])
+//! adds [`BuildHasher::random`] and a `Default` implementation for
+//! [`BuildHasher`], using a random key from the operating system.
 //!
 //! The `zeroize` feature (disabled by default, but enabled for [docs.rs])
 //! implements
@@ -56,7 +75,7 @@
 //! The `serde` feature (disabled by default, but enabled for [docs.rs]) implements
 //! [`serde::Serialize`](https://docs.rs/serde/latest/serde/trait.Serialize.html) and
 //! [`serde::Deserialize`](https://docs.rs/serde/latest/serde/trait.Deserialize.html)
-//! for [`Hash`](struct@Hash).
+//! for [`Hash`](struct@Hash), [`Checkpoint`], and [`RangeProof`].
 //!
 //! The NEON implementation is enabled by default for AArch64 but requires the
 //! `neon` feature for other ARM targets. Not all ARMv7 CPUs support NEON, and
@@ -68,9 +87,10 @@
 //! support is assumed. This may become the default in the future.
 //!
 //! The `traits-preview` feature enables implementations of traits from the
-//! RustCrypto [`digest`] crate, and re-exports that crate as `traits::digest`.
-//! However, the traits aren't stable, and they're expected to change in
-//! incompatible ways before that crate reaches 1.0. For that reason, this crate
+//! RustCrypto [`digest`] and [`cipher`] crates, and re-exports those crates as
+//! `traits::digest` and `traits::cipher`. However, the traits aren't stable,
+//! and they're expected to change in incompatible ways before those crates
+//! reach 1.0. For that reason, this crate
 //! makes no SemVer guarantees for this feature, and callers who use it should
 //! expect breaking changes between patch versions. (The "-preview" feature name
 //! follows the conventions of the RustCrypto [`signature`] crate.)
@@ -83,6 +103,7 @@
 //! [`Write`]: https://doc.rust-lang.org/std/io/trait.Write.html
 //! [`Seek`]: https://doc.rust-lang.org/std/io/trait.Seek.html
 //! [`digest`]: https://crates.io/crates/digest
+//! [`cipher`]: https://crates.io/crates/cipher
 //! [`signature`]: https://crates.io/crates/signature
 
 #![cfg_attr(not(feature = "std"), no_std)]
@@ -135,13 +156,36 @@ mod wasm32_simd;
 #[cfg(feature = "traits-preview")]
 pub mod traits;
 
-mod io;
-mod join;
+mod buffered_output;
+mod build_hasher;
+mod checkpoint;
+mod const_fn;
+mod encoding;
+#[cfg(feature = "std")]
+pub mod io;
+pub mod join;
+mod mac;
+mod many;
+mod range_proof;
+mod subtree_result;
+#[cfg(feature = "std")]
+mod tree_builder;
+#[cfg(feature = "std")]
+pub mod verified;
+
+pub use buffered_output::BufferedOutputReader;
+pub use build_hasher::{BuildHasher, TableHasher};
+pub use checkpoint::{Checkpoint, CheckpointError};
+pub use encoding::{DecodeError, Multibase};
+pub use mac::{Mac, MacError};
+pub use many::{derive_key_many, hash_many, keyed_hash_many};
+pub use range_proof::{RangeProof, RangeProofError};
 
 use arrayref::{array_mut_ref, array_ref};
 use arrayvec::{ArrayString, ArrayVec};
 use core::cmp;
 use core::fmt;
+use join::Join;
 use platform::{MAX_SIMD_DEGREE, MAX_SIMD_DEGREE_OR_2, Platform};
 #[cfg(feature = "zeroize")]
 use zeroize::Zeroize;
@@ -202,12 +246,12 @@ const DERIVE_KEY_CONTEXT: u8 = 1 << 5;
 const DERIVE_KEY_MATERIAL: u8 = 1 << 6;
 
 #[inline]
-fn counter_low(counter: u64) -> u32 {
+const fn counter_low(counter: u64) -> u32 {
     counter as u32
 }
 
 #[inline]
-fn counter_high(counter: u64) -> u32 {
+const fn counter_high(counter: u64) -> u32 {
     (counter >> 32) as u32
 }
 
@@ -294,7 +338,10 @@ impl Hash {
     /// error.
     ///
     /// Note that `Hash` also implements `FromStr`, so `Hash::from_hex("...")`
-    /// is equivalent to `"...".parse()`.
+    /// is equivalent to `"...".parse()`. See also
+    /// [`from_base64url`](Hash::from_base64url),
+    /// [`from_base32`](Hash::from_base32), and
+    /// [`from_multibase`](Hash::from_multibase).
     pub fn from_hex(hex: impl AsRef<[u8]>) -> Result<Self, HexError> {
         fn hex_val(byte: u8) -> Result<u8, HexError> {
             match byte {
@@ -781,6 +828,7 @@ fn compress_subtree_wide<J: join::Join>(
     chunk_counter: u64,
     flags: u8,
     platform: Platform,
+    join: &J,
     out: &mut [u8],
 ) -> usize {
     // Note that the single chunk case does *not* bump the SIMD degree up to 2
@@ -812,11 +860,25 @@ fn compress_subtree_wide<J: join::Join>(
     let (left_out, right_out) = cv_array.split_at_mut(degree * OUT_LEN);
 
     // Recurse! For update_rayon(), this is where we take advantage of RayonJoin and use multiple
-    // threads.
-    let (left_n, right_n) = J::join(
-        || compress_subtree_wide::<J>(left, key, chunk_counter, flags, platform, left_out),
-        || compress_subtree_wide::<J>(right, key, right_chunk_counter, flags, platform, right_out),
-    );
+    // threads. Subtrees below the Join's minimum length stay on this thread.
+    let left_op =
+        || compress_subtree_wide(left, key, chunk_counter, flags, platform, join, left_out);
+    let right_op = || {
+        compress_subtree_wide(
+            right,
+            key,
+            right_chunk_counter,
+            flags,
+            platform,
+            join,
+            right_out,
+        )
+    };
+    let (left_n, right_n) = if input.len() <= join.min_subtree_len() {
+        join::SerialJoin.join(left_op, right_op)
+    } else {
+        join.join(left_op, right_op)
+    };
 
     // The special case again. If simd_degree=1, then we'll have left_n=1 and
     // right_n=1. Rather than compressing them into a single output, return
@@ -855,11 +917,31 @@ fn compress_subtree_to_parent_node<J: join::Join>(
     chunk_counter: u64,
     flags: u8,
     platform: Platform,
+    join: &J,
 ) -> [u8; BLOCK_LEN] {
     debug_assert!(input.len() > CHUNK_LEN);
     let mut cv_array = [0; MAX_SIMD_DEGREE_OR_2 * OUT_LEN];
-    let mut num_cvs =
-        compress_subtree_wide::<J>(input, &key, chunk_counter, flags, platform, &mut cv_array);
+    let num_cvs = compress_subtree_wide(
+        input,
+        &key,
+        chunk_counter,
+        flags,
+        platform,
+        join,
+        &mut cv_array,
+    );
+    condense_cvs_to_parent_node(&mut cv_array, num_cvs, key, flags, platform)
+}
+
+// The second half of compress_subtree_to_parent_node(), shared with the positioned-read file
+// hashing in io.rs.
+fn condense_cvs_to_parent_node(
+    cv_array: &mut [u8; MAX_SIMD_DEGREE_OR_2 * OUT_LEN],
+    mut num_cvs: usize,
+    key: &CVWords,
+    flags: u8,
+    platform: Platform,
+) -> [u8; BLOCK_LEN] {
     debug_assert!(num_cvs >= 2);
 
     // If MAX_SIMD_DEGREE is greater than 2 and there's enough input,
@@ -876,7 +958,7 @@ fn compress_subtree_to_parent_node<J: join::Join>(
 
 // Hash a complete input all at once. Unlike compress_subtree_wide() and
 // compress_subtree_to_parent_node(), this function handles the 1 chunk case.
-fn hash_all_at_once<J: join::Join>(input: &[u8], key: &CVWords, flags: u8) -> Output {
+fn hash_all_at_once<J: join::Join>(input: &[u8], key: &CVWords, flags: u8, join: &J) -> Output {
     let platform = Platform::detect();
 
     // If the whole subtree is one chunk, hash it directly with a ChunkState.
@@ -890,7 +972,7 @@ fn hash_all_at_once<J: join::Join>(input: &[u8], key: &CVWords, flags: u8) -> Ou
     // compress_subtree_to_parent_node().
     Output {
         input_chaining_value: *key,
-        block: compress_subtree_to_parent_node::<J>(input, key, 0, flags, platform),
+        block: compress_subtree_to_parent_node(input, key, 0, flags, platform, join),
         block_len: BLOCK_LEN as u8,
         counter: 0,
         flags: flags | PARENT,
@@ -918,7 +1000,23 @@ fn hash_all_at_once<J: join::Join>(input: &[u8], key: &CVWords, flags: u8) -> Ou
 /// This function is always single-threaded. For multithreading support, see
 /// [`Hasher::update_rayon`](struct.Hasher.html#method.update_rayon).
 pub fn hash(input: &[u8]) -> Hash {
-    hash_all_at_once::<join::SerialJoin>(input, IV, 0).root_hash()
+    hash_all_at_once(input, IV, 0, &join::SerialJoin).root_hash()
+}
+
+/// A `const fn` version of [`hash`], for hashing at compile time.
+///
+/// This gives the same result as [`hash`], but it only uses the portable implementation, without
+/// SIMD or multithreading. It's intended for short inputs in `const` items. At runtime, prefer
+/// [`hash`], which is much faster for anything longer than a few blocks.
+///
+/// # Example
+///
+/// ```
+/// const FOO_HASH: blake3::Hash = blake3::const_hash(b"foo");
+/// assert_eq!(FOO_HASH, blake3::hash(b"foo"));
+/// ```
+pub const fn const_hash(input: &[u8]) -> Hash {
+    Hash(const_fn::hash_all_at_once(input, IV, 0))
 }
 
 /// The keyed hash function.
@@ -927,7 +1025,7 @@ pub fn hash(input: &[u8]) -> Hash {
 /// replace an HMAC instance. In that use case, the constant-time equality
 /// checking provided by [`Hash`](struct.Hash.html) is almost always a security
 /// requirement, and callers need to be careful not to compare MACs as raw
-/// bytes.
+/// bytes. The [`Mac`] type handles tag verification, including truncated tags.
 ///
 /// For an incremental version that accepts multiple writes, see [`Hasher::new_keyed`],
 /// [`Hasher::update`], and [`Hasher::finalize`]. These two lines are equivalent:
@@ -948,7 +1046,16 @@ pub fn hash(input: &[u8]) -> Hash {
 /// [`Hasher::update_rayon`](struct.Hasher.html#method.update_rayon).
 pub fn keyed_hash(key: &[u8; KEY_LEN], input: &[u8]) -> Hash {
     let key_words = platform::words_from_le_bytes_32(key);
-    hash_all_at_once::<join::SerialJoin>(input, &key_words, KEYED_HASH).root_hash()
+    hash_all_at_once(input, &key_words, KEYED_HASH, &join::SerialJoin).root_hash()
+}
+
+/// A `const fn` version of [`keyed_hash`], for hashing at compile time.
+///
+/// As with [`const_hash`], this only uses the portable implementation, and at runtime
+/// [`keyed_hash`] is much faster for long inputs.
+pub const fn const_keyed_hash(key: &[u8; KEY_LEN], input: &[u8]) -> Hash {
+    let key_words = platform::words_from_le_bytes_32(key);
+    Hash(const_fn::hash_all_at_once(input, &key_words, KEYED_HASH))
 }
 
 /// The key derivation function.
@@ -1003,9 +1110,14 @@ pub fn keyed_hash(key: &[u8; KEY_LEN], input: &[u8]) -> Hash {
 pub fn derive_key(context: &str, key_material: &[u8]) -> [u8; OUT_LEN] {
     let context_key = hazmat::hash_derive_key_context(context);
     let context_key_words = platform::words_from_le_bytes_32(&context_key);
-    hash_all_at_once::<join::SerialJoin>(key_material, &context_key_words, DERIVE_KEY_MATERIAL)
-        .root_hash()
-        .0
+    hash_all_at_once(
+        key_material,
+        &context_key_words,
+        DERIVE_KEY_MATERIAL,
+        &join::SerialJoin,
+    )
+    .root_hash()
+    .0
 }
 
 fn parent_node_output(
@@ -1194,6 +1306,35 @@ impl Hasher {
         self.cv_stack.push(*new_cv);
     }
 
+    // Finalize a full chunk_state and push its CV. This is only valid when more input is coming,
+    // so that we know the chunk isn't the root.
+    fn push_full_chunk(&mut self) {
+        debug_assert_eq!(self.chunk_state.count(), CHUNK_LEN);
+        let chunk_cv = self.chunk_state.output().chaining_value();
+        self.push_cv(&chunk_cv, self.chunk_state.chunk_counter);
+        self.chunk_state = ChunkState::new(
+            &self.key,
+            self.chunk_state.chunk_counter + 1,
+            self.chunk_state.flags,
+            self.chunk_state.platform,
+        );
+    }
+
+    // Check that `input_len` more bytes fit in the subtree, if this is a subtree hasher from
+    // hazmat::HasherExt::set_input_offset.
+    fn assert_subtree_len(&self, input_len: u64) {
+        if let Err(e) = hazmat::check_subtree_len(self, input_len) {
+            panic!("{e}");
+        }
+    }
+
+    // Check that this isn't a subtree hasher, before finalizing the root.
+    fn assert_root(&self) {
+        if let Err(e) = hazmat::check_root(self) {
+            panic!("{e}");
+        }
+    }
+
     /// Add input bytes to the hash state. You can call this any number of times.
     ///
     /// This method is always single-threaded. For multithreading support, see
@@ -1202,21 +1343,24 @@ impl Hasher {
     /// Note that the degree of SIMD parallelism that `update` can use is limited by the size of
     /// this input buffer. See [`update_reader`](#method.update_reader).
     pub fn update(&mut self, input: &[u8]) -> &mut Self {
-        self.update_with_join::<join::SerialJoin>(input)
-    }
-
-    fn update_with_join<J: join::Join>(&mut self, mut input: &[u8]) -> &mut Self {
-        let input_offset = self.initial_chunk_counter * CHUNK_LEN as u64;
-        if let Some(max) = hazmat::max_subtree_len(input_offset) {
-            let remaining = max - self.count();
-            assert!(
-                input.len() as u64 <= remaining,
-                "the subtree starting at {} contains at most {} bytes (found {})",
-                CHUNK_LEN as u64 * self.initial_chunk_counter,
-                max,
-                input.len(),
-            );
-        }
+        self.update_with_join(input, &join::SerialJoin)
+    }
+
+    /// As [`update`](Hasher::update), but using the given [`Join`] implementation for
+    /// multithreading internally.
+    ///
+    /// This is the general form of [`update_rayon`](Hasher::update_rayon), for callers who want
+    /// to use their own executor or thread pool, or who can't depend on Rayon. The
+    /// [`ThreadJoin`](join::ThreadJoin) implementation uses [`std::thread::scope`], and
+    /// [`SerialJoin`](join::SerialJoin) makes this method equivalent to
+    /// [`update`](Hasher::update). See the [`join`] module for more details.
+    ///
+    /// As with [`update_rayon`](Hasher::update_rayon), the input buffer needs to be large to get
+    /// any performance benefit from multithreading. The
+    /// [`min_subtree_len`](join::Join::min_subtree_len) of the `Join` controls the smallest
+    /// subtree that's handed to a worker.
+    pub fn update_with_join<J: join::Join>(&mut self, mut input: &[u8], join: &J) -> &mut Self {
+        self.assert_subtree_len(input.len() as u64);
         // If we have some partial chunk bytes in the internal chunk_state, we
         // need to finish that chunk first.
         if self.chunk_state.count() > 0 {
@@ -1228,15 +1372,7 @@ impl Hasher {
                 // We've filled the current chunk, and there's more input
                 // coming, so we know it's not the root and we can finalize it.
                 // Then we'll proceed to hashing whole chunks below.
-                debug_assert_eq!(self.chunk_state.count(), CHUNK_LEN);
-                let chunk_cv = self.chunk_state.output().chaining_value();
-                self.push_cv(&chunk_cv, self.chunk_state.chunk_counter);
-                self.chunk_state = ChunkState::new(
-                    &self.key,
-                    self.chunk_state.chunk_counter + 1,
-                    self.chunk_state.flags,
-                    self.chunk_state.platform,
-                );
+                self.push_full_chunk();
             } else {
                 return self;
             }
@@ -1301,12 +1437,13 @@ impl Hasher {
             } else {
                 // This is the high-performance happy path, though getting here
                 // depends on the caller giving us a long enough input.
-                let cv_pair = compress_subtree_to_parent_node::<J>(
+                let cv_pair = compress_subtree_to_parent_node(
                     &input[..subtree_len],
                     &self.key,
                     self.chunk_state.chunk_counter,
                     self.chunk_state.flags,
                     self.chunk_state.platform,
+                    join,
                 );
                 let left_cv = array_ref!(cv_pair, 0, 32);
                 let right_cv = array_ref!(cv_pair, 32, 32);
@@ -1396,10 +1533,7 @@ impl Hasher {
     /// This method is idempotent. Calling it twice will give the same result.
     /// You can also add more input and finalize again.
     pub fn finalize(&self) -> Hash {
-        assert_eq!(
-            self.initial_chunk_counter, 0,
-            "set_input_offset must be used with finalize_non_root",
-        );
+        self.assert_root();
         self.final_output().root_hash()
     }
 
@@ -1411,10 +1545,7 @@ impl Hasher {
     ///
     /// [`OutputReader`]: struct.OutputReader.html
     pub fn finalize_xof(&self) -> OutputReader {
-        assert_eq!(
-            self.initial_chunk_counter, 0,
-            "set_input_offset must be used with finalize_non_root",
-        );
+        self.assert_root();
         OutputReader::new(self.final_output())
     }
 
@@ -1473,6 +1604,127 @@ impl Hasher {
         Ok(self)
     }
 
+    /// As [`update_reader`](Hasher::update_reader), but reading on a separate thread, and hashing
+    /// with the given [`Join`] implementation for multithreading.
+    ///
+    /// `update_reader` alternates between reading and hashing, and its buffer is too small to
+    /// benefit from multithreading. This method spawns a scoped thread that reads into large
+    /// buffers (currently three buffers of 2 MiB each, which may change at any time), while the
+    /// calling thread hashes the buffers it's already filled with
+    /// [`update_with_join`](Hasher::update_with_join). For a fast source like a pipe or a socket
+    /// on a fast network, this can hash at close to the speed of memory.
+    ///
+    /// The reader thread fills each buffer completely before handing it over, so any amount of
+    /// input smaller than one buffer is read and hashed serially, with the extra cost of spawning
+    /// a thread. For files that support it, [`update_mmap_rayon`](Hasher::update_mmap_rayon) and
+    /// [`update_pread_with_join`](Hasher::update_pread_with_join) avoid the copy into a buffer
+    /// entirely.
+    ///
+    /// This method requires the `std` Cargo feature, which is enabled by default.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::io;
+    /// # fn main() -> io::Result<()> {
+    /// use blake3::join::ThreadJoin;
+    ///
+    /// // Hash standard input.
+    /// let mut hasher = blake3::Hasher::new();
+    /// hasher.update_reader_with_join(std::io::stdin(), &ThreadJoin::new())?;
+    /// println!("{}", hasher.finalize());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn update_reader_with_join<J: join::Join>(
+        &mut self,
+        reader: impl std::io::Read + Send,
+        join: &J,
+    ) -> std::io::Result<&mut Self> {
+        io::copy_parallel(reader, self, join)?;
+        Ok(self)
+    }
+
+    /// As [`update_reader_with_join`](Hasher::update_reader_with_join), using the Rayon thread
+    /// pool for hashing. The reader thread is still a separate, dedicated thread, so that
+    /// blocking reads don't tie up a Rayon worker.
+    ///
+    /// This method requires the `rayon` Cargo feature, which is disabled by default but enabled
+    /// on [docs.rs](https://docs.rs).
+    #[cfg(feature = "rayon")]
+    pub fn update_reader_rayon(
+        &mut self,
+        reader: impl std::io::Read + Send,
+    ) -> std::io::Result<&mut Self> {
+        self.update_reader_with_join(reader, &join::RayonJoin)
+    }
+
+    /// As [`update_reader`](Hasher::update_reader), but reading from a Tokio
+    /// [`AsyncRead`](https://docs.rs/tokio/latest/tokio/io/trait.AsyncRead.html) implementation.
+    ///
+    /// [`Hasher`] also implements Tokio's
+    /// [`AsyncWrite`](https://docs.rs/tokio/latest/tokio/io/trait.AsyncWrite.html), but as with
+    /// [`update_reader`](Hasher::update_reader), copying into it with a small buffer limits SIMD
+    /// parallelism. This method reads into an internal buffer that's large enough for all of this
+    /// crate's SIMD implementations, and it hashes each buffer on the calling task as soon as it's
+    /// read. The buffer size may change at any time.
+    ///
+    /// This method requires the `tokio` Cargo feature, which is disabled by default but enabled on
+    /// [docs.rs](https://docs.rs).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn example() -> std::io::Result<()> {
+    /// # let reader: &[u8] = b"foo";
+    /// // `reader` is any tokio::io::AsyncRead + Unpin, like a tokio::fs::File.
+    /// let mut hasher = blake3::Hasher::new();
+    /// hasher.update_tokio_reader(reader).await?;
+    /// println!("{}", hasher.finalize());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn update_tokio_reader(
+        &mut self,
+        reader: impl tokio::io::AsyncRead + Unpin,
+    ) -> std::io::Result<&mut Self> {
+        io::copy_wide_tokio(reader, self).await?;
+        Ok(self)
+    }
+
+    /// As [`update_reader`](Hasher::update_reader), but reading from a
+    /// [`futures_io::AsyncRead`](https://docs.rs/futures-io/latest/futures_io/trait.AsyncRead.html)
+    /// implementation.
+    ///
+    /// See [`update_tokio_reader`](Hasher::update_tokio_reader), which is the same thing for the
+    /// Tokio ecosystem.
+    ///
+    /// This method requires the `futures-io` Cargo feature, which is disabled by default but
+    /// enabled on [docs.rs](https://docs.rs).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # async fn example() -> std::io::Result<()> {
+    /// # let reader: &[u8] = b"foo";
+    /// // `reader` is any futures_io::AsyncRead + Unpin.
+    /// let mut hasher = blake3::Hasher::new();
+    /// hasher.update_futures_reader(reader).await?;
+    /// println!("{}", hasher.finalize());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "futures-io")]
+    pub async fn update_futures_reader(
+        &mut self,
+        reader: impl futures_io::AsyncRead + Unpin,
+    ) -> std::io::Result<&mut Self> {
+        io::copy_wide_futures(reader, self).await?;
+        Ok(self)
+    }
+
     /// As [`update`](Hasher::update), but using Rayon-based multithreading
     /// internally.
     ///
@@ -1495,7 +1747,7 @@ impl Hasher {
     /// the `rayon` and `mmap` Cargo features.
     #[cfg(feature = "rayon")]
     pub fn update_rayon(&mut self, input: &[u8]) -> &mut Self {
-        self.update_with_join::<join::RayonJoin>(input)
+        self.update_with_join(input, &join::RayonJoin)
     }
 
     /// As [`update`](Hasher::update), but reading the contents of a file using memory mapping.
@@ -1604,6 +1856,190 @@ impl Hasher {
         }
         Ok(self)
     }
+
+    /// As [`update_mmap`](Hasher::update_mmap), but hashing only the `len` bytes of the file
+    /// starting at `offset`.
+    ///
+    /// Only the requested window is memory mapped, so this is suitable for hashing a region of a
+    /// very large file. The fallback to standard file IO uses positioned reads, and it applies
+    /// when the window is small, using the same heuristic as `update_mmap`, or when the file can't
+    /// be memory mapped. If the file ends before `offset + len`, this method returns an error of
+    /// kind [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof). In that case, if the file
+    /// isn't a regular file, some of the range might have been hashed already.
+    ///
+    /// This method doesn't change how the input is positioned in the BLAKE3 tree. The region is
+    /// hashed as if it were the whole input (or as if it were appended to any input you've already
+    /// given this `Hasher`). To hash a chunk or subtree of a larger input instead, for example to
+    /// split one file across several processes, call
+    /// [`set_input_offset`](hazmat::HasherExt::set_input_offset) first with the same `offset`, and
+    /// merge the results with the [`hazmat`] module.
+    ///
+    /// This method requires the `mmap` Cargo feature, which is disabled by default but enabled on
+    /// [docs.rs](https://docs.rs).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::io;
+    /// # use std::path::Path;
+    /// # fn main() -> io::Result<()> {
+    /// // Hash a 1 MiB payload that follows a 4 KiB header.
+    /// let path = Path::new("file.dat");
+    /// let mut hasher = blake3::Hasher::new();
+    /// hasher.update_mmap_range(path, 4096, 1 << 20)?;
+    /// println!("{}", hasher.finalize());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "mmap")]
+    pub fn update_mmap_range(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        offset: u64,
+        len: u64,
+    ) -> std::io::Result<&mut Self> {
+        let file = std::fs::File::open(path.as_ref())?;
+        if let Some(mmap) = io::maybe_mmap_file_range(&file, offset, len)? {
+            self.update(&mmap);
+        } else {
+            io::copy_range_wide(&file, offset, len, self)?;
+        }
+        Ok(self)
+    }
+
+    /// As [`update_mmap_rayon`](Hasher::update_mmap_rayon), but hashing only the `len` bytes of
+    /// the file starting at `offset`. See [`update_mmap_range`](Hasher::update_mmap_range) for
+    /// details about the range, and `update_mmap_rayon` for the performance warning about
+    /// multithreading. The fallback to standard file IO is single-threaded.
+    ///
+    /// This method requires both the `mmap` and `rayon` Cargo features, which are disabled by
+    /// default but enabled on [docs.rs](https://docs.rs).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::io;
+    /// # use std::path::Path;
+    /// # fn main() -> io::Result<()> {
+    /// # #[cfg(feature = "rayon")]
+    /// # {
+    /// use blake3::hazmat::{ChainingValue, HasherExt};
+    ///
+    /// // Hash the second 1 GiB subtree of a large file, for example in a separate process.
+    /// let path = Path::new("big_file.dat");
+    /// let mut hasher = blake3::Hasher::new();
+    /// hasher.set_input_offset(1 << 30);
+    /// hasher.update_mmap_range_rayon(path, 1 << 30, 1 << 30)?;
+    /// let right_cv: ChainingValue = hasher.finalize_non_root();
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "mmap")]
+    #[cfg(feature = "rayon")]
+    pub fn update_mmap_range_rayon(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        offset: u64,
+        len: u64,
+    ) -> std::io::Result<&mut Self> {
+        let file = std::fs::File::open(path.as_ref())?;
+        if let Some(mmap) = io::maybe_mmap_file_range(&file, offset, len)? {
+            self.update_rayon(&mmap);
+        } else {
+            io::copy_range_wide(&file, offset, len, self)?;
+        }
+        Ok(self)
+    }
+
+    /// As [`update_with_join`](Hasher::update_with_join), but reading the contents of a file
+    /// with positioned reads from multiple threads, without memory mapping.
+    ///
+    /// Memory mapping is the fastest way to hash a large file with multiple threads, but it isn't
+    /// always available. Some network and FUSE filesystems don't support it, or they turn IO
+    /// errors into crashes (`SIGBUS` on Unix) instead of error returns. This method splits the
+    /// file into subtrees of 1 MiB and up, the same way [`update_with_join`] splits a buffer in
+    /// memory, and each task reads its own subtree into a buffer with a positioned read
+    /// (`pread` on Unix, `ReadFile` with an offset on Windows). IO errors are returned as usual.
+    /// Files that aren't regular files (for example pipes), and platforms without positioned
+    /// reads, fall back to single-threaded [`update_reader`](Hasher::update_reader).
+    ///
+    /// The length of the file is read once at the start. If the file shrinks while it's being
+    /// hashed, this method returns an error of kind
+    /// [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof). If it grows, the new bytes are
+    /// ignored.
+    ///
+    /// The input buffers total at most 1 MiB per thread, plus one 64 KiB buffer on the calling
+    /// thread. See the performance warning on [`update_mmap_rayon`](Hasher::update_mmap_rayon),
+    /// which applies here too.
+    ///
+    /// As with [`update_mmap`](Hasher::update_mmap), this method takes a `Path` and opens the
+    /// file internally, because positioned reads ignore the seek position of an existing file
+    /// handle.
+    ///
+    /// [`update_with_join`]: Hasher::update_with_join
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::io;
+    /// # use std::path::Path;
+    /// # fn main() -> io::Result<()> {
+    /// use blake3::join::ThreadJoin;
+    ///
+    /// let path = Path::new("/mnt/nfs/big_file.dat");
+    /// let mut hasher = blake3::Hasher::new();
+    /// hasher.update_pread_with_join(path, &ThreadJoin::new())?;
+    /// println!("{}", hasher.finalize());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn update_pread_with_join<J: join::Join>(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        join: &J,
+    ) -> std::io::Result<&mut Self> {
+        let file = std::fs::File::open(path.as_ref())?;
+        let metadata = file.metadata()?;
+        if metadata.is_file() && io::POSITIONED_READS {
+            io::hash_file_pread(&file, metadata.len(), self, join)?;
+        } else {
+            io::copy_wide(&file, self)?;
+        }
+        Ok(self)
+    }
+
+    /// As [`update_pread_with_join`](Hasher::update_pread_with_join), using the Rayon thread
+    /// pool. This is an alternative to [`update_mmap_rayon`](Hasher::update_mmap_rayon) for
+    /// filesystems where memory mapping is unsupported or unsafe.
+    ///
+    /// This method requires the `rayon` Cargo feature, which is disabled by default but enabled
+    /// on [docs.rs](https://docs.rs).
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use std::io;
+    /// # use std::path::Path;
+    /// # fn main() -> io::Result<()> {
+    /// # #[cfg(feature = "rayon")]
+    /// # {
+    /// let path = Path::new("/mnt/nfs/big_file.dat");
+    /// let mut hasher = blake3::Hasher::new();
+    /// hasher.update_pread_rayon(path)?;
+    /// println!("{}", hasher.finalize());
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn update_pread_rayon(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+    ) -> std::io::Result<&mut Self> {
+        self.update_pread_with_join(path, &join::RayonJoin)
+    }
 }
 
 // Don't derive(Debug), because the state may be secret.
@@ -1638,6 +2074,66 @@ impl std::io::Write for Hasher {
     }
 }
 
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for Hasher {
+    /// This is equivalent to [`update`](#method.update).
+    #[inline]
+    fn poll_write(
+        self: core::pin::Pin<&mut Self>,
+        _cx: &mut core::task::Context<'_>,
+        input: &[u8],
+    ) -> core::task::Poll<std::io::Result<usize>> {
+        self.get_mut().update(input);
+        core::task::Poll::Ready(Ok(input.len()))
+    }
+
+    #[inline]
+    fn poll_flush(
+        self: core::pin::Pin<&mut Self>,
+        _cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<std::io::Result<()>> {
+        core::task::Poll::Ready(Ok(()))
+    }
+
+    #[inline]
+    fn poll_shutdown(
+        self: core::pin::Pin<&mut Self>,
+        _cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<std::io::Result<()>> {
+        core::task::Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl futures_io::AsyncWrite for Hasher {
+    /// This is equivalent to [`update`](#method.update).
+    #[inline]
+    fn poll_write(
+        self: core::pin::Pin<&mut Self>,
+        _cx: &mut core::task::Context<'_>,
+        input: &[u8],
+    ) -> core::task::Poll<std::io::Result<usize>> {
+        self.get_mut().update(input);
+        core::task::Poll::Ready(Ok(input.len()))
+    }
+
+    #[inline]
+    fn poll_flush(
+        self: core::pin::Pin<&mut Self>,
+        _cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<std::io::Result<()>> {
+        core::task::Poll::Ready(Ok(()))
+    }
+
+    #[inline]
+    fn poll_close(
+        self: core::pin::Pin<&mut Self>,
+        _cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<std::io::Result<()>> {
+        core::task::Poll::Ready(Ok(()))
+    }
+}
+
 #[cfg(feature = "zeroize")]
 impl Zeroize for Hasher {
     fn zeroize(&mut self) {
@@ -1695,12 +2191,17 @@ impl OutputReader {
 
     // This helper function handles both the case where the output buffer is
     // shorter than one block, and the case where our position_within_block is
-    // non-zero.
-    fn fill_one_block(&mut self, buf: &mut &mut [u8]) {
+    // non-zero. If `xor` is true, output bytes are XORed into the buffer
+    // rather than copied.
+    fn fill_one_block(&mut self, buf: &mut &mut [u8], xor: bool) {
         let output_block: [u8; BLOCK_LEN] = self.inner.root_output_block();
         let output_bytes = &output_block[self.position_within_block as usize..];
         let take = cmp::min(buf.len(), output_bytes.len());
-        buf[..take].copy_from_slice(&output_bytes[..take]);
+        if xor {
+            xor_bytes(&mut buf[..take], &output_bytes[..take]);
+        } else {
+            buf[..take].copy_from_slice(&output_bytes[..take]);
+        }
         self.position_within_block += take as u8;
         if self.position_within_block == BLOCK_LEN as u8 {
             self.inner.counter += 1;
@@ -1718,46 +2219,140 @@ impl OutputReader {
     /// calling `fill` repeatedly with a short-length or odd-length slice will
     /// end up performing the same compression multiple times. If you're
     /// reading output in a loop, prefer a slice length that's a multiple of
-    /// [`BLOCK_LEN`] (64 bytes).
+    /// [`BLOCK_LEN`] (64 bytes), or wrap the reader in a
+    /// [`BufferedOutputReader`].
+    ///
+    /// This method is always single-threaded. For multithreading support, see
+    /// [`fill_rayon`](OutputReader::fill_rayon) (enabled with the `rayon` Cargo
+    /// feature) and [`fill_with_join`](OutputReader::fill_with_join).
     ///
     /// The maximum output size of BLAKE3 is 2<sup>64</sup>-1 bytes. If you try
     /// to extract more than that, for example by seeking near the end and
     /// reading further, the behavior is unspecified.
     ///
     /// [`Read::read`]: #method.read
-    pub fn fill(&mut self, mut buf: &mut [u8]) {
+    pub fn fill(&mut self, buf: &mut [u8]) {
+        self.fill_with_join(buf, &join::SerialJoin);
+    }
+
+    /// As [`fill`](OutputReader::fill), but using the given [`Join`] implementation to generate
+    /// output blocks on multiple threads.
+    ///
+    /// Every output block depends only on the root node and its block counter, so the whole
+    /// blocks of `buf` are split into contiguous ranges that are computed independently, each one
+    /// with the widest SIMD implementation available. Ranges shorter than the `Join`'s
+    /// [`min_subtree_len`](join::Join::min_subtree_len), or shorter than 16 KiB, aren't split
+    /// further. The output bytes and the final position are exactly the same as with `fill`.
+    ///
+    /// See also [`fill_rayon`](OutputReader::fill_rayon) and the [`join`] module.
+    pub fn fill_with_join<J: join::Join>(&mut self, mut buf: &mut [u8], join: &J) {
         if buf.is_empty() {
             return;
         }
 
         // If we're partway through a block, try to get to a block boundary.
         if self.position_within_block != 0 {
-            self.fill_one_block(&mut buf);
+            self.fill_one_block(&mut buf, false);
         }
 
         let full_blocks = buf.len() / BLOCK_LEN;
         let full_blocks_len = full_blocks * BLOCK_LEN;
         if full_blocks > 0 {
             debug_assert_eq!(0, self.position_within_block);
+            xof_wide(
+                &self.inner,
+                self.inner.counter,
+                &mut buf[..full_blocks_len],
+                join,
+            );
+            self.inner.counter += full_blocks as u64;
+            buf = &mut buf[full_blocks * BLOCK_LEN..];
+        }
+
+        if !buf.is_empty() {
+            debug_assert!(buf.len() < BLOCK_LEN);
+            self.fill_one_block(&mut buf, false);
+            debug_assert!(buf.is_empty());
+        }
+    }
+
+    /// As [`fill`](OutputReader::fill), but using Rayon-based multithreading internally. This is
+    /// equivalent to [`fill_with_join`](OutputReader::fill_with_join) with
+    /// [`RayonJoin`](join::RayonJoin).
+    ///
+    /// This method is gated by the `rayon` Cargo feature, which is disabled by default but enabled
+    /// on [docs.rs](https://docs.rs).
+    ///
+    /// As with [`Hasher::update_rayon`], the output buffer needs to be large to get any
+    /// performance benefit from multithreading. This is intended for generating megabytes or
+    /// gigabytes of output at once.
+    #[cfg(feature = "rayon")]
+    pub fn fill_rayon(&mut self, buf: &mut [u8]) {
+        self.fill_with_join(buf, &join::RayonJoin);
+    }
+
+    /// XOR output bytes into a buffer and advance the position of the
+    /// `OutputReader`, like a stream cipher applying its keystream.
+    ///
+    /// This is equivalent to calling [`fill`](OutputReader::fill) on a
+    /// temporary buffer and XORing that into `buf`, but it doesn't need a
+    /// temporary buffer the size of `buf`. Output is generated a few blocks at a
+    /// time, as wide as the SIMD implementation allows, and XORed in while it's
+    /// still in cache. As with `fill`, use
+    /// [`set_position`](OutputReader::set_position) or [`Seek::seek`] to
+    /// apply the output from some other offset, and prefer slice lengths that
+    /// are a multiple of [`BLOCK_LEN`]. For short or odd-length slices, see
+    /// [`BufferedOutputReader::xor_into`].
+    ///
+    /// With the `traits-preview` Cargo feature, `OutputReader` also implements
+    /// the [`cipher`](https://crates.io/crates/cipher) crate's
+    /// `StreamCipher` and `StreamCipherSeek` traits, using this method.
+    ///
+    /// [`Seek::seek`]: #method.seek
+    pub fn xor_into(&mut self, mut buf: &mut [u8]) {
+        if buf.is_empty() {
+            return;
+        }
+
+        // If we're partway through a block, try to get to a block boundary.
+        if self.position_within_block != 0 {
+            self.fill_one_block(&mut buf, true);
+        }
+
+        let mut stream = [0; MAX_SIMD_DEGREE * BLOCK_LEN];
+        let stream_len = self.inner.platform.simd_degree() * BLOCK_LEN;
+        while buf.len() >= BLOCK_LEN {
+            debug_assert_eq!(0, self.position_within_block);
+            let take = cmp::min(stream_len, buf.len() - buf.len() % BLOCK_LEN);
             self.inner.platform.xof_many(
                 &self.inner.input_chaining_value,
                 &self.inner.block,
                 self.inner.block_len,
                 self.inner.counter,
                 self.inner.flags | ROOT,
-                &mut buf[..full_blocks_len],
+                &mut stream[..take],
             );
-            self.inner.counter += full_blocks as u64;
-            buf = &mut buf[full_blocks * BLOCK_LEN..];
+            xor_bytes(&mut buf[..take], &stream[..take]);
+            self.inner.counter += (take / BLOCK_LEN) as u64;
+            buf = &mut buf[take..];
         }
 
         if !buf.is_empty() {
-            debug_assert!(buf.len() < BLOCK_LEN);
-            self.fill_one_block(&mut buf);
+            self.fill_one_block(&mut buf, true);
             debug_assert!(buf.is_empty());
         }
     }
 
+    /// Seek to `position` and then XOR output bytes into `buf`, as with
+    /// [`xor_into`](OutputReader::xor_into). This is shorthand for calling
+    /// [`set_position`](OutputReader::set_position) first, convenient when
+    /// encrypting or decrypting a random-access region of a larger message.
+    /// Afterwards the position is `position + buf.len()`.
+    pub fn xor_into_at(&mut self, position: u64, buf: &mut [u8]) {
+        self.set_position(position);
+        self.xor_into(buf);
+    }
+
     /// Return the current read position in the output stream. This is
     /// equivalent to [`Seek::stream_position`], except that it doesn't return
     /// a `Result`. The position of a new `OutputReader` starts at 0, and each
@@ -1783,6 +2378,39 @@ impl OutputReader {
     }
 }
 
+fn xor_bytes(dest: &mut [u8], src: &[u8]) {
+    debug_assert_eq!(dest.len(), src.len());
+    for (d, s) in dest.iter_mut().zip(src) {
+        *d ^= s;
+    }
+}
+
+// The smallest range of output that xof_wide() splits between two threads.
+const MIN_XOF_SPLIT_LEN: usize = 16 * CHUNK_LEN;
+
+// Generate whole output blocks, starting from block number `counter`, recursively splitting the
+// output buffer in half with `join`.
+fn xof_wide<J: join::Join>(output: &Output, counter: u64, out: &mut [u8], join: &J) {
+    debug_assert_eq!(0, out.len() % BLOCK_LEN, "whole blocks only");
+    if out.len() <= cmp::max(MIN_XOF_SPLIT_LEN, join.min_subtree_len()) {
+        output.platform.xof_many(
+            &output.input_chaining_value,
+            &output.block,
+            output.block_len,
+            counter,
+            output.flags | ROOT,
+            out,
+        );
+        return;
+    }
+    let left_blocks = out.len() / BLOCK_LEN / 2;
+    let (left, right) = out.split_at_mut(left_blocks * BLOCK_LEN);
+    join.join(
+        || xof_wide(output, counter, left, join),
+        || xof_wide(output, counter + left_blocks as u64, right, join),
+    );
+}
+
 // Don't derive(Debug), because the state may be secret.
 impl fmt::Debug for OutputReader {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -1804,25 +2432,88 @@ impl std::io::Read for OutputReader {
 #[cfg(feature = "std")]
 impl std::io::Seek for OutputReader {
     fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
-        let max_position = u64::max_value() as i128;
-        let target_position: i128 = match pos {
-            std::io::SeekFrom::Start(x) => x as i128,
-            std::io::SeekFrom::Current(x) => self.position() as i128 + x as i128,
-            std::io::SeekFrom::End(_) => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    "seek from end not supported",
-                ));
-            }
-        };
-        if target_position < 0 {
+        self.set_position(seek_target(self.position(), pos)?);
+        Ok(self.position())
+    }
+}
+
+// The Seek logic shared by OutputReader and BufferedOutputReader.
+#[cfg(feature = "std")]
+fn seek_target(position: u64, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+    let max_position = u64::max_value() as i128;
+    let target_position: i128 = match pos {
+        std::io::SeekFrom::Start(x) => x as i128,
+        std::io::SeekFrom::Current(x) => position as i128 + x as i128,
+        std::io::SeekFrom::End(_) => {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidInput,
-                "seek before start",
+                "seek from end not supported",
             ));
         }
-        self.set_position(cmp::min(target_position, max_position) as u64);
-        Ok(self.position())
+    };
+    if target_position < 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "seek before start",
+        ));
+    }
+    Ok(cmp::min(target_position, max_position) as u64)
+}
+
+// Output is always ready, so the async implementations below never return Pending.
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncRead for OutputReader {
+    #[inline]
+    fn poll_read(
+        self: core::pin::Pin<&mut Self>,
+        _cx: &mut core::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> core::task::Poll<std::io::Result<()>> {
+        let unfilled = buf.initialize_unfilled();
+        let len = unfilled.len();
+        self.get_mut().fill(unfilled);
+        buf.advance(len);
+        core::task::Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncSeek for OutputReader {
+    fn start_seek(self: core::pin::Pin<&mut Self>, pos: std::io::SeekFrom) -> std::io::Result<()> {
+        std::io::Seek::seek(self.get_mut(), pos)?;
+        Ok(())
+    }
+
+    fn poll_complete(
+        self: core::pin::Pin<&mut Self>,
+        _cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<std::io::Result<u64>> {
+        core::task::Poll::Ready(Ok(self.position()))
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl futures_io::AsyncRead for OutputReader {
+    #[inline]
+    fn poll_read(
+        self: core::pin::Pin<&mut Self>,
+        _cx: &mut core::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> core::task::Poll<std::io::Result<usize>> {
+        self.get_mut().fill(buf);
+        core::task::Poll::Ready(Ok(buf.len()))
+    }
+}
+
+#[cfg(feature = "futures-io")]
+impl futures_io::AsyncSeek for OutputReader {
+    fn poll_seek(
+        self: core::pin::Pin<&mut Self>,
+        _cx: &mut core::task::Context<'_>,
+        pos: std::io::SeekFrom,
+    ) -> core::task::Poll<std::io::Result<u64>> {
+        core::task::Poll::Ready(std::io::Seek::seek(self.get_mut(), pos))
     }
 }
 
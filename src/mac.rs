@@ -0,0 +1,159 @@
+//! A keyed hasher with constant-time tag verification.
+
+use crate::{Hash, Hasher, KEY_LEN, OUT_LEN};
+use core::fmt;
+
+/// An incremental message authentication code (MAC), with constant-time tag verification.
+///
+/// This is a thin wrapper around [`Hasher::new_keyed`]. Unlike a plain `Hasher`, it doesn't
+/// give you a [`Hash`](struct@Hash) to compare yourself. Instead, [`verify`](Mac::verify) and
+/// [`verify_truncated`](Mac::verify_truncated) compare the expected tag in constant time, and
+/// `verify_truncated` rejects tags that are too short to be secure. Mistakes like comparing tags
+/// as raw bytes, or accepting an empty tag because an empty prefix trivially matches, aren't
+/// possible with this interface.
+///
+/// [`finalize`](Mac::finalize) is still available for producing tags.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), blake3::MacError> {
+/// let key = [42; 32];
+/// let tag = blake3::Mac::new(&key).update(b"message").finalize();
+///
+/// // Later, on the receiving end:
+/// blake3::Mac::new(&key).update(b"message").verify(tag.as_bytes())?;
+/// blake3::Mac::new(&key).update(b"message").verify_truncated(&tag.as_bytes()[..16])?;
+/// assert!(blake3::Mac::new(&key).update(b"forgery").verify(tag.as_bytes()).is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Mac {
+    hasher: Hasher,
+}
+
+impl Mac {
+    /// The shortest tag accepted by [`verify_truncated`](Mac::verify_truncated), 16 bytes (128
+    /// bits). An N-bit tag allows a forgery with probability 2<sup>-N</sup> per attempt.
+    pub const MIN_TAG_LEN: usize = 16;
+
+    /// Construct a new `Mac` with the given key. See [`Hasher::new_keyed`].
+    pub fn new(key: &[u8; KEY_LEN]) -> Self {
+        Self {
+            hasher: Hasher::new_keyed(key),
+        }
+    }
+
+    /// Add input bytes to the MAC state. See [`Hasher::update`].
+    pub fn update(&mut self, input: &[u8]) -> &mut Self {
+        self.hasher.update(input);
+        self
+    }
+
+    /// Reset the `Mac` to its initial state, keeping the key. See [`Hasher::reset`].
+    pub fn reset(&mut self) -> &mut Self {
+        self.hasher.reset();
+        self
+    }
+
+    /// Finalize the MAC state and return the 32-byte tag. This method is idempotent, and calling
+    /// it multiple times will give the same result.
+    ///
+    /// The returned [`Hash`](struct@Hash) has a constant-time `PartialEq` implementation, but when
+    /// you're checking a tag you received, prefer [`verify`](Mac::verify).
+    pub fn finalize(&self) -> Hash {
+        self.hasher.finalize()
+    }
+
+    /// Check a 32-byte tag, in constant time.
+    pub fn verify(&self, tag: &[u8; OUT_LEN]) -> Result<(), MacError> {
+        if self.finalize() == *tag {
+            Ok(())
+        } else {
+            Err(MacError(MacErrorInner::Mismatch))
+        }
+    }
+
+    /// Check a truncated tag, in constant time.
+    ///
+    /// The tag must be between [`MIN_TAG_LEN`](Mac::MIN_TAG_LEN) and 32 bytes long, and it's
+    /// compared against the same number of bytes from the start of the full tag. (Shorter BLAKE3
+    /// outputs are prefixes of longer ones.) A tag of any other length returns an error without
+    /// comparing anything. The tag length itself isn't secret.
+    pub fn verify_truncated(&self, tag: &[u8]) -> Result<(), MacError> {
+        if tag.len() < Self::MIN_TAG_LEN || tag.len() > OUT_LEN {
+            return Err(MacError(MacErrorInner::InvalidLen(tag.len())));
+        }
+        let full_tag = self.finalize();
+        if constant_time_eq::constant_time_eq(&full_tag.as_bytes()[..tag.len()], tag) {
+            Ok(())
+        } else {
+            Err(MacError(MacErrorInner::Mismatch))
+        }
+    }
+}
+
+// Don't derive(Debug), because the state may be secret.
+impl fmt::Debug for Mac {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Mac").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Write for Mac {
+    /// This is equivalent to [`update`](#method.update).
+    #[inline]
+    fn write(&mut self, input: &[u8]) -> std::io::Result<usize> {
+        self.update(input);
+        Ok(input.len())
+    }
+
+    #[inline]
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for Mac {
+    fn zeroize(&mut self) {
+        // Destructuring to trigger compile error as a reminder to update this impl.
+        let Self { hasher } = self;
+
+        hasher.zeroize();
+    }
+}
+
+/// The error type for [`Mac::verify`] and [`Mac::verify_truncated`].
+///
+/// The `.to_string()` representation of this error currently distinguishes between bad length
+/// errors and mismatched tags. This is to help with logging and debugging, but it isn't a stable
+/// API detail, and it may change at any time.
+#[derive(Clone, Debug)]
+pub struct MacError(MacErrorInner);
+
+#[derive(Clone, Debug)]
+enum MacErrorInner {
+    Mismatch,
+    InvalidLen(usize),
+}
+
+impl fmt::Display for MacError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            MacErrorInner::Mismatch => write!(f, "MAC tag mismatch"),
+            MacErrorInner::InvalidLen(len) => write!(
+                f,
+                "expected a MAC tag of {} to {} bytes, received {}",
+                Mac::MIN_TAG_LEN,
+                OUT_LEN,
+                len,
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for MacError {}
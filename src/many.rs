@@ -0,0 +1,202 @@
+//! Hashing many independent short messages at once.
+//!
+//! [`Platform::hash_many`] compresses up to `MAX_SIMD_DEGREE` inputs in parallel, one input per
+//! SIMD lane, but all the inputs have to be the same number of whole blocks. So this module sorts
+//! single-chunk messages into buckets by their number of whole blocks. A full bucket goes through
+//! `hash_many` in one call. For messages whose length is a multiple of `BLOCK_LEN`, that's the
+//! whole message, with `CHUNK_END` and `ROOT` set on the last block. Otherwise it's every block but
+//! the last one, and the final partial block is compressed separately, since the SIMD kernels
+//! assume full blocks. Messages shorter than one block don't benefit from SIMD, and messages
+//! longer than one chunk go through the regular `hash_all_at_once` path, which already uses SIMD
+//! across chunks.
+
+use crate::platform::{MAX_SIMD_DEGREE, Platform};
+use crate::{
+    BLOCK_LEN, CHUNK_END, CHUNK_LEN, CHUNK_START, CVWords, DERIVE_KEY_MATERIAL, Hash, IV,
+    IncrementCounter, KEY_LEN, KEYED_HASH, OUT_LEN, Output, ROOT, platform,
+};
+use arrayref::array_ref;
+use arrayvec::ArrayVec;
+
+const MAX_BLOCKS: usize = CHUNK_LEN / BLOCK_LEN;
+
+// Two buckets for each number of whole blocks: one for lengths that are a multiple of BLOCK_LEN,
+// and one for lengths that have a partial block left over.
+const NUM_BUCKETS: usize = 2 * (MAX_BLOCKS + 1);
+
+type Bucket = ArrayVec<usize, MAX_SIMD_DEGREE>;
+
+// Hash each input, and call `write_output` with its index and its hash. Outputs are written in
+// an unspecified order.
+fn hash_many_impl(
+    inputs: &[&[u8]],
+    key: &CVWords,
+    flags: u8,
+    write_output: &mut impl FnMut(usize, [u8; OUT_LEN]),
+) {
+    let platform = Platform::detect();
+    let degree = platform.simd_degree();
+    let mut buckets: [Bucket; NUM_BUCKETS] = core::array::from_fn(|_| Bucket::new());
+    for (i, input) in inputs.iter().enumerate() {
+        if input.len() < BLOCK_LEN || input.len() > CHUNK_LEN {
+            let output = crate::hash_all_at_once(input, key, flags, &crate::join::SerialJoin);
+            write_output(i, output.root_hash().0);
+            continue;
+        }
+        let bucket_index = 2 * (input.len() / BLOCK_LEN) + (input.len() % BLOCK_LEN == 0) as usize;
+        let bucket = &mut buckets[bucket_index];
+        bucket.push(i);
+        if bucket.len() == degree {
+            hash_bucket(inputs, bucket, key, flags, platform, write_output);
+            bucket.clear();
+        }
+    }
+    for bucket in &buckets {
+        if !bucket.is_empty() {
+            hash_bucket(inputs, bucket, key, flags, platform, write_output);
+        }
+    }
+}
+
+// Dispatch to a monomorphized hash_bucket_blocks() for the number of whole blocks in this bucket.
+fn hash_bucket(
+    inputs: &[&[u8]],
+    bucket: &[usize],
+    key: &CVWords,
+    flags: u8,
+    platform: Platform,
+    write_output: &mut impl FnMut(usize, [u8; OUT_LEN]),
+) {
+    macro_rules! dispatch {
+        ($($blocks:literal)*) => {
+            match inputs[bucket[0]].len() / BLOCK_LEN {
+                $($blocks => hash_bucket_blocks::<{ $blocks * BLOCK_LEN }>(
+                    inputs, bucket, key, flags, platform, write_output,
+                ),)*
+                _ => unreachable!(),
+            }
+        };
+    }
+    dispatch!(1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16);
+}
+
+fn hash_bucket_blocks<const N: usize>(
+    inputs: &[&[u8]],
+    bucket: &[usize],
+    key: &CVWords,
+    flags: u8,
+    platform: Platform,
+    write_output: &mut impl FnMut(usize, [u8; OUT_LEN]),
+) {
+    // All the inputs in a bucket have the same length, so it's enough to check the first one.
+    let whole = inputs[bucket[0]].len() == N;
+    let arrays: ArrayVec<&[u8; N], MAX_SIMD_DEGREE> = bucket
+        .iter()
+        .map(|&i| <&[u8; N]>::try_from(&inputs[i][..N]).unwrap())
+        .collect();
+    let flags_end = if whole { CHUNK_END | ROOT } else { 0 };
+    let mut cvs = [0; MAX_SIMD_DEGREE * OUT_LEN];
+    platform.hash_many(
+        &arrays,
+        key,
+        0,
+        IncrementCounter::No,
+        flags,
+        CHUNK_START,
+        flags_end,
+        &mut cvs,
+    );
+    for (&i, cv) in bucket.iter().zip(cvs.chunks_exact(OUT_LEN)) {
+        if whole {
+            write_output(i, *array_ref!(cv, 0, OUT_LEN));
+            continue;
+        }
+        // Compress the final partial block as the root.
+        let last_block = &inputs[i][N..];
+        let mut block = [0; BLOCK_LEN];
+        block[..last_block.len()].copy_from_slice(last_block);
+        let output = Output {
+            input_chaining_value: platform::words_from_le_bytes_32(array_ref!(cv, 0, OUT_LEN)),
+            block,
+            block_len: last_block.len() as u8,
+            counter: 0,
+            flags: flags | CHUNK_END,
+            platform,
+        };
+        write_output(i, output.root_hash().0);
+    }
+}
+
+/// Hash many independent messages, and write their hashes to `outputs`.
+///
+/// This is equivalent to calling [`hash`](crate::hash) on each input, but it uses SIMD
+/// parallelism across messages. For inputs of one chunk (1024 bytes) or less, `hash` can only
+/// compress one block at a time. This function sorts those inputs by length, and it compresses
+/// inputs with the same number of 64-byte blocks in parallel, as many as the SIMD implementation
+/// allows, up to 16 at a time with AVX-512. The last block of an input whose length isn't a
+/// multiple of 64 can't go through the SIMD implementation, and inputs shorter than 64 bytes don't
+/// benefit at all. Inputs longer than 1024 bytes are hashed one at a time, with SIMD parallelism
+/// across their chunks, as in `hash`.
+///
+/// This function is always single-threaded.
+///
+/// # Panics
+///
+/// Panics if `inputs` and `outputs` aren't the same length.
+///
+/// # Example
+///
+/// ```
+/// let inputs: [&[u8]; 3] = [b"foo", b"bar", &[42; 1000]];
+/// let mut outputs = [blake3::Hash::from_bytes([0; 32]); 3];
+/// blake3::hash_many(&inputs, &mut outputs);
+/// for (input, output) in inputs.iter().zip(&outputs) {
+///     assert_eq!(blake3::hash(input), *output);
+/// }
+/// ```
+pub fn hash_many(inputs: &[&[u8]], outputs: &mut [Hash]) {
+    assert_eq!(inputs.len(), outputs.len(), "mismatched output length");
+    hash_many_impl(inputs, IV, 0, &mut |i, hash| outputs[i] = Hash(hash));
+}
+
+/// Compute the keyed hashes of many independent messages with the same key, and write them to
+/// `outputs`.
+///
+/// This is equivalent to calling [`keyed_hash`](crate::keyed_hash) on each input. See
+/// [`hash_many`] for details about SIMD parallelism.
+///
+/// # Panics
+///
+/// Panics if `inputs` and `outputs` aren't the same length.
+pub fn keyed_hash_many(key: &[u8; KEY_LEN], inputs: &[&[u8]], outputs: &mut [Hash]) {
+    assert_eq!(inputs.len(), outputs.len(), "mismatched output length");
+    let key_words = platform::words_from_le_bytes_32(key);
+    hash_many_impl(inputs, &key_words, KEYED_HASH, &mut |i, hash| {
+        outputs[i] = Hash(hash)
+    });
+}
+
+/// Derive many keys from independent key materials with the same context string, and write them
+/// to `outputs`.
+///
+/// This is equivalent to calling [`derive_key`](crate::derive_key) on each key material, but the
+/// context string is only hashed once. See [`hash_many`] for details about SIMD parallelism.
+///
+/// # Panics
+///
+/// Panics if `key_materials` and `outputs` aren't the same length.
+pub fn derive_key_many(context: &str, key_materials: &[&[u8]], outputs: &mut [[u8; OUT_LEN]]) {
+    assert_eq!(
+        key_materials.len(),
+        outputs.len(),
+        "mismatched output length"
+    );
+    let context_key = crate::hazmat::hash_derive_key_context(context);
+    let context_key_words = platform::words_from_le_bytes_32(&context_key);
+    hash_many_impl(
+        key_materials,
+        &context_key_words,
+        DERIVE_KEY_MATERIAL,
+        &mut |i, key| outputs[i] = key,
+    );
+}
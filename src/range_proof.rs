@@ -0,0 +1,446 @@
+//! Merkle inclusion proofs for byte ranges of an input.
+//!
+//! A [`RangeProof`] holds the chaining values that a verifier needs, together with the bytes of a
+//! chunk-aligned range, to recompute the root hash: the siblings of the subtrees along the left
+//! and right edges of the range, from the root down. Every subtree that's entirely inside the range
+//! is hashed from the range bytes, and every subtree that's entirely outside it contributes one
+//! chaining value to the proof, so a proof is at most `2 * MAX_DEPTH` chaining values, no matter
+//! how long the input is.
+
+use crate::hazmat::{
+    ChainingValue, HasherExt, Mode, left_subtree_len, merge_subtrees_non_root, merge_subtrees_root,
+};
+use crate::{CHUNK_LEN, Hash, Hasher, MAX_DEPTH, OUT_LEN};
+use arrayref::array_ref;
+use arrayvec::ArrayVec;
+use core::fmt;
+use core::ops::Range;
+
+const VERSION: u8 = 1;
+
+const HEADER_LEN: usize = 1 + 8 + 8 + 8 + 1;
+
+// Each level of the tree below the root has at most two subtrees that are partly in the range,
+// and each of those has at most one sibling outside it.
+const MAX_CVS: usize = 2 * MAX_DEPTH;
+
+const MAX_LEN: usize = HEADER_LEN + MAX_CVS * OUT_LEN;
+
+/// A proof that a range of bytes is part of an input with a given root hash, returned by
+/// [`RangeProof::new`].
+///
+/// The range must be chunk-aligned: its start must be a multiple of [`CHUNK_LEN`], and its end
+/// must be a multiple of [`CHUNK_LEN`] or the end of the input. It can't be empty, unless the
+/// whole input is empty. [`verify`](RangeProof::verify) checks the bytes of the range against the
+/// root hash. It doesn't need `std` or `alloc`, and neither does anything else in this type.
+///
+/// The byte encoding returned by [`as_bytes`](RangeProof::as_bytes) is versioned and stable, so
+/// proofs can be sent to other processes and implemented in other languages. Version 1 is laid out
+/// as follows, with little-endian integers:
+///
+/// ```text
+/// offset  len   field
+/// 0       1     version (1)
+/// 1       8     input length
+/// 9       8     range start
+/// 17      8     range end
+/// 25      1     number of chaining values (N)
+/// 26      32*N  sibling chaining values, left to right
+/// ```
+///
+/// The chaining values are in the order of a depth-first, left-to-right walk of the tree, which is
+/// also left-to-right by input offset. [`from_bytes`](RangeProof::from_bytes) checks that the
+/// encoding is well-formed, for example that it has the right number of chaining values for its
+/// range, but of course only [`verify`](RangeProof::verify) can tell whether a proof is correct.
+///
+/// The position of the range is verified, because every chunk's chaining value depends on its
+/// offset. The input length is only verified if the range includes the end of the input. If it
+/// doesn't, a proof with the wrong input length can still verify, as long as the tree has the same
+/// shape along the path to the range.
+///
+/// With the `serde` Cargo feature, this type implements `Serialize` and `Deserialize`, using the
+/// same byte encoding and the same validation.
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), blake3::RangeProofError> {
+/// use blake3::hazmat::Mode;
+/// use blake3::{CHUNK_LEN, RangeProof};
+///
+/// let input = [42; 100 * CHUNK_LEN];
+/// let hash = blake3::hash(&input);
+/// let range = 10 * CHUNK_LEN..20 * CHUNK_LEN;
+/// let proof = RangeProof::new(&input, range.start as u64..range.end as u64, Mode::Hash)?;
+///
+/// // The verifier only needs the root hash, the proof, and the bytes of the range.
+/// let proof = RangeProof::from_bytes(proof.as_bytes())?;
+/// proof.verify(&input[range.clone()], &hash, Mode::Hash)?;
+///
+/// let mut wrong = input[range].to_vec();
+/// wrong[0] ^= 1;
+/// assert!(proof.verify(&wrong, &hash, Mode::Hash).is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct RangeProof {
+    bytes: ArrayVec<u8, MAX_LEN>,
+}
+
+impl RangeProof {
+    /// Compute the proof for `range` of `input`, in the given `mode`.
+    ///
+    /// This hashes all of the input outside the range. This returns an error if the range isn't
+    /// chunk-aligned or if it extends past the end of the input.
+    pub fn new(input: &[u8], range: Range<u64>, mode: Mode) -> Result<Self, RangeProofError> {
+        let input_len = input.len() as u64;
+        check_range(input_len, &range)?;
+        let mut bytes = ArrayVec::new();
+        bytes.push(VERSION);
+        bytes.extend(input_len.to_le_bytes());
+        bytes.extend(range.start.to_le_bytes());
+        bytes.extend(range.end.to_le_bytes());
+        bytes.push(0);
+        let mut num_cvs = 0;
+        if range != (0..input_len) {
+            prove_subtree(input, 0, input_len, &range, mode, &mut |cv| {
+                bytes.extend(*cv);
+                num_cvs += 1;
+            });
+        }
+        bytes[HEADER_LEN - 1] = num_cvs;
+        Ok(Self { bytes })
+    }
+
+    /// The encoded bytes of this proof.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Decode a proof from the bytes returned by [`as_bytes`](RangeProof::as_bytes).
+    ///
+    /// This returns an error if the version is unknown, if the length is wrong, if the range isn't
+    /// a valid range of the input, or if the number of chaining values doesn't match the range.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, RangeProofError> {
+        if bytes.is_empty() {
+            return err(RangeProofErrorInner::InvalidLen(0));
+        }
+        if bytes[0] != VERSION {
+            return err(RangeProofErrorInner::UnsupportedVersion(bytes[0]));
+        }
+        if bytes.len() < HEADER_LEN {
+            return err(RangeProofErrorInner::InvalidLen(bytes.len()));
+        }
+        let num_cvs = bytes[HEADER_LEN - 1] as usize;
+        if bytes.len() != HEADER_LEN + num_cvs * OUT_LEN {
+            return err(RangeProofErrorInner::InvalidLen(bytes.len()));
+        }
+        let mut proof = Self {
+            bytes: ArrayVec::new(),
+        };
+        if proof.bytes.try_extend_from_slice(bytes).is_err() {
+            return err(RangeProofErrorInner::InvalidLen(bytes.len()));
+        }
+        let input_len = proof.input_len();
+        let range = proof.range();
+        check_range(input_len, &range)?;
+        let expected = if range == (0..input_len) {
+            0
+        } else {
+            count_siblings(0, input_len, &range)
+        };
+        if num_cvs != expected {
+            return err(RangeProofErrorInner::InvalidNumCvs {
+                found: num_cvs,
+                expected,
+            });
+        }
+        Ok(proof)
+    }
+
+    /// The length of the whole input.
+    pub fn input_len(&self) -> u64 {
+        u64::from_le_bytes(*array_ref!(self.bytes, 1, 8))
+    }
+
+    /// The range of the input that this proof is for.
+    pub fn range(&self) -> Range<u64> {
+        let start = u64::from_le_bytes(*array_ref!(self.bytes, 9, 8));
+        let end = u64::from_le_bytes(*array_ref!(self.bytes, 17, 8));
+        start..end
+    }
+
+    /// Check the bytes of the range against the root hash, in the given `mode`.
+    ///
+    /// This returns an error if `range_bytes` is the wrong length for the range, or if the root
+    /// hash doesn't match. Use [`RangeProofError::is_mismatch`] to tell those apart.
+    pub fn verify(
+        &self,
+        range_bytes: &[u8],
+        hash: &Hash,
+        mode: Mode,
+    ) -> Result<(), RangeProofError> {
+        let input_len = self.input_len();
+        let range = self.range();
+        if range_bytes.len() as u64 != range.end - range.start {
+            return err(RangeProofErrorInner::WrongRangeLen {
+                found: range_bytes.len(),
+                expected: range.end - range.start,
+            });
+        }
+        let root_hash = if range == (0..input_len) {
+            new_hasher(mode).update(range_bytes).finalize()
+        } else {
+            let mut cvs = self.bytes[HEADER_LEN..]
+                .chunks_exact(OUT_LEN)
+                .map(|cv| array_ref!(cv, 0, OUT_LEN));
+            // from_bytes() checked the number of chaining values.
+            let mut next_cv = || *cvs.next().unwrap();
+            let (left_cv, right_cv) =
+                verify_subtree(range_bytes, 0, input_len, &range, mode, &mut next_cv);
+            merge_subtrees_root(&left_cv, &right_cv, mode)
+        };
+        // Comparing Hashes is constant-time.
+        if root_hash == *hash {
+            Ok(())
+        } else {
+            err(RangeProofErrorInner::Mismatch)
+        }
+    }
+}
+
+fn new_hasher(mode: Mode) -> Hasher {
+    Hasher::new_internal(&mode.key_words(), mode.flags_byte())
+}
+
+fn check_range(input_len: u64, range: &Range<u64>) -> Result<(), RangeProofError> {
+    let offset_in_chunk = |offset: u64| offset % CHUNK_LEN as u64;
+    let start_ok = offset_in_chunk(range.start) == 0;
+    let end_ok = offset_in_chunk(range.end) == 0 || range.end == input_len;
+    let len_ok = range.start < range.end || (range.start == 0 && range.end == input_len);
+    if start_ok && end_ok && len_ok && range.end <= input_len {
+        Ok(())
+    } else {
+        err(RangeProofErrorInner::InvalidRange {
+            start: range.start,
+            end: range.end,
+            input_len,
+        })
+    }
+}
+
+// The children of the subtree of `len` bytes at `offset`, which is longer than one chunk.
+fn children(offset: u64, len: u64) -> [(u64, u64); 2] {
+    let left_len = left_subtree_len(len);
+    [(offset, left_len), (offset + left_len, len - left_len)]
+}
+
+fn contains(range: &Range<u64>, offset: u64, len: u64) -> bool {
+    range.start <= offset && offset + len <= range.end
+}
+
+fn overlaps(range: &Range<u64>, offset: u64, len: u64) -> bool {
+    offset < range.end && range.start < offset + len
+}
+
+// The subtree of `len` bytes at `offset` is partly inside the range. Recurse into each of its
+// children that's partly inside, and call `sibling` with the chaining value of each child that's
+// entirely outside. `input` is the whole input.
+fn prove_subtree(
+    input: &[u8],
+    offset: u64,
+    len: u64,
+    range: &Range<u64>,
+    mode: Mode,
+    sibling: &mut impl FnMut(&ChainingValue),
+) {
+    for (child_offset, child_len) in children(offset, len) {
+        if contains(range, child_offset, child_len) {
+            continue;
+        } else if overlaps(range, child_offset, child_len) {
+            prove_subtree(input, child_offset, child_len, range, mode, sibling);
+        } else {
+            let child_input = &input[child_offset as usize..][..child_len as usize];
+            let cv = new_hasher(mode)
+                .set_input_offset(child_offset)
+                .update(child_input)
+                .finalize_non_root();
+            sibling(&cv);
+        }
+    }
+}
+
+// The number of chaining values that prove_subtree() would produce.
+fn count_siblings(offset: u64, len: u64, range: &Range<u64>) -> usize {
+    let mut count = 0;
+    for (child_offset, child_len) in children(offset, len) {
+        if contains(range, child_offset, child_len) {
+            continue;
+        } else if overlaps(range, child_offset, child_len) {
+            count += count_siblings(child_offset, child_len, range);
+        } else {
+            count += 1;
+        }
+    }
+    count
+}
+
+// The subtree of `len` bytes at `offset` is partly inside the range. Return its children's
+// chaining values, hashing the ones inside the range from `range_bytes`, recursing into the ones
+// partly inside, and taking the ones outside from `next_cv`.
+fn verify_subtree(
+    range_bytes: &[u8],
+    offset: u64,
+    len: u64,
+    range: &Range<u64>,
+    mode: Mode,
+    next_cv: &mut impl FnMut() -> ChainingValue,
+) -> (ChainingValue, ChainingValue) {
+    let mut child_cvs = [[0; OUT_LEN]; 2];
+    for (i, (child_offset, child_len)) in children(offset, len).into_iter().enumerate() {
+        child_cvs[i] = if contains(range, child_offset, child_len) {
+            let start = (child_offset - range.start) as usize;
+            new_hasher(mode)
+                .set_input_offset(child_offset)
+                .update(&range_bytes[start..][..child_len as usize])
+                .finalize_non_root()
+        } else if overlaps(range, child_offset, child_len) {
+            let (left_cv, right_cv) =
+                verify_subtree(range_bytes, child_offset, child_len, range, mode, next_cv);
+            merge_subtrees_non_root(&left_cv, &right_cv, mode)
+        } else {
+            next_cv()
+        };
+    }
+    (child_cvs[0], child_cvs[1])
+}
+
+impl fmt::Debug for RangeProof {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RangeProof")
+            .field("input_len", &self.input_len())
+            .field("range", &self.range())
+            .field("num_cvs", &self.bytes[HEADER_LEN - 1])
+            .finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for RangeProof {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.as_bytes())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for RangeProof {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct RangeProofVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for RangeProofVisitor {
+            type Value = RangeProof;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("BLAKE3 range proof bytes")
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<RangeProof, E> {
+                RangeProof::from_bytes(bytes).map_err(E::custom)
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<RangeProof, A::Error> {
+                let mut bytes = ArrayVec::<u8, MAX_LEN>::new();
+                while let Some(byte) = seq.next_element()? {
+                    if bytes.try_push(byte).is_err() {
+                        return Err(serde::de::Error::custom(RangeProofError(
+                            RangeProofErrorInner::InvalidLen(MAX_LEN + 1),
+                        )));
+                    }
+                }
+                RangeProof::from_bytes(&bytes).map_err(serde::de::Error::custom)
+            }
+        }
+
+        deserializer.deserialize_bytes(RangeProofVisitor)
+    }
+}
+
+/// The error type for [`RangeProof`].
+///
+/// The `.to_string()` representation of this error currently describes which check failed. This
+/// is to help with logging and debugging, but it isn't a stable API detail, and it may change at
+/// any time.
+#[derive(Clone, Debug)]
+pub struct RangeProofError(RangeProofErrorInner);
+
+#[derive(Clone, Debug)]
+enum RangeProofErrorInner {
+    UnsupportedVersion(u8),
+    InvalidLen(usize),
+    InvalidRange {
+        start: u64,
+        end: u64,
+        input_len: u64,
+    },
+    InvalidNumCvs {
+        found: usize,
+        expected: usize,
+    },
+    WrongRangeLen {
+        found: usize,
+        expected: u64,
+    },
+    Mismatch,
+}
+
+impl RangeProofError {
+    /// Return true if [`RangeProof::verify`] failed because the root hash didn't match. The other
+    /// errors mean that the proof or the range bytes were malformed.
+    pub fn is_mismatch(&self) -> bool {
+        matches!(self.0, RangeProofErrorInner::Mismatch)
+    }
+}
+
+impl fmt::Display for RangeProofError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            RangeProofErrorInner::UnsupportedVersion(version) => {
+                write!(f, "unsupported range proof version: {}", version)
+            }
+            RangeProofErrorInner::InvalidLen(len) => {
+                write!(f, "invalid range proof length: {}", len)
+            }
+            RangeProofErrorInner::InvalidRange {
+                start,
+                end,
+                input_len,
+            } => write!(
+                f,
+                "{}..{} isn't a chunk-aligned range of an input of {} bytes",
+                start, end, input_len,
+            ),
+            RangeProofErrorInner::InvalidNumCvs { found, expected } => write!(
+                f,
+                "range proof has {} chaining values, but its range needs {}",
+                found, expected,
+            ),
+            RangeProofErrorInner::WrongRangeLen { found, expected } => write!(
+                f,
+                "range proof is for {} bytes, but {} were given",
+                expected, found,
+            ),
+            RangeProofErrorInner::Mismatch => write!(f, "BLAKE3 hash mismatch"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RangeProofError {}
+
+fn err<T>(inner: RangeProofErrorInner) -> Result<T, RangeProofError> {
+    Err(RangeProofError(inner))
+}
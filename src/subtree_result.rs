@@ -0,0 +1,371 @@
+//! A wire format for subtree chaining values, and a combiner for them. See [`SubtreeResult`].
+
+use crate::hazmat::{
+    ChainingValue, Mode, ModeKind, left_subtree_len, max_subtree_len, merge_subtrees_non_root,
+    merge_subtrees_root, merge_subtrees_root_xof,
+};
+use crate::{CHUNK_LEN, Hash, OUT_LEN, OutputReader};
+use arrayref::{array_ref, array_refs, mut_array_refs};
+use core::fmt;
+
+const VERSION: u8 = 1;
+
+const ENCODED_LEN: usize = 1 + 1 + 8 + 8 + OUT_LEN;
+
+/// The chaining value of one subtree of a larger input, together with where it goes.
+///
+/// When an input is hashed on several machines, each of them computes the chaining value of its
+/// part with [`HasherExt`](crate::hazmat::HasherExt), and a coordinator merges them with
+/// [`combine_subtrees`] or [`combine_subtrees_xof`]. This type is what gets sent to the
+/// coordinator. With the `serde` Cargo feature it implements `Serialize` and `Deserialize`, and
+/// [`to_bytes`](SubtreeResult::to_bytes) and [`from_bytes`](SubtreeResult::from_bytes) give a
+/// compact, versioned binary encoding of [`ENCODED_LEN`](SubtreeResult::ENCODED_LEN) bytes:
+///
+/// ```text
+/// offset  len  field
+/// 0       1    version (1)
+/// 1       1    mode (0 for Hash, 1 for KeyedHash, 2 for DeriveKeyMaterial)
+/// 2       8    input offset (little-endian)
+/// 10      8    length (little-endian)
+/// 18      32   chaining value
+/// ```
+///
+/// The mode is recorded as a [`ModeKind`], without the key. The key shouldn't travel with the
+/// results, and the combiner needs it anyway to merge them. That means the combiner can catch a
+/// result computed in the wrong mode, but not one computed with the wrong key.
+///
+/// # Example
+///
+/// ```
+/// use blake3::hazmat::{HasherExt, Mode, SubtreeResult, combine_subtrees};
+/// use blake3::{CHUNK_LEN, Hasher};
+///
+/// let input = [42; 5 * CHUNK_LEN];
+/// let mut results = Vec::new();
+/// for (offset, len) in [(4 * CHUNK_LEN, CHUNK_LEN), (0, 4 * CHUNK_LEN)] {
+///     // This part could happen on another machine.
+///     let cv = Hasher::new()
+///         .set_input_offset(offset as u64)
+///         .update(&input[offset..][..len])
+///         .finalize_non_root();
+///     let result = SubtreeResult {
+///         mode: Mode::Hash.kind(),
+///         input_offset: offset as u64,
+///         len: len as u64,
+///         cv,
+///     };
+///     results.push(SubtreeResult::from_bytes(&result.to_bytes())?);
+/// }
+/// assert_eq!(combine_subtrees(&mut results, Mode::Hash)?, blake3::hash(&input));
+/// # Ok::<(), blake3::hazmat::SubtreeError>(())
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct SubtreeResult {
+    /// The mode that the chaining value was computed in.
+    pub mode: ModeKind,
+    /// The offset of the subtree in the whole input, a multiple of [`CHUNK_LEN`].
+    pub input_offset: u64,
+    /// The length of the subtree in bytes.
+    pub len: u64,
+    /// The chaining value of the subtree, from
+    /// [`finalize_non_root`](crate::hazmat::HasherExt::finalize_non_root).
+    pub cv: ChainingValue,
+}
+
+impl SubtreeResult {
+    /// The length of the encoding returned by [`to_bytes`](SubtreeResult::to_bytes).
+    pub const ENCODED_LEN: usize = ENCODED_LEN;
+
+    /// Encode this result in the binary format described [above](SubtreeResult).
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0; Self::ENCODED_LEN];
+        let (version, mode, input_offset, len, cv) =
+            mut_array_refs!(&mut bytes, 1, 1, 8, 8, OUT_LEN);
+        version[0] = VERSION;
+        mode[0] = match self.mode {
+            ModeKind::Hash => 0,
+            ModeKind::KeyedHash => 1,
+            ModeKind::DeriveKeyMaterial => 2,
+        };
+        *input_offset = self.input_offset.to_le_bytes();
+        *len = self.len.to_le_bytes();
+        *cv = self.cv;
+        bytes
+    }
+
+    /// Decode a result from the bytes returned by [`to_bytes`](SubtreeResult::to_bytes).
+    ///
+    /// This only checks the encoding. The offset and length are checked by the combiner.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SubtreeError> {
+        if let Some(&version) = bytes.first().filter(|&&version| version != VERSION) {
+            return err(SubtreeErrorInner::UnsupportedVersion(version));
+        }
+        if bytes.len() != Self::ENCODED_LEN {
+            return err(SubtreeErrorInner::InvalidLen(bytes.len()));
+        }
+        let (_, mode, input_offset, len, cv) =
+            array_refs!(array_ref!(bytes, 0, ENCODED_LEN), 1, 1, 8, 8, OUT_LEN);
+        let mode = match mode[0] {
+            0 => ModeKind::Hash,
+            1 => ModeKind::KeyedHash,
+            2 => ModeKind::DeriveKeyMaterial,
+            other => return err(SubtreeErrorInner::InvalidMode(other)),
+        };
+        Ok(Self {
+            mode,
+            input_offset: u64::from_le_bytes(*input_offset),
+            len: u64::from_le_bytes(*len),
+            cv: *cv,
+        })
+    }
+
+    fn end(&self) -> u64 {
+        // check_results() made sure this doesn't overflow.
+        self.input_offset + self.len
+    }
+}
+
+/// Combine the chaining values of subtrees that together make up a whole input, and return the
+/// root hash.
+///
+/// The results can be in any order, and this sorts them by offset in place. They have to cover
+/// the input from offset zero with no gaps or overlaps, each one has to be a valid subtree in its
+/// position (see [`max_subtree_len`]), and each one has to have been computed in the same kind of
+/// [`Mode`] as `mode`. Any violation is an error that describes the first result that's wrong,
+/// instead of a garbage hash. The end of the last result is the end of the input.
+///
+/// The root node is never a chaining value, so there have to be at least two results. If one
+/// machine hashed the whole input, it should call [`Hasher::finalize`](crate::Hasher::finalize)
+/// itself.
+///
+/// These checks catch results in the wrong place or the wrong mode, but they can't catch a
+/// chaining value that was computed incorrectly, for example with the wrong key.
+pub fn combine_subtrees(results: &mut [SubtreeResult], mode: Mode) -> Result<Hash, SubtreeError> {
+    let (left_cv, right_cv) = combine_root_children(results, mode)?;
+    Ok(merge_subtrees_root(&left_cv, &right_cv, mode))
+}
+
+/// Like [`combine_subtrees`], but return an extended output.
+pub fn combine_subtrees_xof(
+    results: &mut [SubtreeResult],
+    mode: Mode,
+) -> Result<OutputReader, SubtreeError> {
+    let (left_cv, right_cv) = combine_root_children(results, mode)?;
+    Ok(merge_subtrees_root_xof(&left_cv, &right_cv, mode))
+}
+
+fn combine_root_children(
+    results: &mut [SubtreeResult],
+    mode: Mode,
+) -> Result<(ChainingValue, ChainingValue), SubtreeError> {
+    results.sort_unstable_by_key(|result| result.input_offset);
+    let input_len = check_results(results, mode.kind())?;
+    if results.len() == 1 {
+        return err(SubtreeErrorInner::Root { len: input_len });
+    }
+    merge_children(results, 0, input_len, mode)
+}
+
+// Check each of the sorted results on its own, and check that they're contiguous. Return the
+// total input length.
+fn check_results(results: &[SubtreeResult], mode: ModeKind) -> Result<u64, SubtreeError> {
+    if results.is_empty() {
+        return err(SubtreeErrorInner::NoResults);
+    }
+    let mut expected_offset = 0;
+    for result in results {
+        let offset = result.input_offset;
+        if result.mode != mode {
+            return err(SubtreeErrorInner::WrongMode {
+                offset,
+                expected: mode,
+                found: result.mode,
+            });
+        }
+        let offset_in_chunk = offset % CHUNK_LEN as u64;
+        if offset_in_chunk != 0 {
+            return err(SubtreeErrorInner::Unaligned { offset });
+        }
+        if offset < expected_offset {
+            return err(SubtreeErrorInner::Overlap {
+                offset,
+                end: expected_offset,
+            });
+        }
+        if offset > expected_offset {
+            return err(SubtreeErrorInner::Gap {
+                offset: expected_offset,
+                end: offset,
+            });
+        }
+        if result.len == 0 {
+            return err(SubtreeErrorInner::Empty { offset });
+        }
+        if let Some(max) = max_subtree_len(offset).filter(|&max| result.len > max) {
+            return err(SubtreeErrorInner::TooLong {
+                offset,
+                len: result.len,
+                max,
+            });
+        }
+        expected_offset = match offset.checked_add(result.len) {
+            Some(end) => end,
+            None => return err(SubtreeErrorInner::Overflow { offset }),
+        };
+    }
+    Ok(expected_offset)
+}
+
+// Return the chaining values of the children of the subtree of `len` bytes at `offset`, which
+// more than one of the contiguous `results` cover exactly.
+fn merge_children(
+    results: &[SubtreeResult],
+    offset: u64,
+    len: u64,
+    mode: Mode,
+) -> Result<(ChainingValue, ChainingValue), SubtreeError> {
+    let left_end = offset + left_subtree_len(len);
+    let split = results.partition_point(|result| result.input_offset < left_end);
+    let (left_results, right_results) = results.split_at(split);
+    // The left results can't be empty, because the first one starts at `offset`.
+    let last_left = left_results[left_results.len() - 1];
+    if last_left.end() > left_end {
+        return err(SubtreeErrorInner::NotASubtree {
+            offset: last_left.input_offset,
+            len: last_left.len,
+        });
+    }
+    let left_cv = merge_subtree(left_results, offset, left_end - offset, mode)?;
+    let right_cv = merge_subtree(right_results, left_end, offset + len - left_end, mode)?;
+    Ok((left_cv, right_cv))
+}
+
+fn merge_subtree(
+    results: &[SubtreeResult],
+    offset: u64,
+    len: u64,
+    mode: Mode,
+) -> Result<ChainingValue, SubtreeError> {
+    if let [result] = results {
+        // Contiguous results that start and end with this subtree are exactly this subtree.
+        debug_assert_eq!((result.input_offset, result.len), (offset, len));
+        return Ok(result.cv);
+    }
+    let (left_cv, right_cv) = merge_children(results, offset, len, mode)?;
+    Ok(merge_subtrees_non_root(&left_cv, &right_cv, mode))
+}
+
+/// The error type for [`SubtreeResult::from_bytes`], [`combine_subtrees`], and
+/// [`combine_subtrees_xof`].
+///
+/// The `.to_string()` representation of this error currently describes what went wrong, for
+/// example which range overlapped or was missing. This is to help with logging and debugging, but
+/// it isn't a stable API detail, and it may change at any time.
+#[derive(Clone, Debug)]
+pub struct SubtreeError(SubtreeErrorInner);
+
+#[derive(Clone, Debug)]
+enum SubtreeErrorInner {
+    UnsupportedVersion(u8),
+    InvalidLen(usize),
+    InvalidMode(u8),
+    NoResults,
+    WrongMode {
+        offset: u64,
+        expected: ModeKind,
+        found: ModeKind,
+    },
+    Unaligned {
+        offset: u64,
+    },
+    Overlap {
+        offset: u64,
+        end: u64,
+    },
+    Gap {
+        offset: u64,
+        end: u64,
+    },
+    Empty {
+        offset: u64,
+    },
+    TooLong {
+        offset: u64,
+        len: u64,
+        max: u64,
+    },
+    Overflow {
+        offset: u64,
+    },
+    NotASubtree {
+        offset: u64,
+        len: u64,
+    },
+    Root {
+        len: u64,
+    },
+}
+
+impl fmt::Display for SubtreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            SubtreeErrorInner::UnsupportedVersion(version) => {
+                write!(f, "unsupported subtree result version: {version}")
+            }
+            SubtreeErrorInner::InvalidLen(len) => {
+                write!(f, "invalid subtree result length: {len}")
+            }
+            SubtreeErrorInner::InvalidMode(mode) => {
+                write!(f, "invalid subtree result mode: {mode}")
+            }
+            SubtreeErrorInner::NoResults => write!(f, "there are no subtree results to combine"),
+            SubtreeErrorInner::WrongMode {
+                offset,
+                expected,
+                found,
+            } => write!(
+                f,
+                "the subtree starting at {offset} was computed in {found:?} mode, not {expected:?}",
+            ),
+            SubtreeErrorInner::Unaligned { offset } => write!(
+                f,
+                "offset ({offset}) must be a chunk boundary (divisible by {CHUNK_LEN})",
+            ),
+            SubtreeErrorInner::Overlap { offset, end } => write!(
+                f,
+                "the subtree starting at {offset} overlaps the previous one, which ends at {end}",
+            ),
+            SubtreeErrorInner::Gap { offset, end } => {
+                write!(f, "no subtree covers the range {offset}..{end}")
+            }
+            SubtreeErrorInner::Empty { offset } => {
+                write!(f, "the subtree starting at {offset} is empty")
+            }
+            SubtreeErrorInner::TooLong { offset, len, max } => write!(
+                f,
+                "the subtree starting at {offset} contains at most {max} bytes (found {len})",
+            ),
+            SubtreeErrorInner::Overflow { offset } => {
+                write!(f, "the subtree starting at {offset} ends past u64::MAX")
+            }
+            SubtreeErrorInner::NotASubtree { offset, len } => write!(
+                f,
+                "{}..{} isn't a subtree of an input that continues past it",
+                offset,
+                offset + len,
+            ),
+            SubtreeErrorInner::Root { len } => write!(
+                f,
+                "a chaining value for 0..{len} would be the root of an input of {len} bytes",
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for SubtreeError {}
+
+fn err<T>(inner: SubtreeErrorInner) -> Result<T, SubtreeError> {
+    Err(SubtreeError(inner))
+}
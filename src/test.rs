@@ -624,6 +624,406 @@ fn test_xof_seek() {
     }
 }
 
+#[test]
+fn test_fill_with_join() {
+    use crate::join::Join;
+    const OUT_LEN_MAX: usize = 100 * CHUNK_LEN + 100;
+    let mut expected = [0; OUT_LEN_MAX];
+    crate::Hasher::new_keyed(&TEST_KEY)
+        .update(b"foo")
+        .finalize_xof()
+        .fill(&mut expected);
+
+    // Start both aligned and unaligned, and end both aligned and unaligned.
+    for &start in &[0, 1, BLOCK_LEN, 3 * CHUNK_LEN + 7] {
+        for &len in &[0, 1, BLOCK_LEN, 17 * CHUNK_LEN, 50 * CHUNK_LEN + 33] {
+            let mut reader = crate::Hasher::new_keyed(&TEST_KEY)
+                .update(b"foo")
+                .finalize_xof();
+            reader.set_position(start as u64);
+            let mut buf = [0; OUT_LEN_MAX];
+            #[cfg(feature = "std")]
+            reader.fill_with_join(
+                &mut buf[..len],
+                &crate::join::ThreadJoin::new()
+                    .max_threads(4)
+                    .min_subtree_len(0),
+            );
+            #[cfg(not(feature = "std"))]
+            reader.fill_with_join(&mut buf[..len], &crate::join::SerialJoin);
+            assert_eq!(buf[..len], expected[start..][..len]);
+            assert_eq!(reader.position(), (start + len) as u64);
+            #[cfg(feature = "rayon")]
+            {
+                reader.set_position(start as u64);
+                buf = [0; OUT_LEN_MAX];
+                reader.fill_rayon(&mut buf[..len]);
+                assert_eq!(buf[..len], expected[start..][..len]);
+                assert_eq!(reader.position(), (start + len) as u64);
+            }
+        }
+    }
+
+    // Large outputs get split.
+    struct CountingJoin(core::sync::atomic::AtomicUsize);
+    impl Join for CountingJoin {
+        fn join<A, B, RA, RB>(&self, oper_a: A, oper_b: B) -> (RA, RB)
+        where
+            A: FnOnce() -> RA + Send,
+            B: FnOnce() -> RB + Send,
+            RA: Send,
+            RB: Send,
+        {
+            self.0.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+            (oper_a(), oper_b())
+        }
+    }
+    let join = CountingJoin(0.into());
+    let mut buf = [0; OUT_LEN_MAX];
+    crate::Hasher::new_keyed(&TEST_KEY)
+        .update(b"foo")
+        .finalize_xof()
+        .fill_with_join(&mut buf, &join);
+    assert_eq!(buf, expected);
+    assert!(join.0.load(core::sync::atomic::Ordering::Relaxed) > 0);
+}
+
+#[test]
+fn test_xor_into() {
+    const OUT_MAX: usize = 3 * crate::platform::MAX_SIMD_DEGREE * BLOCK_LEN + 7;
+    let mut stream = [0; 2 * OUT_MAX];
+    crate::Hasher::new_keyed(&TEST_KEY)
+        .update(b"foo")
+        .finalize_xof()
+        .fill(&mut stream);
+    let mut data = [0; OUT_MAX];
+    paint_test_input(&mut data);
+
+    for &start in &[0, 1, BLOCK_LEN - 1, BLOCK_LEN, OUT_MAX - 1] {
+        for &len in &[0, 1, BLOCK_LEN - 1, BLOCK_LEN, 2 * BLOCK_LEN + 1, OUT_MAX] {
+            #[cfg(feature = "std")]
+            dbg!(start, len);
+            let mut expected = data;
+            for i in 0..len {
+                expected[i] ^= stream[start + i];
+            }
+            let mut reader = crate::Hasher::new_keyed(&TEST_KEY)
+                .update(b"foo")
+                .finalize_xof();
+            reader.set_position(start as u64);
+            let mut buf = data;
+            reader.xor_into(&mut buf[..len]);
+            assert_eq!(buf, expected);
+            assert_eq!(reader.position(), (start + len) as u64);
+
+            // XORing again from the same position undoes it.
+            reader.set_position(start as u64);
+            reader.xor_into(&mut buf[..len]);
+            assert_eq!(buf, data);
+
+            // The buffered reader, in odd-sized pieces.
+            let mut reader = crate::BufferedOutputReader::new(reader);
+            reader.set_position(start as u64);
+            let mut buf = data;
+            for piece in buf[..len].chunks_mut(13) {
+                reader.xor_into(piece);
+            }
+            assert_eq!(buf, expected);
+            assert_eq!(reader.position(), (start + len) as u64);
+        }
+    }
+}
+
+#[test]
+fn test_xor_into_at() {
+    const OUT_LEN: usize = 2 * crate::platform::MAX_SIMD_DEGREE * BLOCK_LEN + 3;
+    let mut data = [0; OUT_LEN];
+    paint_test_input(&mut data);
+    let mut reader = crate::Hasher::new_keyed(&TEST_KEY)
+        .update(b"foo")
+        .finalize_xof();
+
+    // Start from an unaligned offset, after the reader has already moved.
+    let position = 3 * BLOCK_LEN as u64 + 5;
+    reader.fill(&mut [0; 100]);
+    let mut stream = [0; OUT_LEN];
+    reader.set_position(position);
+    reader.fill(&mut stream);
+
+    let mut buf = data;
+    reader.xor_into_at(position, &mut buf);
+    assert_eq!(reader.position(), position + OUT_LEN as u64);
+    for i in 0..OUT_LEN {
+        assert_eq!(buf[i], data[i] ^ stream[i], "byte {}", i);
+    }
+    reader.xor_into_at(position, &mut buf);
+    assert_eq!(buf, data);
+}
+
+#[test]
+fn test_buffered_output_reader() {
+    const OUT_MAX: usize = 10 * crate::platform::MAX_SIMD_DEGREE * BLOCK_LEN;
+    let mut expected = [0; OUT_MAX];
+    crate::Hasher::new_keyed(&TEST_KEY)
+        .update(b"foo")
+        .finalize_xof()
+        .fill(&mut expected);
+
+    // Random reads and seeks, both long and short, aligned and unaligned.
+    let mut rng = chacha20::ChaCha8Rng::from_seed([1; 32]);
+    let mut reader = crate::BufferedOutputReader::new(
+        crate::Hasher::new_keyed(&TEST_KEY)
+            .update(b"foo")
+            .finalize_xof(),
+    );
+    let mut buf = [0; OUT_MAX];
+    for _ in 0..1000 {
+        if rng.random_bool(0.2) {
+            reader.set_position(rng.random_range(0..OUT_MAX as u64));
+        }
+        let start = reader.position() as usize;
+        let len = rng.random_range(0..=OUT_MAX - start);
+        let len = if rng.random() { len } else { len % 100 };
+        reader.fill(&mut buf[..len]);
+        assert_eq!(buf[..len], expected[start..][..len]);
+        assert_eq!(reader.position() as usize, start + len);
+    }
+
+    // Wrapping a reader that's already partway through the output, and unwrapping it again.
+    let mut inner = crate::Hasher::new().finalize_xof();
+    inner.set_position(100);
+    let mut reader = crate::BufferedOutputReader::new(inner);
+    assert_eq!(reader.position(), 100);
+    reader.fill(&mut buf[..10]);
+    let mut inner = reader.into_inner();
+    assert_eq!(inner.position(), 110);
+    let mut expected = [0; 20];
+    crate::Hasher::new().finalize_xof().fill(&mut expected);
+    let mut inner_buf = [0; 10];
+    inner.set_position(10);
+    inner.fill(&mut inner_buf);
+    assert_eq!(inner_buf, expected[10..]);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_buffered_output_reader_io() -> std::io::Result<()> {
+    use std::io::{BufRead, Read, Seek, SeekFrom};
+    let mut expected = [0; 2 * crate::platform::MAX_SIMD_DEGREE * BLOCK_LEN];
+    crate::Hasher::new().finalize_xof().fill(&mut expected);
+
+    let mut reader = crate::BufferedOutputReader::new(crate::Hasher::new().finalize_xof());
+    assert_eq!(reader.seek(SeekFrom::Start(3))?, 3);
+    let available = reader.fill_buf()?;
+    assert!(!available.is_empty());
+    assert_eq!(available, &expected[3..][..available.len()]);
+    reader.consume(5);
+    assert_eq!(reader.stream_position()?, 8);
+    assert_eq!(reader.seek(SeekFrom::Current(-2))?, 6);
+    let mut buf = [0; 500];
+    reader.read_exact(&mut buf)?;
+    assert_eq!(buf, expected[6..506]);
+    assert!(reader.seek(SeekFrom::Current(-1000)).is_err());
+    assert!(reader.seek(SeekFrom::End(0)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_hash_many() {
+    // Every test case length, several times over and in a shuffled order, so that buckets fill
+    // up and flush partway through.
+    const NUM_INPUTS: usize = 3 * TEST_CASES.len();
+    let mut input_buf = [0; TEST_CASES_MAX];
+    paint_test_input(&mut input_buf);
+    let mut inputs: ArrayVec<&[u8], NUM_INPUTS> = ArrayVec::new();
+    for _ in 0..3 {
+        for &case in TEST_CASES {
+            inputs.push(&input_buf[..case]);
+        }
+    }
+    let mut rng = chacha20::ChaCha8Rng::from_seed([1; 32]);
+    inputs.shuffle(&mut rng);
+
+    let mut hashes = [crate::Hash::from_bytes([0; OUT_LEN]); NUM_INPUTS];
+    crate::hash_many(&inputs, &mut hashes);
+    let mut keyed_hashes = [crate::Hash::from_bytes([0; OUT_LEN]); NUM_INPUTS];
+    crate::keyed_hash_many(&TEST_KEY, &inputs, &mut keyed_hashes);
+    let mut derived_keys = [[0; OUT_LEN]; NUM_INPUTS];
+    crate::derive_key_many("test context", &inputs, &mut derived_keys);
+    for (i, input) in inputs.iter().enumerate() {
+        #[cfg(feature = "std")]
+        dbg!(input.len());
+        assert_eq!(hashes[i], crate::hash(input));
+        assert_eq!(keyed_hashes[i], crate::keyed_hash(&TEST_KEY, input));
+        assert_eq!(derived_keys[i], crate::derive_key("test context", input));
+    }
+
+    // Empty inputs.
+    crate::hash_many(&[], &mut []);
+}
+
+#[test]
+#[should_panic]
+fn test_hash_many_mismatched_lengths() {
+    crate::hash_many(&[b"foo"], &mut []);
+}
+
+#[test]
+fn test_mac() {
+    let mut input = [0; TEST_CASES_MAX];
+    paint_test_input(&mut input);
+    for &case in TEST_CASES {
+        #[cfg(feature = "std")]
+        dbg!(case);
+        let expected = crate::keyed_hash(&TEST_KEY, &input[..case]);
+        let mut mac = crate::Mac::new(&TEST_KEY);
+        mac.update(&input[..case]);
+        assert_eq!(mac.finalize(), expected);
+        mac.verify(expected.as_bytes()).unwrap();
+        for len in crate::Mac::MIN_TAG_LEN..=OUT_LEN {
+            mac.verify_truncated(&expected.as_bytes()[..len]).unwrap();
+        }
+    }
+
+    let mut mac = crate::Mac::new(&TEST_KEY);
+    mac.update(b"foo");
+    let tag = *mac.finalize().as_bytes();
+    let mut bad_tag = tag;
+    bad_tag[31] ^= 1;
+    let _result = mac.verify(&bad_tag).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "MAC tag mismatch");
+    mac.verify_truncated(&bad_tag[..31]).unwrap();
+    let _result = mac.verify_truncated(&bad_tag).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "MAC tag mismatch");
+
+    // Short and empty prefixes of the right tag are still rejected.
+    for len in [0, 1, crate::Mac::MIN_TAG_LEN - 1] {
+        let _result = mac.verify_truncated(&tag[..len]).unwrap_err();
+        #[cfg(feature = "std")]
+        assert_eq!(
+            _result.to_string(),
+            std::format!("expected a MAC tag of 16 to 32 bytes, received {}", len),
+        );
+    }
+    let _result = mac.verify_truncated(&[0; 33]).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(
+        _result.to_string(),
+        "expected a MAC tag of 16 to 32 bytes, received 33"
+    );
+
+    // Reset keeps the key.
+    mac.update(b"bar");
+    assert!(mac.verify(&tag).is_err());
+    mac.reset().update(b"foo");
+    mac.verify(&tag).unwrap();
+}
+
+#[test]
+fn test_build_hasher() {
+    use core::hash::{BuildHasher, Hasher};
+
+    let mut input = [0; 200];
+    paint_test_input(&mut input);
+    let build_hasher = crate::BuildHasher::new(&TEST_KEY);
+    let expected_u64 = |input: &[u8]| {
+        let hash = crate::keyed_hash(&TEST_KEY, input);
+        u64::from_le_bytes(*array_ref!(hash.as_bytes(), 0, 8))
+    };
+    // Write in every combination of two pieces, across the one-block fast path boundary.
+    for len in 0..input.len() {
+        for split in 0..=len {
+            let mut hasher = build_hasher.build_hasher();
+            hasher.write(&input[..split]);
+            hasher.write(&input[split..len]);
+            assert_eq!(hasher.finish(), expected_u64(&input[..len]));
+        }
+    }
+
+    // Many small writes, like a Hash impl for a struct.
+    let mut hasher = build_hasher.build_hasher();
+    for i in 0..100u64 {
+        hasher.write_u64(i);
+    }
+    let mut expected_input = ArrayVec::<u8, 800>::new();
+    for i in 0..100u64 {
+        expected_input
+            .try_extend_from_slice(&i.to_ne_bytes())
+            .unwrap();
+    }
+    assert_eq!(hasher.finish(), expected_u64(&expected_input));
+    // finish() doesn't change the state.
+    assert_eq!(hasher.finish(), expected_u64(&expected_input));
+
+    // Different keys give different hashes.
+    let other = crate::BuildHasher::new(&[0; 32]);
+    assert_ne!(build_hasher.hash_one("foo"), other.hash_one("foo"));
+    // The Hash impl for str writes its bytes and then a 0xff terminator.
+    assert_eq!(build_hasher.hash_one("foo"), expected_u64(b"foo\xff"));
+
+    #[cfg(feature = "getrandom")]
+    {
+        let random1 = crate::BuildHasher::random();
+        let random2 = crate::BuildHasher::default();
+        assert_ne!(random1.hash_one(42u64), random2.hash_one(42u64));
+    }
+
+    #[cfg(feature = "std")]
+    {
+        let mut map = std::collections::HashMap::with_hasher(build_hasher.clone());
+        for i in 0..1000 {
+            map.insert(i, i * 2);
+        }
+        for i in 0..1000 {
+            assert_eq!(map[&i], i * 2);
+        }
+    }
+}
+
+#[test]
+fn test_const_hash() {
+    use crate::hazmat::HasherExt;
+
+    let mut input = [0; TEST_CASES_MAX];
+    paint_test_input(&mut input);
+    for &case in TEST_CASES {
+        #[cfg(feature = "std")]
+        dbg!(case);
+        assert_eq!(
+            crate::const_hash(&input[..case]),
+            crate::hash(&input[..case])
+        );
+        assert_eq!(
+            crate::const_keyed_hash(&TEST_KEY, &input[..case]),
+            crate::keyed_hash(&TEST_KEY, &input[..case]),
+        );
+    }
+
+    // These are evaluated at compile time.
+    const EMPTY_HASH: crate::Hash = crate::const_hash(b"");
+    const CONTEXT_KEY: crate::hazmat::ContextKey = crate::hazmat::const_hash_derive_key_context(
+        "BLAKE3 2019-12-27 16:29:52 test vectors context",
+    );
+    assert_eq!(EMPTY_HASH, crate::hash(b""));
+    let context_key = crate::context_key!("BLAKE3 2019-12-27 16:29:52 test vectors context");
+    assert_eq!(context_key, CONTEXT_KEY);
+    assert_eq!(
+        context_key,
+        crate::hazmat::hash_derive_key_context("BLAKE3 2019-12-27 16:29:52 test vectors context"),
+    );
+    let mut hasher = crate::Hasher::new_from_context_key(&context_key);
+    hasher.update(&input[..100]);
+    assert_eq!(
+        *hasher.finalize().as_bytes(),
+        crate::derive_key(
+            "BLAKE3 2019-12-27 16:29:52 test vectors context",
+            &input[..100],
+        ),
+    );
+}
+
 #[test]
 fn test_msg_schedule_permutation() {
     let permutation = [2, 6, 3, 10, 7, 0, 4, 13, 1, 11, 12, 5, 9, 14, 15, 8];
@@ -667,6 +1067,433 @@ fn test_reset() {
     assert_eq!(kdf.finalize(), expected);
 }
 
+#[test]
+fn test_update_with_join() {
+    use crate::join::Join;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    // A custom Join implementation that counts how many times it's called.
+    struct CountingJoin {
+        calls: AtomicUsize,
+        min_subtree_len: usize,
+    }
+    impl Join for CountingJoin {
+        fn join<A, B, RA, RB>(&self, oper_a: A, oper_b: B) -> (RA, RB)
+        where
+            A: FnOnce() -> RA + Send,
+            B: FnOnce() -> RB + Send,
+            RA: Send,
+            RB: Send,
+        {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            (oper_a(), oper_b())
+        }
+
+        fn min_subtree_len(&self) -> usize {
+            self.min_subtree_len
+        }
+    }
+
+    let mut input_buf = [0; TEST_CASES_MAX];
+    paint_test_input(&mut input_buf);
+    for &case in TEST_CASES {
+        #[cfg(feature = "std")]
+        dbg!(case);
+        let input = &input_buf[..case];
+        let expected = crate::hash(input);
+
+        let join = CountingJoin {
+            calls: AtomicUsize::new(0),
+            min_subtree_len: 0,
+        };
+        let mut hasher = crate::Hasher::new();
+        hasher.update_with_join(input, &join);
+        assert_eq!(expected, hasher.finalize());
+        // The largest case includes a 64-chunk subtree, which is always split.
+        if case == TEST_CASES_MAX {
+            assert!(join.calls.load(Ordering::Relaxed) > 0);
+        }
+
+        // Subtrees at or below the minimum length never reach the Join.
+        let join = CountingJoin {
+            calls: AtomicUsize::new(0),
+            min_subtree_len: TEST_CASES_MAX,
+        };
+        let mut hasher = crate::Hasher::new();
+        hasher.update_with_join(input, &join);
+        assert_eq!(expected, hasher.finalize());
+        assert_eq!(join.calls.load(Ordering::Relaxed), 0);
+
+        #[cfg(feature = "std")]
+        {
+            let join = crate::join::ThreadJoin::new()
+                .max_threads(3)
+                .min_subtree_len(CHUNK_LEN);
+            let mut hasher = crate::Hasher::new_keyed(&TEST_KEY);
+            hasher.update_with_join(input, &join);
+            assert_eq!(crate::keyed_hash(&TEST_KEY, input), hasher.finalize());
+        }
+    }
+}
+
+#[test]
+fn test_checkpoint_resume() {
+    // Don't use all the long test cases here, since that's unnecessarily slow
+    // in debug mode.
+    let mut short_test_cases = TEST_CASES;
+    while *short_test_cases.last().unwrap() > 4 * CHUNK_LEN {
+        short_test_cases = &short_test_cases[..short_test_cases.len() - 1];
+    }
+
+    let mut input_buf = [0; 2 * TEST_CASES_MAX];
+    paint_test_input(&mut input_buf);
+    let context = "BLAKE3 2026-10-18 12:00:00 checkpoint test";
+    let new_hashers = [
+        crate::Hasher::new(),
+        crate::Hasher::new_keyed(&TEST_KEY),
+        crate::Hasher::new_derive_key(context),
+    ];
+
+    for new_hasher in &new_hashers {
+        for &first_update in short_test_cases {
+            #[cfg(feature = "std")]
+            dbg!(first_update);
+            let mut hasher = new_hasher.clone();
+            hasher.update(&input_buf[..first_update]);
+            let checkpoint = hasher.to_checkpoint();
+            let decoded = crate::Checkpoint::from_bytes(checkpoint.as_bytes()).unwrap();
+            assert_eq!(checkpoint.as_bytes(), decoded.as_bytes());
+
+            // Finalizing right away matches the original hasher.
+            let resumed = crate::Hasher::from_checkpoint(&decoded);
+            assert_eq!(hasher.count(), resumed.count());
+            assert_eq!(hasher.finalize(), resumed.finalize());
+
+            for &second_update in short_test_cases {
+                let second_input = &input_buf[first_update..][..second_update];
+                let mut original = hasher.clone();
+                original.update(second_input);
+                let mut resumed = crate::Hasher::from_checkpoint(&decoded);
+                resumed.update(second_input);
+                assert_eq!(original.finalize(), resumed.finalize());
+                let mut original_xof = [0; 100];
+                let mut resumed_xof = [0; 100];
+                original.finalize_xof().fill(&mut original_xof);
+                resumed.finalize_xof().fill(&mut resumed_xof);
+                assert_eq!(original_xof, resumed_xof);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_checkpoint_fuzz() {
+    const INPUT_MAX: usize = 4 * CHUNK_LEN;
+    let mut input_buf = [0; 5 * INPUT_MAX];
+    paint_test_input(&mut input_buf);
+
+    let num_tests = if cfg!(debug_assertions) { 100 } else { 10_000 };
+
+    // Use a fixed RNG seed for reproducibility.
+    let mut rng = chacha20::ChaCha8Rng::from_seed([1; 32]);
+    for _num_test in 0..num_tests {
+        #[cfg(feature = "std")]
+        dbg!(_num_test);
+        let mut hasher = crate::Hasher::new();
+        let mut total_input = 0;
+        // Write 5 inputs of random length, round-tripping through a checkpoint
+        // after each one. Lengths that are multiples of CHUNK_LEN leave
+        // unmerged CVs in the stack, so make those common.
+        for _ in 0..5 {
+            let input_len = if rng.random() {
+                rng.random_range(0..5) * CHUNK_LEN
+            } else {
+                rng.random_range(0..(INPUT_MAX + 1))
+            };
+            hasher.update(&input_buf[total_input..][..input_len]);
+            total_input += input_len;
+            let checkpoint = crate::Checkpoint::from_bytes(hasher.to_checkpoint().as_bytes());
+            hasher = crate::Hasher::from_checkpoint(&checkpoint.unwrap());
+        }
+        let expected = reference_hash(&input_buf[..total_input]);
+        assert_eq!(expected, hasher.finalize());
+    }
+}
+
+#[test]
+fn test_checkpoint_input_offset() {
+    use crate::hazmat::HasherExt;
+
+    let mut input = [0; 4 * CHUNK_LEN];
+    paint_test_input(&mut input);
+    let mut hasher = crate::Hasher::new();
+    hasher.set_input_offset(4 * CHUNK_LEN as u64);
+    hasher.update(&input[..CHUNK_LEN + 1]);
+    let checkpoint = crate::Checkpoint::from_bytes(hasher.to_checkpoint().as_bytes()).unwrap();
+    let mut resumed = crate::Hasher::from_checkpoint(&checkpoint);
+    hasher.update(&input[CHUNK_LEN + 1..]);
+    resumed.update(&input[CHUNK_LEN + 1..]);
+    assert_eq!(hasher.finalize_non_root(), resumed.finalize_non_root());
+}
+
+#[test]
+fn test_checkpoint_invalid() {
+    let mut input = [0; 3 * CHUNK_LEN + 7];
+    paint_test_input(&mut input);
+    let mut hasher = crate::Hasher::new();
+    hasher.update(&input);
+    let good = hasher.to_checkpoint();
+    assert!(crate::Checkpoint::from_bytes(good.as_bytes()).is_ok());
+
+    // Each of these modifications should be caught.
+    type Bytes = ArrayVec<u8, 2048>;
+    type Mutation = (&'static str, fn(&mut Bytes));
+    let mutations: &[Mutation] = &[
+        ("empty", |b| b.clear()),
+        ("truncated", |b| b.truncate(b.len() - 1)),
+        ("extended", |b| b.push(0)),
+        ("version", |b| b[0] = 2),
+        ("flags", |b| b[1] = crate::ROOT),
+        ("keyed flag with the default key is fine", |b| {
+            b[1] = crate::KEYED_HASH
+        }),
+        ("key", |b| b[2] ^= 1),
+        ("initial chunk counter past chunk counter", |b| b[34] = 4),
+        ("chunk counter", |b| b[42] = 4),
+        ("cv of a chunk with no compressed blocks", |b| b[50] ^= 1),
+        ("buf len", |b| b[83] = 65),
+        ("nonzero bytes past buf len", |b| b[84 + 63] = 1),
+        ("stack len", |b| {
+            b[148] = 3;
+            b.try_extend_from_slice(&[0; 32]).unwrap();
+        }),
+    ];
+    for (name, mutate) in mutations {
+        #[cfg(feature = "std")]
+        dbg!(name);
+        let mut bytes = Bytes::try_from(good.as_bytes()).unwrap();
+        mutate(&mut bytes);
+        let result = crate::Checkpoint::from_bytes(&bytes);
+        if name.ends_with("is fine") {
+            assert!(result.is_ok());
+        } else {
+            assert!(result.is_err());
+        }
+    }
+
+    // A subtree can't grow past its max length.
+    let mut bytes = Bytes::try_from(good.as_bytes()).unwrap();
+    bytes[34] = 1;
+    bytes[42] = 4;
+    let _result = crate::Checkpoint::from_bytes(&bytes).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "invalid checkpoint chunk counter");
+
+    let _result = crate::Checkpoint::from_bytes(&[7]).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "unsupported checkpoint version: 7");
+}
+
+#[test]
+fn test_range_proof() {
+    use crate::RangeProof;
+    use crate::hazmat::{HasherExt, Mode};
+
+    let mut input_buf = [0; TEST_CASES_MAX];
+    paint_test_input(&mut input_buf);
+    let modes = [
+        Mode::Hash,
+        Mode::KeyedHash(&TEST_KEY),
+        Mode::DeriveKeyMaterial(&[7; 32]),
+    ];
+    for &case in TEST_CASES {
+        let input = &input_buf[..case];
+        let len = case as u64;
+        let chunk_len = CHUNK_LEN as u64;
+        let num_chunks = len.div_ceil(chunk_len).max(1);
+        // Ranges of one, two, and all chunks, at the start, middle, and end of the input.
+        let mut ranges = ArrayVec::<core::ops::Range<u64>, 16>::new();
+        for start_chunk in [0, num_chunks / 2, num_chunks - 1] {
+            for range_chunks in [1, 2, num_chunks] {
+                let start = start_chunk * chunk_len;
+                let end = ((start_chunk + range_chunks) * chunk_len).min(len);
+                if start < end || len == 0 {
+                    ranges.push(start..end);
+                }
+            }
+        }
+        for &mode in &modes {
+            let hash = match mode {
+                Mode::Hash => crate::hash(input),
+                Mode::KeyedHash(key) => crate::keyed_hash(key, input),
+                Mode::DeriveKeyMaterial(key) => crate::Hasher::new_from_context_key(key)
+                    .update(input)
+                    .finalize(),
+            };
+            for range in ranges.iter().cloned() {
+                #[cfg(feature = "std")]
+                dbg!(case, &range);
+                let proof = RangeProof::new(input, range.clone(), mode).unwrap();
+                assert_eq!(proof.input_len(), len);
+                assert_eq!(proof.range(), range);
+                let proof = RangeProof::from_bytes(proof.as_bytes()).unwrap();
+                let range_bytes = &input[range.start as usize..range.end as usize];
+                proof.verify(range_bytes, &hash, mode).unwrap();
+
+                // A different mode or hash doesn't verify.
+                let other_mode = match mode {
+                    Mode::Hash => Mode::KeyedHash(&TEST_KEY),
+                    _ => Mode::Hash,
+                };
+                assert!(
+                    proof
+                        .verify(range_bytes, &hash, other_mode)
+                        .unwrap_err()
+                        .is_mismatch()
+                );
+                let wrong_hash = crate::Hash::from_bytes([0; 32]);
+                assert!(
+                    proof
+                        .verify(range_bytes, &wrong_hash, mode)
+                        .unwrap_err()
+                        .is_mismatch()
+                );
+
+                // Neither do modified range bytes or chaining values.
+                if !range_bytes.is_empty() {
+                    let mut bytes = [0; TEST_CASES_MAX];
+                    let bytes = &mut bytes[..range_bytes.len()];
+                    bytes.copy_from_slice(range_bytes);
+                    bytes[bytes.len() - 1] ^= 1;
+                    assert!(proof.verify(bytes, &hash, mode).unwrap_err().is_mismatch());
+                    let result = proof.verify(&range_bytes[1..], &hash, mode);
+                    assert!(!result.unwrap_err().is_mismatch());
+                }
+                let proof_len = proof.as_bytes().len();
+                if proof_len > 26 {
+                    let mut bytes = ArrayVec::<u8, 4096>::try_from(proof.as_bytes()).unwrap();
+                    bytes[proof_len - 1] ^= 1;
+                    let bad_proof = RangeProof::from_bytes(&bytes).unwrap();
+                    let result = bad_proof.verify(range_bytes, &hash, mode);
+                    assert!(result.unwrap_err().is_mismatch());
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_range_proof_invalid() {
+    use crate::RangeProof;
+    use crate::hazmat::Mode;
+
+    // Six chunks, the last one partial. The tree is a four-chunk subtree on the left, and a
+    // two-chunk subtree on the right.
+    let mut input = [0; 5 * CHUNK_LEN + 7];
+    paint_test_input(&mut input);
+    let hash = crate::hash(&input);
+    let c = CHUNK_LEN as u64;
+    let check_new = |range: core::ops::Range<u64>, _expected: &str| {
+        let err = RangeProof::new(&input, range, Mode::Hash).unwrap_err();
+        assert!(!err.is_mismatch());
+        #[cfg(feature = "std")]
+        assert_eq!(err.to_string(), _expected);
+    };
+    check_new(
+        1..c,
+        "1..1024 isn't a chunk-aligned range of an input of 5127 bytes",
+    );
+    check_new(
+        0..c + 1,
+        "0..1025 isn't a chunk-aligned range of an input of 5127 bytes",
+    );
+    check_new(
+        c..c,
+        "1024..1024 isn't a chunk-aligned range of an input of 5127 bytes",
+    );
+    check_new(
+        5 * c..5 * c + 8,
+        "5120..5128 isn't a chunk-aligned range of an input of 5127 bytes",
+    );
+    assert!(RangeProof::new(&[], 0..0, Mode::Hash).is_ok());
+
+    // A proof for the second chunk has three sibling chaining values: the first chunk, the other
+    // half of the left subtree, and the right subtree.
+    let good = RangeProof::new(&input, c..2 * c, Mode::Hash).unwrap();
+    let good_bytes: [u8; 26 + 3 * 32] = good.as_bytes().try_into().unwrap();
+    let range_bytes = &input[CHUNK_LEN..2 * CHUNK_LEN];
+    RangeProof::from_bytes(&good_bytes)
+        .unwrap()
+        .verify(range_bytes, &hash, Mode::Hash)
+        .unwrap();
+
+    // Malformed encodings are caught by from_bytes.
+    let check_bytes = |bytes: &[u8], _expected: &str| {
+        let err = RangeProof::from_bytes(bytes).unwrap_err();
+        assert!(!err.is_mismatch());
+        #[cfg(feature = "std")]
+        assert_eq!(err.to_string(), _expected);
+    };
+    let with_byte = |i: usize, value: u8| {
+        let mut bytes = good_bytes;
+        bytes[i] = value;
+        bytes
+    };
+    check_bytes(&[], "invalid range proof length: 0");
+    check_bytes(&[7], "unsupported range proof version: 7");
+    check_bytes(&with_byte(0, 2), "unsupported range proof version: 2");
+    check_bytes(&good_bytes[..121], "invalid range proof length: 121");
+    let mut extended = [0; 123];
+    extended[..122].copy_from_slice(&good_bytes);
+    check_bytes(&extended, "invalid range proof length: 123");
+    // The input length is 0x1407, and the range end is 0x0800.
+    check_bytes(
+        &with_byte(2, 0),
+        "1024..2048 isn't a chunk-aligned range of an input of 7 bytes",
+    );
+    check_bytes(
+        &with_byte(9, 1),
+        "1025..2048 isn't a chunk-aligned range of an input of 5127 bytes",
+    );
+    check_bytes(
+        &with_byte(17, 1),
+        "1024..2049 isn't a chunk-aligned range of an input of 5127 bytes",
+    );
+    check_bytes(
+        &with_byte(18, 0x18),
+        "1024..6144 isn't a chunk-aligned range of an input of 5127 bytes",
+    );
+    let mut extra_cv = [0; 122 + 32];
+    extra_cv[..122].copy_from_slice(&good_bytes);
+    extra_cv[25] = 4;
+    check_bytes(
+        &extra_cv,
+        "range proof has 4 chaining values, but its range needs 3",
+    );
+
+    // A flipped sibling chaining value is well-formed, but it doesn't verify.
+    let flipped = RangeProof::from_bytes(&with_byte(26 + 32, good_bytes[26 + 32] ^ 1)).unwrap();
+    let err = flipped.verify(range_bytes, &hash, Mode::Hash).unwrap_err();
+    assert!(err.is_mismatch());
+
+    // Range bytes of the wrong length are an error, but not a mismatch.
+    let err = good
+        .verify(&range_bytes[1..], &hash, Mode::Hash)
+        .unwrap_err();
+    assert!(!err.is_mismatch());
+    #[cfg(feature = "std")]
+    assert_eq!(
+        err.to_string(),
+        "range proof is for 1024 bytes, but 1023 were given"
+    );
+
+    // The input length isn't verified if the range doesn't include the end of the input. 0x1400
+    // bytes give the same tree shape along the path to the second chunk, so this verifies.
+    let other_len = RangeProof::from_bytes(&with_byte(1, 0)).unwrap();
+    assert_eq!(other_len.input_len(), 5 * c);
+    other_len.verify(range_bytes, &hash, Mode::Hash).unwrap();
+}
+
 #[test]
 fn test_hex_encoding_decoding() {
     let digest_str = "04e0bb39f30b1a3feb89f536c93be15055482df748674b00d26e5a75777702e9";
@@ -705,6 +1532,99 @@ fn test_hex_encoding_decoding() {
     assert_eq!(_result.to_string(), "invalid hex character: 0x80");
 }
 
+#[test]
+fn test_base64url_base32_multibase() {
+    use crate::Multibase;
+    let digest = crate::hash(b"foo");
+    let base64url = "BOC7OfMLGj_rifU2yTvhUFVILfdIZ0sA0m5adXd3Auk";
+    let base32 = "atqlwoptbmnd724j6u3mso7bkbkuqlpxjbtuwagsnznhk53xaluq";
+    assert_eq!(digest.to_base64url().as_str(), base64url);
+    assert_eq!(digest.to_base32().as_str(), base32);
+    assert_eq!(crate::Hash::from_base64url(base64url).unwrap(), digest);
+    assert_eq!(crate::Hash::from_base32(base32).unwrap(), digest);
+    let base32_upper: ArrayVec<u8, 52> = base32.bytes().map(|b| b.to_ascii_uppercase()).collect();
+    assert_eq!(crate::Hash::from_base32(&base32_upper).unwrap(), digest);
+
+    // Multibase
+    for (base, prefix) in [
+        (Multibase::Base16, 'f'),
+        (Multibase::Base32, 'b'),
+        (Multibase::Base64Url, 'u'),
+    ] {
+        let encoded = digest.to_multibase(base);
+        assert!(encoded.starts_with(prefix));
+        assert_eq!(
+            crate::Hash::from_multibase(encoded.as_str()).unwrap(),
+            digest
+        );
+    }
+    assert_eq!(
+        &digest.to_multibase(Multibase::Base16)[1..],
+        digest.to_hex().as_str(),
+    );
+
+    // Round trip random hashes, which exercises every alphabet position.
+    let mut rng = chacha20::ChaCha8Rng::from_seed([1; 32]);
+    for _ in 0..100 {
+        let hash = crate::Hash::from_bytes(rng.random());
+        assert_eq!(
+            crate::Hash::from_base64url(hash.to_base64url().as_str()).unwrap(),
+            hash
+        );
+        assert_eq!(
+            crate::Hash::from_base32(hash.to_base32().as_str()).unwrap(),
+            hash
+        );
+    }
+
+    // Test errors
+    let _result = crate::Hash::from_base64url(&base64url[1..]).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(
+        _result.to_string(),
+        "expected 43 base64url bytes, received 42"
+    );
+
+    let mut bad_char: ArrayVec<u8, 43> = base64url.bytes().collect();
+    bad_char[0] = b'+';
+    let _result = crate::Hash::from_base64url(&bad_char).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "invalid base64url character: '+'");
+
+    let mut bad_char: ArrayVec<u8, 52> = base32.bytes().collect();
+    bad_char[0] = 0x80;
+    let _result = crate::Hash::from_base32(&bad_char).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "invalid base32 character: 0x80");
+
+    // The last base64url character carries 2 padding bits, and the last base32 character carries
+    // 4. 'k' = 36 = 0b100100 in base64url, so 'l' sets a padding bit.
+    let mut non_canonical: ArrayVec<u8, 43> = base64url.bytes().collect();
+    non_canonical[42] = b'l';
+    let _result = crate::Hash::from_base64url(&non_canonical).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(
+        _result.to_string(),
+        "nonzero padding bits in base64url input"
+    );
+    // 'q' = 16 = 0b10000 in base32, so 'r' sets a padding bit.
+    let mut non_canonical: ArrayVec<u8, 52> = base32.bytes().collect();
+    non_canonical[51] = b'r';
+    let _result = crate::Hash::from_base32(&non_canonical).unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "nonzero padding bits in base32 input");
+
+    let _result = crate::Hash::from_multibase("z").unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "unsupported multibase prefix: 'z'");
+    let _result = crate::Hash::from_multibase("").unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "empty multibase input");
+    let _result = crate::Hash::from_multibase("f1234").unwrap_err();
+    #[cfg(feature = "std")]
+    assert_eq!(_result.to_string(), "expected 64 hex bytes, received 4");
+}
+
 // This test is a mimized failure case for the Windows SSE2 bug described in
 // https://github.com/BLAKE3-team/BLAKE3/issues/206.
 //
@@ -866,27 +1786,242 @@ fn test_update_reader_interrupted() -> std::io::Result<()> {
             }
         }
     }
-    impl<'a> io::Read for InterruptingReader<'a> {
-        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-            if !self.already_interrupted {
-                self.already_interrupted = true;
-                return Err(io::Error::from(io::ErrorKind::Interrupted));
-            }
-            let take = std::cmp::min(self.slice.len(), buf.len());
-            buf[..take].copy_from_slice(&self.slice[..take]);
-            self.slice = &self.slice[take..];
-            Ok(take)
+    impl<'a> io::Read for InterruptingReader<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if !self.already_interrupted {
+                self.already_interrupted = true;
+                return Err(io::Error::from(io::ErrorKind::Interrupted));
+            }
+            let take = std::cmp::min(self.slice.len(), buf.len());
+            buf[..take].copy_from_slice(&self.slice[..take]);
+            self.slice = &self.slice[take..];
+            Ok(take)
+        }
+    }
+
+    let input = b"hello world";
+    let mut reader = InterruptingReader::new(input);
+    let mut hasher = crate::Hasher::new();
+    hasher.update_reader(&mut reader)?;
+    assert_eq!(hasher.finalize(), crate::hash(input));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "std")]
+// Large inputs and threads are too slow under Miri.
+#[cfg(not(miri))]
+fn test_update_reader_with_join() -> std::io::Result<()> {
+    use crate::join::{SerialJoin, ThreadJoin};
+    use std::io;
+
+    // Short reads, interruptions, and an optional error at the end of the input.
+    struct ChoppyReader<'a> {
+        slice: &'a [u8],
+        reads: usize,
+        error_at_end: bool,
+    }
+    impl<'a> io::Read for ChoppyReader<'a> {
+        // is_multiple_of() is newer than our MSRV.
+        #[allow(clippy::manual_is_multiple_of)]
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads += 1;
+            if self.reads % 7 == 0 {
+                return Err(io::Error::from(io::ErrorKind::Interrupted));
+            }
+            if self.slice.is_empty() && self.error_at_end {
+                return Err(io::Error::other("the end"));
+            }
+            let take = std::cmp::min(std::cmp::min(self.slice.len(), buf.len()), 100_000);
+            buf[..take].copy_from_slice(&self.slice[..take]);
+            self.slice = &self.slice[take..];
+            Ok(take)
+        }
+    }
+
+    let mut input = vec![0; 7 << 20];
+    paint_test_input(&mut input);
+    for len in [0, 1, 1025, 2 << 20, (2 << 20) + 1, input.len()] {
+        dbg!(len);
+        let mut expected_hasher = crate::Hasher::new();
+        expected_hasher.update(b"prefix").update(&input[..len]);
+        let expected = expected_hasher.finalize();
+
+        let mut hasher = crate::Hasher::new();
+        hasher.update(b"prefix");
+        hasher.update_reader_with_join(&input[..len], &ThreadJoin::new())?;
+        assert_eq!(hasher.finalize(), expected);
+
+        let reader = ChoppyReader {
+            slice: &input[..len],
+            reads: 0,
+            error_at_end: false,
+        };
+        let mut hasher = crate::Hasher::new();
+        hasher.update(b"prefix");
+        hasher.update_reader_with_join(reader, &SerialJoin)?;
+        assert_eq!(hasher.finalize(), expected);
+
+        #[cfg(feature = "rayon")]
+        {
+            let mut hasher = crate::Hasher::new();
+            hasher.update(b"prefix");
+            hasher.update_reader_rayon(&input[..len])?;
+            assert_eq!(hasher.finalize(), expected);
+        }
+
+        let reader = ChoppyReader {
+            slice: &input[..len],
+            reads: 0,
+            error_at_end: true,
+        };
+        let err = crate::Hasher::new()
+            .update_reader_with_join(reader, &SerialJoin)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "the end");
+    }
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_io_adapters() -> std::io::Result<()> {
+    use crate::io::{HashMismatch, HashingReader, HashingWriter, VerifyingReader};
+    use std::io::prelude::*;
+
+    let mut input = vec![0; 100_000];
+    paint_test_input(&mut input);
+    let expected = crate::hash(&input);
+
+    let mut reader = HashingReader::new(&input[..]);
+    let mut output = Vec::new();
+    // Copy in small pieces, to exercise short reads and writes.
+    let mut writer = HashingWriter::new(&mut output);
+    let mut buf = [0; 1000];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&buf[..n])?;
+    }
+    writer.flush()?;
+    assert_eq!(reader.hasher().finalize(), expected);
+    assert_eq!(writer.hasher().finalize(), expected);
+    let (_, hasher) = writer.into_parts();
+    assert_eq!(hasher.count(), input.len() as u64);
+    assert_eq!(output, input);
+
+    // A writer that only accepts part of each write. Only accepted bytes are hashed.
+    struct ShortWriter(Vec<u8>);
+    impl Write for ShortWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let n = std::cmp::min(buf.len(), 7);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
         }
     }
-
-    let input = b"hello world";
-    let mut reader = InterruptingReader::new(input);
-    let mut hasher = crate::Hasher::new();
-    hasher.update_reader(&mut reader)?;
-    assert_eq!(hasher.finalize(), crate::hash(input));
+    let mut writer = HashingWriter::new(ShortWriter(Vec::new()));
+    assert_eq!(writer.write(&input[..100])?, 7);
+    assert_eq!(writer.hasher().finalize(), crate::hash(&input[..7]));
+    writer.write_all(&input[7..])?;
+    assert_eq!(writer.hasher().finalize(), expected);
+
+    // Verification succeeds at EOF, and repeated reads at EOF keep succeeding.
+    let mut reader = VerifyingReader::new(&input[..], expected);
+    output.clear();
+    reader.read_to_end(&mut output)?;
+    assert_eq!(output, input);
+    assert_eq!(reader.read(&mut buf)?, 0);
+    // An empty read isn't EOF, so it doesn't fail before EOF.
+    let mut reader = VerifyingReader::new(&input[..], crate::hash(b"wrong"));
+    assert_eq!(reader.read(&mut [])?, 0);
+    // A mismatch fails at EOF, every time.
+    let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(HashMismatch::is_mismatch(&err));
+    assert_eq!(err.to_string(), "BLAKE3 hash mismatch");
+    let err = reader.read(&mut buf).unwrap_err();
+    assert!(HashMismatch::is_mismatch(&err));
+    assert!(!HashMismatch::is_mismatch(&std::io::Error::from(
+        std::io::ErrorKind::InvalidData
+    )));
+
+    // Keyed verification.
+    let tag = crate::keyed_hash(&TEST_KEY, &input);
+    let hasher = crate::Hasher::new_keyed(&TEST_KEY);
+    let mut reader = VerifyingReader::with_hasher(&input[..], hasher, tag);
+    reader.read_to_end(&mut Vec::new())?;
+    let mut reader = VerifyingReader::new(&input[..], tag);
+    reader.read_to_end(&mut Vec::new()).unwrap_err();
     Ok(())
 }
 
+#[test]
+#[cfg(feature = "tokio")]
+fn test_tokio() -> std::io::Result<()> {
+    use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+    futures::executor::block_on(async {
+        let mut input = vec![0; 1_000_000];
+        paint_test_input(&mut input);
+        let expected = crate::hash(&input);
+
+        let mut hasher = crate::Hasher::new();
+        hasher.update_tokio_reader(&input[..]).await?;
+        assert_eq!(hasher.finalize(), expected);
+
+        let mut hasher = crate::Hasher::new();
+        hasher.write_all(&input).await?;
+        hasher.shutdown().await?;
+        assert_eq!(hasher.finalize(), expected);
+
+        let mut expected_xof = [0; 1000];
+        hasher.finalize_xof().fill(&mut expected_xof);
+        let mut reader = hasher.finalize_xof();
+        let mut buf = [0; 1000];
+        reader.read_exact(&mut buf).await?;
+        assert_eq!(buf, expected_xof);
+        assert_eq!(reader.seek(std::io::SeekFrom::Start(123)).await?, 123);
+        reader.read_exact(&mut buf[..500]).await?;
+        assert_eq!(buf[..500], expected_xof[123..623]);
+        Ok(())
+    })
+}
+
+#[test]
+#[cfg(feature = "futures-io")]
+fn test_futures_io() -> std::io::Result<()> {
+    use futures::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+    futures::executor::block_on(async {
+        let mut input = vec![0; 1_000_000];
+        paint_test_input(&mut input);
+        let expected = crate::hash(&input);
+
+        let mut hasher = crate::Hasher::new();
+        hasher.update_futures_reader(&input[..]).await?;
+        assert_eq!(hasher.finalize(), expected);
+
+        let mut hasher = crate::Hasher::new();
+        hasher.write_all(&input).await?;
+        hasher.close().await?;
+        assert_eq!(hasher.finalize(), expected);
+
+        let mut expected_xof = [0; 1000];
+        hasher.finalize_xof().fill(&mut expected_xof);
+        let mut reader = hasher.finalize_xof();
+        let mut buf = [0; 1000];
+        reader.read_exact(&mut buf).await?;
+        assert_eq!(buf, expected_xof);
+        assert_eq!(reader.seek(std::io::SeekFrom::Start(123)).await?, 123);
+        reader.read_exact(&mut buf[..500]).await?;
+        assert_eq!(buf[..500], expected_xof[123..623]);
+        Ok(())
+    })
+}
+
 #[test]
 #[cfg(feature = "mmap")]
 // NamedTempFile isn't Miri-compatible
@@ -951,6 +2086,133 @@ fn test_mmap_rayon() -> Result<(), std::io::Error> {
     Ok(())
 }
 
+#[test]
+#[cfg(feature = "mmap")]
+// NamedTempFile isn't Miri-compatible
+#[cfg(not(miri))]
+fn test_mmap_range() -> Result<(), std::io::Error> {
+    use crate::hazmat::HasherExt;
+    use std::io::prelude::*;
+    let mut input = vec![0; 1_000_000];
+    paint_test_input(&mut input);
+    let mut tempfile = tempfile::NamedTempFile::new()?;
+    tempfile.write_all(&input)?;
+    tempfile.flush()?;
+    // Short ranges use positioned reads, and long ranges use mmap. Offsets aren't page-aligned.
+    let ranges = [
+        (0, 0),
+        (0, 1),
+        (12345, 1000),
+        (12345, 100_000),
+        (0, input.len()),
+        (1, input.len() - 1),
+        (input.len(), 0),
+        (input.len() - 20_000, 20_000),
+    ];
+    for (offset, len) in ranges {
+        dbg!(offset, len);
+        let expected = crate::hash(&input[offset..][..len]);
+        let mut hasher = crate::Hasher::new();
+        hasher.update_mmap_range(tempfile.path(), offset as u64, len as u64)?;
+        assert_eq!(hasher.finalize(), expected);
+        #[cfg(feature = "rayon")]
+        {
+            let mut hasher = crate::Hasher::new();
+            hasher.update_mmap_range_rayon(tempfile.path(), offset as u64, len as u64)?;
+            assert_eq!(hasher.finalize(), expected);
+        }
+    }
+
+    // Hash the two halves of the input as subtrees, and merge them.
+    let half = input.len().next_power_of_two() / 2;
+    let left_cv = crate::Hasher::new()
+        .update_mmap_range(tempfile.path(), 0, half as u64)?
+        .finalize_non_root();
+    let right_cv = crate::Hasher::new()
+        .set_input_offset(half as u64)
+        .update_mmap_range(tempfile.path(), half as u64, (input.len() - half) as u64)?
+        .finalize_non_root();
+    assert_eq!(
+        crate::hazmat::merge_subtrees_root(&left_cv, &right_cv, crate::hazmat::Mode::Hash),
+        crate::hash(&input),
+    );
+
+    // Ranges that extend past the end of the file are errors, with or without mmap.
+    let file_len = input.len() as u64;
+    for (offset, len) in [(file_len, 1), (100, file_len), (1, u64::MAX)] {
+        let err = crate::Hasher::new()
+            .update_mmap_range(tempfile.path(), offset, len)
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "std")]
+// NamedTempFile isn't Miri-compatible
+#[cfg(not(miri))]
+fn test_update_pread() -> Result<(), std::io::Error> {
+    use crate::hazmat::HasherExt;
+    use crate::join::{SerialJoin, ThreadJoin};
+    use std::io::prelude::*;
+    let mut input = vec![0; 5 << 20];
+    paint_test_input(&mut input);
+    let join = ThreadJoin::new().max_threads(4);
+    // Files shorter than one leaf are read serially, and longer files are split into subtrees.
+    // Prefixes of the input that the Hasher has already seen change how the file is split.
+    let file_lens = [0, 1, 1023, 1024, 1025, 100_000, (3 << 20) + 1234, 4 << 20];
+    let prefix_lens = [0, 1, 1024, 1025, 65536, 1 << 20];
+    for file_len in file_lens {
+        let mut tempfile = tempfile::NamedTempFile::new()?;
+        tempfile.write_all(&input[..file_len])?;
+        tempfile.flush()?;
+        for prefix_len in prefix_lens {
+            dbg!(file_len, prefix_len);
+            let prefix = &input[input.len() - prefix_len..];
+            let mut expected_hasher = crate::Hasher::new();
+            expected_hasher.update(prefix).update(&input[..file_len]);
+            let expected = expected_hasher.finalize();
+
+            let mut hasher = crate::Hasher::new();
+            hasher.update(prefix);
+            hasher.update_pread_with_join(tempfile.path(), &join)?;
+            assert_eq!(hasher.count(), (prefix_len + file_len) as u64);
+            assert_eq!(hasher.finalize(), expected);
+
+            let mut hasher = crate::Hasher::new();
+            hasher.update(prefix);
+            hasher.update_pread_with_join(tempfile.path(), &SerialJoin)?;
+            assert_eq!(hasher.finalize(), expected);
+
+            #[cfg(feature = "rayon")]
+            {
+                let mut hasher = crate::Hasher::new();
+                hasher.update(prefix);
+                hasher.update_pread_rayon(tempfile.path())?;
+                assert_eq!(hasher.finalize(), expected);
+            }
+        }
+    }
+
+    // A subtree hasher, as the right half of a larger input.
+    let mut tempfile = tempfile::NamedTempFile::new()?;
+    tempfile.write_all(&input[4 << 20..])?;
+    tempfile.flush()?;
+    let left_cv = crate::Hasher::new()
+        .update(&input[..4 << 20])
+        .finalize_non_root();
+    let right_cv = crate::Hasher::new()
+        .set_input_offset(4 << 20)
+        .update_pread_with_join(tempfile.path(), &join)?
+        .finalize_non_root();
+    assert_eq!(
+        crate::hazmat::merge_subtrees_root(&left_cv, &right_cv, crate::hazmat::Mode::Hash),
+        crate::hash(&input),
+    );
+    Ok(())
+}
+
 #[test]
 #[cfg(feature = "std")]
 #[cfg(feature = "serde")]
@@ -996,6 +2258,781 @@ fn test_serde() {
     assert_eq!(hash_from_bytestring_cbor, hash);
 }
 
+#[test]
+#[cfg(feature = "std")]
+#[cfg(feature = "serde")]
+fn test_serde_checkpoint() {
+    let mut hasher = crate::Hasher::new_keyed(&TEST_KEY);
+    hasher.update(&[42; 5 * CHUNK_LEN + 5]);
+    let checkpoint = hasher.to_checkpoint();
+
+    let json = serde_json::to_string(&checkpoint).unwrap();
+    let from_json: crate::Checkpoint = serde_json::from_str(&json).unwrap();
+    assert_eq!(checkpoint.as_bytes(), from_json.as_bytes());
+
+    let mut cbor = Vec::<u8>::new();
+    ciborium::into_writer(&checkpoint, &mut cbor).unwrap();
+    let from_cbor: crate::Checkpoint = ciborium::from_reader(&cbor[..]).unwrap();
+    assert_eq!(checkpoint.as_bytes(), from_cbor.as_bytes());
+
+    assert_eq!(
+        crate::Hasher::from_checkpoint(&from_cbor).finalize(),
+        hasher.finalize(),
+    );
+
+    // Deserialization validates the checkpoint.
+    let mut bad_bytes = checkpoint.as_bytes().to_vec();
+    bad_bytes[0] = 0;
+    let bad_json = serde_json::to_string(&bad_bytes).unwrap();
+    assert!(serde_json::from_str::<crate::Checkpoint>(&bad_json).is_err());
+}
+
+#[test]
+#[cfg(feature = "std")]
+#[cfg(feature = "serde")]
+fn test_serde_range_proof() {
+    let input = [42; 5 * CHUNK_LEN + 5];
+    let range = CHUNK_LEN as u64..3 * CHUNK_LEN as u64;
+    let proof = crate::RangeProof::new(&input, range, crate::hazmat::Mode::Hash).unwrap();
+
+    let json = serde_json::to_string(&proof).unwrap();
+    let from_json: crate::RangeProof = serde_json::from_str(&json).unwrap();
+    assert_eq!(proof, from_json);
+
+    let mut cbor = Vec::<u8>::new();
+    ciborium::into_writer(&proof, &mut cbor).unwrap();
+    let from_cbor: crate::RangeProof = ciborium::from_reader(&cbor[..]).unwrap();
+    assert_eq!(proof, from_cbor);
+
+    // Deserializing validates the proof.
+    let bad_json = serde_json::to_string(&proof.as_bytes()[..30]).unwrap();
+    assert!(serde_json::from_str::<crate::RangeProof>(&bad_json).is_err());
+}
+
+#[test]
+#[cfg(feature = "std")]
+#[cfg(feature = "serde")]
+fn test_serde_subtree_result() {
+    use crate::hazmat::{ModeKind, SubtreeResult};
+
+    let result = SubtreeResult {
+        mode: ModeKind::DeriveKeyMaterial,
+        input_offset: 4 * CHUNK_LEN as u64,
+        len: 3 * CHUNK_LEN as u64,
+        cv: [7; 32],
+    };
+    let json = serde_json::to_string(&result).unwrap();
+    let from_json: SubtreeResult = serde_json::from_str(&json).unwrap();
+    assert_eq!(result, from_json);
+
+    let mut cbor = Vec::<u8>::new();
+    ciborium::into_writer(&result, &mut cbor).unwrap();
+    let from_cbor: SubtreeResult = ciborium::from_reader(&cbor[..]).unwrap();
+    assert_eq!(result, from_cbor);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_tree_builder() {
+    use crate::hazmat::{HasherExt, Mode, TreeBuilder, left_subtree_len};
+
+    // Split a subtree into valid pieces at random, recursing into the left and right children.
+    fn split(offset: u64, len: u64, rng: &mut impl Rng, pieces: &mut Vec<(u64, u64)>) {
+        if len == 0 {
+            // An empty input has no pieces at all.
+        } else if len <= CHUNK_LEN as u64 || (offset > 0 && rng.random_bool(0.3)) {
+            pieces.push((offset, len));
+        } else {
+            let left_len = left_subtree_len(len);
+            split(offset, left_len, rng, pieces);
+            split(offset + left_len, len - left_len, rng, pieces);
+        }
+    }
+
+    let mut rng = chacha20::ChaCha8Rng::from_seed([1; 32]);
+    let mut input = vec![0; TEST_CASES_MAX];
+    paint_test_input(&mut input);
+    for &case in TEST_CASES {
+        let input = &input[..case];
+        for keyed in [false, true] {
+            dbg!(case, keyed);
+            let (mode, expected) = if keyed {
+                (
+                    Mode::KeyedHash(&TEST_KEY),
+                    crate::keyed_hash(&TEST_KEY, input),
+                )
+            } else {
+                (Mode::Hash, crate::hash(input))
+            };
+            let mut pieces = Vec::new();
+            split(0, case as u64, &mut rng, &mut pieces);
+            pieces.shuffle(&mut rng);
+            let mut builder = TreeBuilder::new(mode);
+            if rng.random() {
+                builder.set_input_len(case as u64).unwrap();
+            }
+            assert!(!builder.is_complete() || case == 0);
+            for &(offset, len) in &pieces {
+                let bytes = &input[offset as usize..][..len as usize];
+                if offset == 0 || rng.random() {
+                    builder.add_input(offset, bytes).unwrap();
+                } else {
+                    let cv = crate::Hasher::new_internal(&mode.key_words(), mode.flags_byte())
+                        .set_input_offset(offset)
+                        .update(bytes)
+                        .finalize_non_root();
+                    builder.add_subtree(offset, len, &cv).unwrap();
+                }
+                let missing: u64 = builder
+                    .missing_ranges()
+                    .iter()
+                    .map(|r| r.end - r.start)
+                    .sum();
+                if builder.input_len().is_some() && !builder.is_complete() {
+                    assert!(builder.finalize().is_err());
+                    assert!(missing > 0);
+                }
+            }
+            // A whole number of chunks doesn't imply the input length.
+            if builder.input_len().is_none() {
+                assert!(builder.finalize().is_err());
+                let missing = builder.missing_ranges();
+                assert_eq!(missing.len(), 1);
+                assert_eq!(missing[0], case as u64..u64::MAX);
+                builder.set_input_len(case as u64).unwrap();
+            }
+            assert!(builder.is_complete());
+            assert!(builder.missing_ranges().is_empty());
+            assert_eq!(builder.finalize().unwrap(), expected);
+            let mut expected_xof = [0; 100];
+            let mut xof = [0; 100];
+            crate::Hasher::new_internal(&mode.key_words(), mode.flags_byte())
+                .update(input)
+                .finalize_xof()
+                .fill(&mut expected_xof);
+            builder.finalize_xof().unwrap().fill(&mut xof);
+            assert_eq!(xof, expected_xof);
+
+            // Adding the whole input at once is also allowed.
+            let mut builder = TreeBuilder::new(mode);
+            builder
+                .add_input(0, input)
+                .unwrap_or_else(|e| assert_eq!(case, 0, "{e}"));
+            builder.set_input_len(case as u64).unwrap();
+            assert_eq!(builder.finalize().unwrap(), expected);
+
+            // So is adding everything after the first chunk at once, which spans many subtrees.
+            if case > CHUNK_LEN {
+                let mut builder = TreeBuilder::new(mode);
+                builder
+                    .add_input(CHUNK_LEN as u64, &input[CHUNK_LEN..])
+                    .unwrap();
+                builder.add_input(0, &input[..CHUNK_LEN]).unwrap();
+                builder.set_input_len(case as u64).unwrap();
+                assert_eq!(builder.finalize().unwrap(), expected);
+            }
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_tree_builder_errors() {
+    use crate::hazmat::{HasherExt, Mode, TreeBuilder};
+
+    let chunk = [7; CHUNK_LEN];
+    let chunk_cv = crate::Hasher::new()
+        .set_input_offset(CHUNK_LEN as u64)
+        .update(&chunk)
+        .finalize_non_root();
+    let c = CHUNK_LEN as u64;
+    let mut builder = TreeBuilder::new(Mode::Hash);
+    let err = |result: Result<(), crate::hazmat::TreeError>| result.unwrap_err().to_string();
+
+    assert_eq!(
+        err(builder.add_subtree(1, c, &chunk_cv)),
+        "offset (1) must be a chunk boundary (divisible by 1024)",
+    );
+    assert_eq!(
+        err(builder.add_input(c, &[])),
+        "empty subtrees are never valid",
+    );
+    assert_eq!(
+        err(builder.add_subtree(c, 2 * c, &chunk_cv)),
+        "the subtree starting at 1024 contains at most 1024 bytes (found 2048)",
+    );
+    assert_eq!(
+        err(builder.add_subtree(u64::MAX - 1023, 2 * c, &chunk_cv)),
+        "the subtree starting at 18446744073709550592 contains at most 1023 bytes (found 2048)",
+    );
+    // A three-chunk subtree has to end the input.
+    builder.add_subtree(4 * c, 3 * c, &chunk_cv).unwrap();
+    assert_eq!(builder.input_len(), Some(7 * c));
+    assert_eq!(
+        err(builder.set_input_len(8 * c)),
+        "a subtree ending at 8192 doesn't fit an input of 7168 bytes",
+    );
+    assert_eq!(
+        err(builder.add_input(2 * c, &[0; 100])),
+        "a subtree ending at 2148 doesn't fit an input of 7168 bytes",
+    );
+    builder.add_subtree(c, c, &chunk_cv).unwrap();
+    assert_eq!(
+        err(builder.add_input(c, &chunk)),
+        "the range 1024..2048 overlaps a range that's already been added",
+    );
+    assert_eq!(builder.missing_ranges(), [0..c, 2 * c..4 * c]);
+    assert_eq!(
+        builder.finalize().unwrap_err().to_string(),
+        "the tree is incomplete, 3072 bytes of input are missing",
+    );
+
+    // A chaining value can never be the root.
+    let mut builder = TreeBuilder::new(Mode::Hash);
+    builder.add_subtree(0, 2 * c, &chunk_cv).unwrap();
+    assert_eq!(
+        err(builder.set_input_len(2 * c)),
+        "a chaining value for 0..2048 would be the root of an input of 2048 bytes",
+    );
+    assert_eq!(
+        builder.finalize().unwrap_err().to_string(),
+        "the input length isn't known yet",
+    );
+    let mut builder = TreeBuilder::new(Mode::Hash);
+    builder.set_input_len(c).unwrap();
+    assert_eq!(
+        err(builder.add_subtree(0, c, &chunk_cv)),
+        "a chaining value for 0..1024 would be the root of an input of 1024 bytes",
+    );
+    builder.add_input(0, &chunk).unwrap();
+    assert_eq!(builder.finalize().unwrap(), crate::hash(&chunk));
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_combine_subtrees() {
+    use crate::hazmat::{
+        HasherExt, Mode, SubtreeResult, combine_subtrees, combine_subtrees_xof, left_subtree_len,
+    };
+
+    // Split a subtree into valid pieces at random, recursing into the left and right children.
+    fn split(offset: u64, len: u64, rng: &mut impl Rng, pieces: &mut Vec<(u64, u64)>) {
+        if len <= CHUNK_LEN as u64 || (offset > 0 && rng.random_bool(0.3)) {
+            pieces.push((offset, len));
+        } else {
+            let left_len = left_subtree_len(len);
+            split(offset, left_len, rng, pieces);
+            split(offset + left_len, len - left_len, rng, pieces);
+        }
+    }
+
+    let mut rng = chacha20::ChaCha8Rng::from_seed([2; 32]);
+    let mut input = vec![0; TEST_CASES_MAX];
+    paint_test_input(&mut input);
+    let context_key = crate::hazmat::hash_derive_key_context("test_combine_subtrees");
+    for &case in TEST_CASES {
+        if case <= CHUNK_LEN {
+            // The root is a chunk, not a combination of subtrees.
+            continue;
+        }
+        let input = &input[..case];
+        for mode in [
+            Mode::Hash,
+            Mode::KeyedHash(&TEST_KEY),
+            Mode::DeriveKeyMaterial(&context_key),
+        ] {
+            let mut expected_hasher =
+                crate::Hasher::new_internal(&mode.key_words(), mode.flags_byte());
+            expected_hasher.update(input);
+            // Fixed-size groups, with the last one possibly short, and random subtrees.
+            let mut splits = Vec::new();
+            for group_len in [CHUNK_LEN, 4 * CHUNK_LEN] {
+                let groups = (0..case)
+                    .step_by(group_len)
+                    .map(|offset| {
+                        (
+                            offset as u64,
+                            std::cmp::min(group_len, case - offset) as u64,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                splits.push(groups);
+            }
+            let mut random = Vec::new();
+            split(0, case as u64, &mut rng, &mut random);
+            splits.push(random);
+            for mut pieces in splits {
+                dbg!(case, &pieces);
+                if pieces.len() < 2 {
+                    continue;
+                }
+                pieces.shuffle(&mut rng);
+                let mut results = pieces
+                    .iter()
+                    .map(|&(offset, len)| {
+                        let cv = crate::Hasher::new_internal(&mode.key_words(), mode.flags_byte())
+                            .set_input_offset(offset)
+                            .update(&input[offset as usize..][..len as usize])
+                            .finalize_non_root();
+                        let result = SubtreeResult {
+                            mode: mode.kind(),
+                            input_offset: offset,
+                            len,
+                            cv,
+                        };
+                        SubtreeResult::from_bytes(&result.to_bytes()).unwrap()
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(
+                    combine_subtrees(&mut results, mode).unwrap(),
+                    expected_hasher.finalize(),
+                );
+                let mut expected_xof = [0; 100];
+                expected_hasher.finalize_xof().fill(&mut expected_xof);
+                let mut xof = [0; 100];
+                results.shuffle(&mut rng);
+                combine_subtrees_xof(&mut results, mode)
+                    .unwrap()
+                    .fill(&mut xof);
+                assert_eq!(xof, expected_xof);
+            }
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_combine_subtrees_errors() {
+    use crate::hazmat::{Mode, ModeKind, SubtreeResult, combine_subtrees};
+
+    let result = |offset: usize, len: usize| SubtreeResult {
+        mode: ModeKind::Hash,
+        input_offset: offset as u64,
+        len: len as u64,
+        cv: [0; 32],
+    };
+    let check = |results: &[SubtreeResult], expected: &str| {
+        let mut results = results.to_vec();
+        let err = combine_subtrees(&mut results, Mode::Hash).unwrap_err();
+        assert_eq!(err.to_string(), expected);
+    };
+    const C: usize = CHUNK_LEN;
+    check(&[], "there are no subtree results to combine");
+    check(
+        &[
+            SubtreeResult {
+                mode: ModeKind::KeyedHash,
+                ..result(0, 2 * C)
+            },
+            result(2 * C, 5),
+        ],
+        "the subtree starting at 0 was computed in KeyedHash mode, not Hash",
+    );
+    check(
+        &[result(C + 1, C), result(0, C + 1)],
+        "offset (1025) must be a chunk boundary (divisible by 1024)",
+    );
+    check(
+        &[result(0, 2 * C), result(C, C)],
+        "the subtree starting at 1024 overlaps the previous one, which ends at 2048",
+    );
+    check(
+        &[result(C, C), result(2 * C, C)],
+        "no subtree covers the range 0..1024",
+    );
+    check(
+        &[result(0, 2 * C), result(2 * C, C), result(4 * C, C)],
+        "no subtree covers the range 3072..4096",
+    );
+    check(
+        &[result(0, C), result(C, 0)],
+        "the subtree starting at 1024 is empty",
+    );
+    check(
+        &[result(0, C), result(C, 2 * C)],
+        "the subtree starting at 1024 contains at most 1024 bytes (found 2048)",
+    );
+    check(
+        &[result(0, 3 * C), result(3 * C, C)],
+        "0..3072 isn't a subtree of an input that continues past it",
+    );
+    check(
+        &[result(0, 5 * C)],
+        "a chaining value for 0..5120 would be the root of an input of 5120 bytes",
+    );
+    let overflow = SubtreeResult {
+        input_offset: 1 << 63,
+        len: 1 << 63,
+        ..result(0, 0)
+    };
+    check(
+        &[result(0, 1 << 63), overflow],
+        "the subtree starting at 9223372036854775808 ends past u64::MAX",
+    );
+
+    // Decoding errors.
+    let bytes = result(0, C).to_bytes();
+    assert_eq!(bytes.len(), SubtreeResult::ENCODED_LEN);
+    let mut bad = bytes;
+    bad[0] = 2;
+    let err = SubtreeResult::from_bytes(&bad).unwrap_err();
+    assert_eq!(err.to_string(), "unsupported subtree result version: 2");
+    let err = SubtreeResult::from_bytes(&bytes[..49]).unwrap_err();
+    assert_eq!(err.to_string(), "invalid subtree result length: 49");
+    let err = SubtreeResult::from_bytes(&[]).unwrap_err();
+    assert_eq!(err.to_string(), "invalid subtree result length: 0");
+    bad = bytes;
+    bad[1] = 3;
+    let err = SubtreeResult::from_bytes(&bad).unwrap_err();
+    assert_eq!(err.to_string(), "invalid subtree result mode: 3");
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_verified_streaming() {
+    use crate::hazmat::{HasherExt, Mode, left_subtree_len};
+    use crate::io::HashMismatch;
+    use crate::verified::{Decoder, HEADER_LEN, decode, encode, encoded_len};
+    use std::io::prelude::*;
+
+    let mut input = vec![0; TEST_CASES_MAX];
+    paint_test_input(&mut input);
+    for &case in TEST_CASES {
+        let input = &input[..case];
+        for mode in [Mode::Hash, Mode::KeyedHash(&TEST_KEY)] {
+            dbg!(case);
+            let (encoded, hash) = encode(input, mode);
+            let expected_hash = match mode {
+                Mode::Hash => crate::hash(input),
+                _ => crate::keyed_hash(&TEST_KEY, input),
+            };
+            assert_eq!(hash, expected_hash);
+            assert_eq!(encoded.len() as u128, encoded_len(case as u64));
+            assert_eq!(encoded[..HEADER_LEN], (case as u64).to_le_bytes());
+            // The root parent node comes first.
+            if case > CHUNK_LEN {
+                let left_len = left_subtree_len(case as u64) as usize;
+                let new_hasher =
+                    || crate::Hasher::new_internal(&mode.key_words(), mode.flags_byte());
+                let left_cv = new_hasher().update(&input[..left_len]).finalize_non_root();
+                let right_cv = new_hasher()
+                    .set_input_offset(left_len as u64)
+                    .update(&input[left_len..])
+                    .finalize_non_root();
+                assert_eq!(encoded[HEADER_LEN..][..OUT_LEN], left_cv);
+                assert_eq!(encoded[HEADER_LEN + OUT_LEN..][..OUT_LEN], right_cv);
+            }
+            assert_eq!(decode(&encoded, &hash, mode).unwrap(), input);
+
+            // Small reads work too.
+            let mut decoder = Decoder::new(&encoded[..], &hash, mode);
+            let mut output = Vec::new();
+            let mut buf = [0; 100];
+            loop {
+                let n = decoder.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                output.extend_from_slice(&buf[..n]);
+            }
+            assert_eq!(decoder.input_len(), Some(case as u64));
+            assert_eq!(output, input);
+
+            // The wrong hash or mode fails.
+            let wrong_hash = crate::hash(b"wrong");
+            let err = decode(&encoded, &wrong_hash, mode).unwrap_err();
+            assert!(HashMismatch::is_mismatch(&err));
+            let wrong_mode = Mode::KeyedHash(&[0; 32]);
+            let err = decode(&encoded, &hash, wrong_mode).unwrap_err();
+            assert!(HashMismatch::is_mismatch(&err));
+
+            // Truncation fails.
+            let err = decode(&encoded[..encoded.len() - 1], &hash, mode).unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+        }
+    }
+
+    // Corrupting any byte fails, and nothing after the corruption is ever returned.
+    let input = &input[..3 * CHUNK_LEN + 1];
+    let (encoded, hash) = encode(input, Mode::Hash);
+    for i in (0..encoded.len()).step_by(37) {
+        let mut corrupt = encoded.clone();
+        corrupt[i] ^= 1;
+        let mut decoder = Decoder::new(&corrupt[..], &hash, Mode::Hash);
+        let mut output = Vec::new();
+        let err = decoder.read_to_end(&mut output).unwrap_err();
+        // A corrupt header can make the input look longer than the encoding.
+        assert!(HashMismatch::is_mismatch(&err) || i < HEADER_LEN, "{i}");
+        assert!(input.starts_with(&output));
+        assert!(output.len() < input.len());
+        // Later reads fail too.
+        assert!(decoder.read(&mut [0; 100]).is_err());
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_outboard() {
+    use crate::hazmat::Mode;
+    use crate::verified::{
+        HEADER_LEN, encode, outboard_len, verify_outboard_range, write_outboard,
+    };
+
+    let mut input = vec![0; TEST_CASES_MAX];
+    paint_test_input(&mut input);
+    for group_len in [
+        CHUNK_LEN as u64,
+        4 * CHUNK_LEN as u64,
+        16 * CHUNK_LEN as u64,
+    ] {
+        for &case in TEST_CASES {
+            let input = &input[..case];
+            for mode in [Mode::Hash, Mode::KeyedHash(&TEST_KEY)] {
+                dbg!(group_len, case);
+                let mut outboard = std::io::Cursor::new(Vec::new());
+                let hash =
+                    write_outboard(input, case as u64, &mut outboard, group_len, mode).unwrap();
+                let outboard = outboard.into_inner();
+                let (encoded, expected_hash) = encode(input, mode);
+                assert_eq!(hash, expected_hash);
+                assert_eq!(outboard.len() as u64, outboard_len(case as u64, group_len));
+                // The header and the root parent node are the same as in the combined encoding.
+                let root_len = if case as u64 > group_len {
+                    HEADER_LEN + 64
+                } else {
+                    HEADER_LEN
+                };
+                assert_eq!(outboard[..root_len], encoded[..root_len]);
+
+                // Verify every range that starts at a group, and either ends at the next group or
+                // at the end of the input.
+                let num_groups = std::cmp::max(1, case.div_ceil(group_len as usize));
+                for i in 0..num_groups {
+                    let start = i * group_len as usize;
+                    for end in [std::cmp::min(start + group_len as usize, case), case] {
+                        let range = &input[start..end];
+                        verify_outboard_range(
+                            &outboard,
+                            group_len,
+                            start as u64,
+                            range,
+                            &hash,
+                            mode,
+                        )
+                        .unwrap();
+                        if range.is_empty() {
+                            continue;
+                        }
+                        let mut corrupt = range.to_vec();
+                        corrupt[range.len() / 2] ^= 1;
+                        let err = verify_outboard_range(
+                            &outboard,
+                            group_len,
+                            start as u64,
+                            &corrupt,
+                            &hash,
+                            mode,
+                        )
+                        .unwrap_err();
+                        assert!(err.is_mismatch());
+                    }
+                }
+
+                // Corrupting any parent node fails verification of the whole input.
+                for i in (HEADER_LEN..outboard.len()).step_by(61) {
+                    let mut corrupt = outboard.clone();
+                    corrupt[i] ^= 1;
+                    let err = verify_outboard_range(&corrupt, group_len, 0, input, &hash, mode)
+                        .unwrap_err();
+                    assert!(err.is_mismatch());
+                }
+
+                // Invalid arguments are errors, but not mismatches.
+                if case > 0 {
+                    let wrong_len = &outboard[..outboard.len() - 1];
+                    let err = verify_outboard_range(wrong_len, group_len, 0, input, &hash, mode)
+                        .unwrap_err();
+                    assert!(!err.is_mismatch(), "{err}");
+                    let err = verify_outboard_range(&outboard, group_len, 0, &[], &hash, mode)
+                        .unwrap_err();
+                    assert!(!err.is_mismatch(), "{err}");
+                }
+                if case as u64 > group_len {
+                    let err =
+                        verify_outboard_range(&outboard, group_len, 1, &input[1..], &hash, mode)
+                            .unwrap_err();
+                    assert_eq!(
+                        err.to_string(),
+                        format!(
+                            "a range of {} bytes at offset 1 isn't a whole number of groups in an \
+                             input of {case} bytes",
+                            case - 1,
+                        ),
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_write_outboard_streaming() {
+    use crate::hazmat::Mode;
+    use crate::verified::{HEADER_LEN, PARENT_LEN, outboard_len, write_outboard};
+    use core::cell::Cell;
+    use std::io::{self, Cursor, SeekFrom};
+
+    // A writer that counts the bytes it receives, and a reader that records that count when it's
+    // first asked for the second half of the input.
+    struct CountingWriter<'a> {
+        inner: Cursor<Vec<u8>>,
+        written: &'a Cell<u64>,
+    }
+    impl io::Write for CountingWriter<'_> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = self.inner.write(buf)?;
+            self.written.set(self.written.get() + n as u64);
+            Ok(n)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    impl io::Seek for CountingWriter<'_> {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+    struct WatchingReader<'a> {
+        input: &'a [u8],
+        position: usize,
+        written: &'a Cell<u64>,
+        written_at_half: Option<u64>,
+    }
+    impl io::Read for WatchingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.position >= self.input.len() / 2 && self.written_at_half.is_none() {
+                self.written_at_half = Some(self.written.get());
+            }
+            let n = io::Read::read(&mut &self.input[self.position..], buf)?;
+            self.position += n;
+            Ok(n)
+        }
+    }
+
+    let group_len = CHUNK_LEN as u64;
+    let num_groups = 256;
+    let mut input = vec![0; num_groups * CHUNK_LEN];
+    paint_test_input(&mut input);
+    let input_len = input.len() as u64;
+    let written = Cell::new(0);
+    let mut reader = WatchingReader {
+        input: &input,
+        position: 0,
+        written: &written,
+        written_at_half: None,
+    };
+    // Start partway through the writer, after some other data.
+    let mut writer = CountingWriter {
+        inner: Cursor::new(b"prefix".to_vec()),
+        written: &written,
+    };
+    writer.inner.set_position(6);
+    let hash = write_outboard(&mut reader, input_len, &mut writer, group_len, Mode::Hash).unwrap();
+    assert_eq!(hash, crate::hash(&input));
+
+    // The parent nodes of the left half of the tree were written before the right half was read.
+    let left_half_len = (HEADER_LEN + (num_groups / 2 - 1) * PARENT_LEN) as u64;
+    assert!(reader.written_at_half.unwrap() >= left_half_len);
+    let outboard = writer.inner.into_inner();
+    let expected_len = outboard_len(input_len, group_len);
+    assert_eq!(outboard.len() as u64, "prefix".len() as u64 + expected_len);
+    assert_eq!(written.get(), expected_len);
+    assert_eq!(&outboard[..6], b"prefix");
+
+    // An input that doesn't match input_len is an error.
+    let mut writer = Cursor::new(Vec::new());
+    let err = write_outboard(
+        &input[..],
+        input_len + 1,
+        &mut writer,
+        group_len,
+        Mode::Hash,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    let err = write_outboard(
+        &input[..],
+        input_len - 1,
+        &mut writer,
+        group_len,
+        Mode::Hash,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn test_update_outboard() {
+    use crate::hazmat::Mode;
+    use crate::verified::{update_outboard, verify_outboard_range, write_outboard};
+
+    let mut original = vec![0; TEST_CASES_MAX];
+    paint_test_input(&mut original);
+    for group_len in [CHUNK_LEN as u64, 4 * CHUNK_LEN as u64] {
+        for &case in TEST_CASES {
+            let original = &original[..case];
+            let mode = Mode::KeyedHash(&TEST_KEY);
+            let mut outboard = std::io::Cursor::new(Vec::new());
+            write_outboard(original, case as u64, &mut outboard, group_len, mode).unwrap();
+            let mut outboard = outboard.into_inner();
+
+            // Change the first, middle, and last groups, one at a time, and then all of them.
+            let num_groups = std::cmp::max(1, case.div_ceil(group_len as usize));
+            let mut input = original.to_vec();
+            let last_group = num_groups - 1;
+            let middle_group = num_groups / 2;
+            for (first, last) in [
+                (0, 0),
+                (middle_group, middle_group),
+                (last_group, last_group),
+                (0, last_group),
+            ] {
+                dbg!(group_len, case, first, last);
+                let start = first * group_len as usize;
+                let end = std::cmp::min((last + 1) * group_len as usize, case);
+                for byte in &mut input[start..end] {
+                    *byte = byte.wrapping_add(1);
+                }
+                let hash = update_outboard(
+                    &mut outboard,
+                    group_len,
+                    start as u64,
+                    &input[start..end],
+                    mode,
+                )
+                .unwrap();
+                assert_eq!(hash, crate::keyed_hash(&TEST_KEY, &input));
+                let mut expected = std::io::Cursor::new(Vec::new());
+                write_outboard(&input[..], case as u64, &mut expected, group_len, mode).unwrap();
+                assert_eq!(outboard, expected.into_inner());
+                verify_outboard_range(&outboard, group_len, 0, &input, &hash, mode).unwrap();
+            }
+
+            // Ranges of partial groups are errors, and the outboard tree stays the same.
+            if case as u64 > group_len {
+                let before = outboard.clone();
+                let err =
+                    update_outboard(&mut outboard, group_len, 1, &input[1..], mode).unwrap_err();
+                assert!(!err.is_mismatch());
+                assert_eq!(outboard, before);
+            }
+        }
+    }
+}
+
 // `cargo +nightly miri test` currently works, but it takes forever, because some of our test
 // inputs are quite large. Most of our unsafe code is platform specific and incompatible with Miri
 // anyway, but we'd like it to be possible for callers to run their own tests under Miri, assuming
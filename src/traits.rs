@@ -1,9 +1,12 @@
 //! Implementations of commonly used traits like `Digest` and `Mac` from the
-//! [`digest`](https://crates.io/crates/digest) crate.
+//! [`digest`](https://crates.io/crates/digest) crate, and `StreamCipher` and
+//! `StreamCipherSeek` from the [`cipher`](https://crates.io/crates/cipher)
+//! crate.
 
+pub use cipher;
 pub use digest;
 
-use crate::{Hasher, OutputReader};
+use crate::{BLOCK_LEN, Hasher, OutputReader};
 use digest::array::{Array, typenum::U32, typenum::U64};
 use digest::common;
 
@@ -85,6 +88,55 @@ impl digest::KeyInit for Hasher {
     }
 }
 
+/// The keystream is the extended output. Note that this makes the keystream of every
+/// `OutputReader` depend on the input and key of the `Hasher` that produced it, so using BLAKE3 as
+/// a stream cipher requires a unique input (a nonce) for every keystream.
+impl cipher::StreamCipher for OutputReader {
+    fn check_remaining(&self, data_len: usize) -> Result<(), cipher::StreamCipherError> {
+        // The maximum output size is 2^64-1 bytes.
+        let remaining = u64::MAX - self.position();
+        match u64::try_from(data_len) {
+            Ok(len) if len <= remaining => Ok(()),
+            _ => Err(cipher::StreamCipherError),
+        }
+    }
+
+    #[inline]
+    fn unchecked_apply_keystream_inout(&mut self, buf: cipher::InOutBuf<'_, '_, u8>) {
+        self.xor_into(buf.into_out_with_copied_in());
+    }
+
+    #[inline]
+    fn unchecked_write_keystream(&mut self, buf: &mut [u8]) {
+        self.fill(buf);
+    }
+}
+
+impl cipher::StreamCipherSeek for OutputReader {
+    fn try_current_pos<T: cipher::SeekNum>(&self) -> Result<T, cipher::OverflowError> {
+        // SeekNum::from_block_byte() computes (block - 1) * block_size + byte, with a nonzero
+        // byte. That's the convention of the RustCrypto block buffer, where `block` is the next
+        // block to generate and `byte` is the position within the previous one.
+        let position = self.position();
+        let block_len = BLOCK_LEN as u64;
+        let (block, byte) = match position % block_len {
+            0 => (position / block_len, BLOCK_LEN as u8),
+            byte => (position / block_len + 1, byte as u8),
+        };
+        T::from_block_byte(block, byte, BLOCK_LEN as u8)
+    }
+
+    fn try_seek<T: cipher::SeekNum>(&mut self, pos: T) -> Result<(), cipher::StreamCipherError> {
+        let (block, byte) = pos.into_block_byte::<u64>(BLOCK_LEN as u8)?;
+        let position = block
+            .checked_mul(BLOCK_LEN as u64)
+            .and_then(|x| x.checked_add(byte as u64))
+            .ok_or(cipher::StreamCipherError)?;
+        self.set_position(position);
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use digest::array::AsArrayMut;
@@ -167,6 +219,46 @@ mod test {
         assert_eq!(out1.as_bytes(), out2.into_bytes().as_slice());
     }
 
+    #[test]
+    fn test_stream_cipher_traits() {
+        use cipher::{StreamCipher, StreamCipherSeek};
+
+        let mut expected = [0; 301];
+        crate::Hasher::new_keyed(&[42; 32])
+            .update(b"nonce")
+            .finalize_xof()
+            .fill(&mut expected);
+
+        let mut reader = crate::Hasher::new_keyed(&[42; 32])
+            .update(b"nonce")
+            .finalize_xof();
+        let mut buf = [0; 301];
+        reader.apply_keystream(&mut buf);
+        assert_eq!(buf, expected);
+        assert_eq!(reader.current_pos::<u64>(), 301);
+
+        // Buffer to buffer, from an unaligned position.
+        for pos in [0u64, 1, 63, 64, 65, 200] {
+            reader.seek(pos);
+            assert_eq!(reader.current_pos::<u64>(), pos);
+            assert_eq!(reader.current_pos::<u32>(), pos as u32);
+            let input = [0xff; 100];
+            let mut output = [0; 100];
+            reader.apply_keystream_b2b(&input, &mut output);
+            for i in 0..100 {
+                assert_eq!(output[i], 0xff ^ expected[pos as usize + i]);
+            }
+            assert_eq!(reader.position(), pos + 100);
+        }
+
+        // The end of the keystream.
+        reader.set_position(u64::MAX - 10);
+        assert!(reader.try_apply_keystream(&mut [0; 11]).is_err());
+        assert!(reader.try_apply_keystream(&mut [0; 10]).is_ok());
+        assert!(reader.try_current_pos::<u32>().is_err());
+        assert!(reader.try_seek(u128::MAX).is_err());
+    }
+
     fn expected_hmac_blake3(key: &[u8], input: &[u8]) -> [u8; 32] {
         // See https://en.wikipedia.org/wiki/HMAC.
         let key_hash;
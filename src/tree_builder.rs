@@ -0,0 +1,527 @@
+//! Assembling a BLAKE3 tree from subtrees that arrive in any order. See [`TreeBuilder`].
+
+use crate::hazmat::{ChainingValue, HasherExt, Mode, left_subtree_len, max_subtree_len};
+use crate::platform::Platform;
+use crate::{CHUNK_LEN, CVWords, ChunkState, Hash, Hasher, Output, OutputReader};
+use core::fmt;
+use core::ops::Range;
+use std::collections::BTreeMap;
+use std::vec::Vec;
+
+/// Assemble a root hash from chunks and subtrees that arrive in any order.
+///
+/// The [`HasherExt`] methods and the `merge_subtrees_*` functions compute the pieces of a tree,
+/// but they leave it up to the caller to keep track of which pieces they have and how they fit
+/// together. `TreeBuilder` does that bookkeeping. Give it chaining values for subtrees with
+/// [`add_subtree`](TreeBuilder::add_subtree), or raw chunk-aligned input with
+/// [`add_input`](TreeBuilder::add_input), in any order. Neighboring subtrees are merged into
+/// their parent as soon as they're both present, so memory use stays proportional to the number
+/// of gaps rather than the number of pieces. Once the total input length is known and all of the
+/// input is covered, [`finalize`](TreeBuilder::finalize) and
+/// [`finalize_xof`](TreeBuilder::finalize_xof) return the root. In the meantime,
+/// [`missing_ranges`](TreeBuilder::missing_ranges) lists what's still needed.
+///
+/// Every piece is checked against the tree structure: its offset must be a multiple of
+/// [`CHUNK_LEN`], its length can't exceed [`max_subtree_len`] of its offset, and it can't overlap
+/// any piece already added. Only a subtree that ends the input can be shorter than a power-of-two
+/// number of chunks, so adding one tells the `TreeBuilder` the total input length, if
+/// [`set_input_len`](TreeBuilder::set_input_len) hasn't already. These checks catch subtrees in
+/// the wrong place, but they can't catch a chaining value that was computed incorrectly, for
+/// example with the wrong [`Mode`] or the wrong input offset. As with the rest of the
+/// [`hazmat`](crate::hazmat) module, test your code against [`blake3::hash`](crate::hash).
+///
+/// The root node is never a chaining value. If the whole input is one chunk or less, it has to be
+/// added with [`add_input`](TreeBuilder::add_input).
+///
+/// This type requires the `std` Cargo feature, which is enabled by default.
+///
+/// # Example
+///
+/// ```
+/// use blake3::hazmat::{HasherExt, Mode, TreeBuilder};
+/// use blake3::{CHUNK_LEN, Hasher};
+///
+/// let input = [42; 5 * CHUNK_LEN + 100];
+/// let mut builder = TreeBuilder::new(Mode::Hash);
+///
+/// // The final subtree ends the input, so this tells the builder the input length.
+/// let last_cv = Hasher::new()
+///     .set_input_offset(4 * CHUNK_LEN as u64)
+///     .update(&input[4 * CHUNK_LEN..])
+///     .finalize_non_root();
+/// builder.add_subtree(4 * CHUNK_LEN as u64, (CHUNK_LEN + 100) as u64, &last_cv)?;
+/// assert_eq!(builder.missing_ranges(), [0..4 * CHUNK_LEN as u64]);
+///
+/// builder.add_input(CHUNK_LEN as u64, &input[CHUNK_LEN..4 * CHUNK_LEN])?;
+/// builder.add_input(0, &input[..CHUNK_LEN])?;
+/// assert!(builder.missing_ranges().is_empty());
+/// assert_eq!(builder.finalize()?, blake3::hash(&input));
+/// # Ok::<(), blake3::hazmat::TreeError>(())
+/// ```
+#[derive(Clone)]
+pub struct TreeBuilder {
+    key: CVWords,
+    flags: u8,
+    platform: Platform,
+    input_len: Option<u64>,
+    // Disjoint subtrees, keyed by input offset, with their lengths and chaining values.
+    subtrees: BTreeMap<u64, (u64, ChainingValue)>,
+    // The output of the first chunk, if it was added as input and it might be the root. Its
+    // chaining value is also in `subtrees` if it's a whole chunk, since it might not be.
+    first_chunk: Option<(u64, Output)>,
+}
+
+impl TreeBuilder {
+    /// Construct a new, empty `TreeBuilder` for the given [`Mode`]. All the chaining values given
+    /// to [`add_subtree`](TreeBuilder::add_subtree) must use the same mode.
+    pub fn new(mode: Mode) -> Self {
+        Self {
+            key: mode.key_words(),
+            flags: mode.flags_byte(),
+            platform: Platform::detect(),
+            input_len: None,
+            subtrees: BTreeMap::new(),
+            first_chunk: None,
+        }
+    }
+
+    /// The total input length in bytes, if it's known.
+    pub fn input_len(&self) -> Option<u64> {
+        self.input_len
+    }
+
+    /// Set the total input length in bytes.
+    ///
+    /// This is an error if a different length has already been set or implied by a subtree that
+    /// ends the input, if a subtree that's already been added extends past `input_len`, or if a
+    /// chaining value that's already been added turns out to be the root.
+    pub fn set_input_len(&mut self, input_len: u64) -> Result<(), TreeError> {
+        self.check_input_len(input_len)?;
+        self.apply_input_len(input_len);
+        Ok(())
+    }
+
+    /// Add the chaining value of the subtree of `len` bytes starting at `input_offset`, as
+    /// returned by [`finalize_non_root`](HasherExt::finalize_non_root) or
+    /// [`merge_subtrees_non_root`](crate::hazmat::merge_subtrees_non_root).
+    ///
+    /// A subtree that isn't a power-of-two number of chunks must end the input, and it sets the
+    /// [`input_len`](TreeBuilder::input_len) if it isn't already set.
+    pub fn add_subtree(
+        &mut self,
+        input_offset: u64,
+        len: u64,
+        chaining_value: &ChainingValue,
+    ) -> Result<(), TreeError> {
+        let is_complete = len.is_power_of_two() && len >= CHUNK_LEN as u64;
+        let input_len = self.check_range(input_offset, len, !is_complete)?;
+        if let Some(max) = max_subtree_len(input_offset).filter(|&max| len > max) {
+            let offset = input_offset;
+            return Err(TreeError(TreeErrorInner::TooLong { offset, len, max }));
+        }
+        if input_offset == 0 && input_len == Some(len) {
+            return Err(TreeError(TreeErrorInner::Root { len }));
+        }
+        if let Some(input_len) = input_len {
+            self.apply_input_len(input_len);
+        }
+        self.insert(input_offset, len, *chaining_value);
+        Ok(())
+    }
+
+    /// Add raw input bytes starting at `input_offset`, which must be a multiple of [`CHUNK_LEN`].
+    ///
+    /// The input can span any number of chunks and subtrees. It's split and hashed along subtree
+    /// boundaries. As with [`add_subtree`](TreeBuilder::add_subtree), input that isn't a whole
+    /// number of chunks must end the input, and it sets the
+    /// [`input_len`](TreeBuilder::input_len) if it isn't already set.
+    pub fn add_input(&mut self, input_offset: u64, input: &[u8]) -> Result<(), TreeError> {
+        let len = input.len() as u64;
+        let partial_chunk_len = len % CHUNK_LEN as u64;
+        let input_len = self.check_range(input_offset, len, partial_chunk_len > 0)?;
+        if let Some(input_len) = input_len {
+            self.apply_input_len(input_len);
+        }
+        let mut offset = input_offset;
+        let mut input = input;
+        if offset == 0 && len <= CHUNK_LEN as u64 {
+            // The first chunk might be the root, so keep its Output until we know.
+            let output = ChunkState::new(&self.key, 0, self.flags, self.platform)
+                .update(input)
+                .output();
+            if self.input_len != Some(len) {
+                debug_assert_eq!(len, CHUNK_LEN as u64);
+                self.insert(0, len, output.chaining_value());
+            }
+            if self
+                .input_len
+                .is_none_or(|input_len| input_len <= CHUNK_LEN as u64)
+            {
+                self.first_chunk = Some((len, output));
+            }
+            return Ok(());
+        }
+        while !input.is_empty() {
+            let remaining = input.len() as u64;
+            let ends_input = self.input_len == Some(offset + remaining);
+            let len = if offset == 0 {
+                // The whole input is never a subtree, and an input that's a power of two might
+                // turn out to be the whole input.
+                if ends_input || (self.input_len.is_none() && remaining.is_power_of_two()) {
+                    left_subtree_len(remaining)
+                } else {
+                    largest_power_of_two_leq(remaining)
+                }
+            } else {
+                let max = max_subtree_len(offset).unwrap();
+                if ends_input && remaining <= max {
+                    remaining
+                } else {
+                    largest_power_of_two_leq(remaining.min(max))
+                }
+            };
+            let (subtree, rest) = input.split_at(len as usize);
+            let chaining_value = Hasher::new_internal(&self.key, self.flags)
+                .set_input_offset(offset)
+                .update(subtree)
+                .finalize_non_root();
+            self.insert(offset, len, chaining_value);
+            offset += len;
+            input = rest;
+        }
+        Ok(())
+    }
+
+    /// The ranges of input that haven't been added yet, in order. If the input length isn't
+    /// known yet, the last range ends at `u64::MAX`.
+    pub fn missing_ranges(&self) -> Vec<Range<u64>> {
+        let mut missing = Vec::new();
+        match self.input_len {
+            Some(input_len) if input_len <= CHUNK_LEN as u64 => {
+                if input_len > 0 && self.first_chunk_len() != Some(input_len) {
+                    missing.push(0..input_len);
+                }
+                return missing;
+            }
+            _ => {}
+        }
+        let mut position = 0;
+        for (&offset, &(len, _)) in &self.subtrees {
+            if offset > position {
+                missing.push(position..offset);
+            }
+            position = offset + len;
+        }
+        let end = self.input_len.unwrap_or(u64::MAX);
+        if position < end {
+            missing.push(position..end);
+        }
+        missing
+    }
+
+    /// Return true if the input length is known and all of the input has been added.
+    pub fn is_complete(&self) -> bool {
+        self.input_len.is_some() && self.missing_ranges().is_empty()
+    }
+
+    /// Finalize the root hash. This is an error if the tree isn't
+    /// [complete](TreeBuilder::is_complete) yet.
+    pub fn finalize(&self) -> Result<Hash, TreeError> {
+        Ok(self.root_output()?.root_hash())
+    }
+
+    /// Finalize the root node and return an [`OutputReader`], which can supply any number of
+    /// output bytes. This is an error if the tree isn't [complete](TreeBuilder::is_complete) yet.
+    pub fn finalize_xof(&self) -> Result<OutputReader, TreeError> {
+        Ok(OutputReader::new(self.root_output()?))
+    }
+
+    fn root_output(&self) -> Result<Output, TreeError> {
+        let Some(input_len) = self.input_len else {
+            return Err(TreeError(TreeErrorInner::UnknownLen));
+        };
+        if input_len == 0 {
+            return Ok(ChunkState::new(&self.key, 0, self.flags, self.platform).output());
+        }
+        if input_len <= CHUNK_LEN as u64 {
+            match &self.first_chunk {
+                Some((len, output)) if *len == input_len => return Ok(output.clone()),
+                _ => {}
+            }
+        } else {
+            // Merging is eager, so a complete tree is always down to the root's two children.
+            let left_len = left_subtree_len(input_len);
+            let right_len = input_len - left_len;
+            match (self.subtrees.get(&0), self.subtrees.get(&left_len)) {
+                (Some(&(len0, left_cv)), Some(&(len1, right_cv)))
+                    if len0 == left_len && len1 == right_len =>
+                {
+                    return Ok(crate::parent_node_output(
+                        &left_cv,
+                        &right_cv,
+                        &self.key,
+                        self.flags,
+                        self.platform,
+                    ));
+                }
+                _ => {}
+            }
+        }
+        let missing = self.missing_ranges().iter().map(|r| r.end - r.start).sum();
+        debug_assert!(missing > 0);
+        Err(TreeError(TreeErrorInner::Incomplete { missing }))
+    }
+
+    // Validate a new subtree or input range without changing anything, and return the input
+    // length if it's known. If `must_end` is true, the range has to end the input, and the input
+    // length might be newly implied by this range.
+    fn check_range(&self, offset: u64, len: u64, must_end: bool) -> Result<Option<u64>, TreeError> {
+        let offset_in_chunk = offset % CHUNK_LEN as u64;
+        if offset_in_chunk > 0 {
+            return Err(TreeError(TreeErrorInner::Unaligned { offset }));
+        }
+        if len == 0 {
+            return Err(TreeError(TreeErrorInner::Empty));
+        }
+        let Some(end) = offset.checked_add(len) else {
+            let max = u64::MAX - offset;
+            return Err(TreeError(TreeErrorInner::TooLong { offset, len, max }));
+        };
+        let input_len = match self.input_len {
+            Some(input_len) if end > input_len || (must_end && end != input_len) => {
+                return Err(TreeError(TreeErrorInner::InputLenMismatch {
+                    input_len,
+                    end,
+                }));
+            }
+            Some(input_len) => Some(input_len),
+            None if must_end => {
+                self.check_input_len(end)?;
+                Some(end)
+            }
+            None => None,
+        };
+        // Check for overlap with the last subtree that starts before the end of this one.
+        let overlaps_subtree = self
+            .subtrees
+            .range(..end)
+            .next_back()
+            .is_some_and(|(&o, &(l, _))| o + l > offset);
+        if overlaps_subtree || (offset == 0 && self.first_chunk.is_some()) {
+            return Err(TreeError(TreeErrorInner::Overlap { offset, len }));
+        }
+        Ok(input_len)
+    }
+
+    fn check_input_len(&self, input_len: u64) -> Result<(), TreeError> {
+        if let Some(known) = self.input_len.filter(|&known| known != input_len) {
+            return Err(TreeError(TreeErrorInner::InputLenMismatch {
+                input_len: known,
+                end: input_len,
+            }));
+        }
+        let end = self.subtrees.last_key_value().map(|(&o, &(l, _))| o + l);
+        if let Some(end) = end.filter(|&end| end > input_len) {
+            return Err(TreeError(TreeErrorInner::InputLenMismatch {
+                input_len,
+                end,
+            }));
+        }
+        // A chaining value for the whole input would've been the root. The first chunk is the
+        // exception, if it was added as input.
+        let first_len = self.subtrees.get(&0).map(|&(len, _)| len);
+        if first_len == Some(input_len) && self.first_chunk_len() != Some(input_len) {
+            return Err(TreeError(TreeErrorInner::Root { len: input_len }));
+        }
+        Ok(())
+    }
+
+    fn first_chunk_len(&self) -> Option<u64> {
+        self.first_chunk.as_ref().map(|(len, _)| *len)
+    }
+
+    fn apply_input_len(&mut self, input_len: u64) {
+        if self.input_len.is_some() {
+            return;
+        }
+        self.input_len = Some(input_len);
+        if input_len <= CHUNK_LEN as u64 {
+            // A whole first chunk that turns out to be the whole input isn't a subtree.
+            self.subtrees.remove(&0);
+            return;
+        }
+        self.first_chunk = None;
+        // Subtrees that end the input, and subtrees at the start of the input, might not have
+        // been mergeable until now.
+        let offsets: Vec<u64> = self.subtrees.keys().copied().collect();
+        for offset in offsets {
+            if self.subtrees.contains_key(&offset) {
+                self.merge_from(offset);
+            }
+        }
+    }
+
+    fn insert(&mut self, offset: u64, len: u64, chaining_value: ChainingValue) {
+        self.subtrees.insert(offset, (len, chaining_value));
+        self.merge_from(offset);
+    }
+
+    // If the left subtree at `left_offset` and its right neighbor of `right_len` bytes are
+    // siblings, return the length of their parent. The left subtree must be a complete,
+    // power-of-two subtree, and the right subtree must either be the same size or end the input.
+    // The parent must not be the root, or a subtree that might turn out to be the root.
+    fn parent_len(&self, left_offset: u64, left_len: u64, right_len: u64) -> Option<u64> {
+        let left_is_complete = left_len.is_power_of_two()
+            && left_len >= CHUNK_LEN as u64
+            && (left_offset / left_len) & 1 == 0;
+        if !left_is_complete {
+            return None;
+        }
+        let parent_len = left_len + right_len;
+        let ends_input = self.input_len == Some(left_offset + parent_len);
+        if right_len != left_len && !(right_len < left_len && ends_input) {
+            return None;
+        }
+        if left_offset == 0 && (self.input_len.is_none() || ends_input) {
+            return None;
+        }
+        Some(parent_len)
+    }
+
+    // Merge the subtree at `offset` with its siblings, repeatedly, as far as possible.
+    fn merge_from(&mut self, mut offset: u64) {
+        loop {
+            if self.merge_with_right(offset) {
+                continue;
+            }
+            let left_offset = match self.subtrees.range(..offset).next_back() {
+                Some((&left_offset, &(left_len, _))) if left_offset + left_len == offset => {
+                    left_offset
+                }
+                _ => return,
+            };
+            if !self.merge_with_right(left_offset) {
+                return;
+            }
+            offset = left_offset;
+        }
+    }
+
+    // If the subtree at `offset` and its right neighbor are siblings, replace them with their
+    // parent and return true.
+    fn merge_with_right(&mut self, offset: u64) -> bool {
+        let (left_len, left_cv) = self.subtrees[&offset];
+        let Some(&(right_len, right_cv)) = self.subtrees.get(&(offset + left_len)) else {
+            return false;
+        };
+        let Some(parent_len) = self.parent_len(offset, left_len, right_len) else {
+            return false;
+        };
+        let parent_cv =
+            crate::parent_node_output(&left_cv, &right_cv, &self.key, self.flags, self.platform)
+                .chaining_value();
+        self.subtrees.remove(&(offset + left_len));
+        self.subtrees.insert(offset, (parent_len, parent_cv));
+        true
+    }
+}
+
+fn largest_power_of_two_leq(n: u64) -> u64 {
+    1 << (63 - n.leading_zeros())
+}
+
+// Don't derive(Debug), because the state may be secret.
+impl fmt::Debug for TreeBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TreeBuilder")
+            .field("input_len", &self.input_len)
+            .field("missing_ranges", &self.missing_ranges())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for TreeBuilder {
+    fn zeroize(&mut self) {
+        // Destructuring to trigger compile error as a reminder to update this impl.
+        let Self {
+            key,
+            flags,
+            platform: _,
+            input_len,
+            subtrees,
+            first_chunk,
+        } = self;
+
+        key.zeroize();
+        flags.zeroize();
+        input_len.zeroize();
+        for (_, (_, chaining_value)) in subtrees.iter_mut() {
+            chaining_value.zeroize();
+        }
+        subtrees.clear();
+        if let Some((_, output)) = first_chunk {
+            output.zeroize();
+        }
+        *first_chunk = None;
+    }
+}
+
+/// The error type for [`TreeBuilder`].
+///
+/// The `.to_string()` representation of this error currently describes what went wrong, for
+/// example which range overlapped or how many bytes are missing. This is to help with logging and
+/// debugging, but it isn't a stable API detail, and it may change at any time.
+#[derive(Clone, Debug)]
+pub struct TreeError(TreeErrorInner);
+
+#[derive(Clone, Debug)]
+enum TreeErrorInner {
+    Unaligned { offset: u64 },
+    Empty,
+    TooLong { offset: u64, len: u64, max: u64 },
+    InputLenMismatch { input_len: u64, end: u64 },
+    Root { len: u64 },
+    Overlap { offset: u64, len: u64 },
+    UnknownLen,
+    Incomplete { missing: u64 },
+}
+
+impl fmt::Display for TreeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            TreeErrorInner::Unaligned { offset } => write!(
+                f,
+                "offset ({offset}) must be a chunk boundary (divisible by {CHUNK_LEN})",
+            ),
+            TreeErrorInner::Empty => write!(f, "empty subtrees are never valid"),
+            TreeErrorInner::TooLong { offset, len, max } => write!(
+                f,
+                "the subtree starting at {offset} contains at most {max} bytes (found {len})",
+            ),
+            TreeErrorInner::InputLenMismatch { input_len, end } => write!(
+                f,
+                "a subtree ending at {end} doesn't fit an input of {input_len} bytes",
+            ),
+            TreeErrorInner::Root { len } => write!(
+                f,
+                "a chaining value for 0..{len} would be the root of an input of {len} bytes",
+            ),
+            TreeErrorInner::Overlap { offset, len } => write!(
+                f,
+                "the range {}..{} overlaps a range that's already been added",
+                offset,
+                offset + len,
+            ),
+            TreeErrorInner::UnknownLen => write!(f, "the input length isn't known yet"),
+            TreeErrorInner::Incomplete { missing } => {
+                write!(
+                    f,
+                    "the tree is incomplete, {missing} bytes of input are missing"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for TreeError {}
@@ -0,0 +1,853 @@
+//! Verified streaming, with chaining values interleaved into the input.
+//!
+//! The [`Hasher`](crate::Hasher) can only check an input once all of it has been read, so a
+//! receiver who knows the expected [`Hash`](struct@Hash) has to buffer everything before trusting
+//! any of it. The combined encoding produced by [`encode`] fixes that, by storing the interior
+//! nodes of the BLAKE3 tree alongside the input. A [`Decoder`] reads the encoding incrementally,
+//! and it checks each chunk against the root hash before returning any of that chunk's bytes.
+//! It never needs more than one chunk of buffer space, plus a small stack of chaining values.
+//!
+//! The encoding starts with the input length as an 8-byte little-endian integer. The rest is the
+//! tree in pre-order: each parent node (the 64-byte concatenation of its left and right
+//! children's chaining values) comes before its left subtree, which comes before its right
+//! subtree. Each leaf is one chunk of input, up to [`CHUNK_LEN`] bytes. This is the same layout
+//! as the combined encoding of [Bao](https://github.com/oconnor663/bao).
+//!
+//! For large inputs that are stored somewhere else anyway, like immutable files, the parent
+//! nodes can go in a separate "outboard" tree instead. [`write_outboard`] writes one, with a
+//! configurable group size for the leaves, and [`verify_outboard_range`] checks any whole groups
+//! of the input against the root hash. When part of a large mutable file changes in place,
+//! [`update_outboard`] rehashes only the changed groups and their parent nodes.
+//!
+//! All the functions here take a [`Mode`], so encodings can use the keyed hash or
+//! [`derive_key`](crate::derive_key) modes as well as the default hash mode. The [`Decoder`] has
+//! to use the same mode as the encoder.
+//!
+//! This module requires the `std` Cargo feature, which is enabled by default.
+//!
+//! # Example
+//!
+//! ```
+//! # fn main() -> std::io::Result<()> {
+//! use blake3::hazmat::Mode;
+//! use blake3::verified::{Decoder, encode};
+//! use std::io::prelude::*;
+//!
+//! let input = vec![42; 1_000_000];
+//! let (encoded, hash) = encode(&input, Mode::Hash);
+//! assert_eq!(hash, blake3::hash(&input));
+//!
+//! let mut decoder = Decoder::new(&encoded[..], &hash, Mode::Hash);
+//! let mut output = Vec::new();
+//! decoder.read_to_end(&mut output)?;
+//! assert_eq!(input, output);
+//!
+//! // Any change to the encoding is an error, at the latest when the decoder reaches it.
+//! let mut corrupt = encoded.clone();
+//! corrupt[500_000] ^= 1;
+//! let mut decoder = Decoder::new(&corrupt[..], &hash, Mode::Hash);
+//! let error = decoder.read_to_end(&mut Vec::new()).unwrap_err();
+//! assert!(blake3::io::HashMismatch::is_mismatch(&error));
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::hazmat::{
+    ChainingValue, HasherExt, Mode, left_subtree_len, merge_subtrees_non_root, merge_subtrees_root,
+};
+use crate::io::HashMismatch;
+use crate::platform::Platform;
+use crate::{BLOCK_LEN, CHUNK_LEN, CVBytes, CVWords, ChunkState, Hash, MAX_DEPTH, OUT_LEN, Output};
+use arrayvec::ArrayVec;
+use core::cmp;
+use core::fmt;
+use std::io::{self, Read};
+use std::vec::Vec;
+
+/// The length of the header at the start of an encoding, which holds the input length.
+pub const HEADER_LEN: usize = 8;
+
+/// The length of each parent node in an encoding, two concatenated chaining values.
+pub const PARENT_LEN: usize = BLOCK_LEN;
+
+/// Return the length of the combined encoding of an input of `input_len` bytes.
+///
+/// This is a `u128`, because the encoding of an input close to the maximum `u64` length is longer
+/// than that.
+pub fn encoded_len(input_len: u64) -> u128 {
+    let num_chunks = cmp::max(1, input_len.div_ceil(CHUNK_LEN as u64));
+    let num_parents = num_chunks - 1;
+    HEADER_LEN as u128 + input_len as u128 + num_parents as u128 * PARENT_LEN as u128
+}
+
+/// Encode `input` in the combined format, and return the encoding and the root hash.
+///
+/// See the [module level docs](index.html) for the format and an example.
+pub fn encode(input: &[u8], mode: Mode) -> (Vec<u8>, Hash) {
+    let mut encoded = std::vec![0; encoded_len(input.len() as u64) as usize];
+    let (header, tree) = encoded.split_at_mut(HEADER_LEN);
+    header.copy_from_slice(&(input.len() as u64).to_le_bytes());
+    let key = mode.key_words();
+    let output = encode_subtree(input, 0, tree, &key, mode.flags_byte(), Platform::detect());
+    (encoded, output.root_hash())
+}
+
+// Write the pre-order encoding of one subtree to `out`, which has exactly the right length, and
+// return the subtree's Output. Each parent node is written after its children are hashed.
+fn encode_subtree(
+    input: &[u8],
+    input_offset: u64,
+    out: &mut [u8],
+    key: &CVWords,
+    flags: u8,
+    platform: Platform,
+) -> Output {
+    if input.len() <= CHUNK_LEN {
+        out.copy_from_slice(input);
+        let chunk_counter = input_offset / CHUNK_LEN as u64;
+        return ChunkState::new(key, chunk_counter, flags, platform)
+            .update(input)
+            .output();
+    }
+    let left_len = left_subtree_len(input.len() as u64) as usize;
+    let (left_input, right_input) = input.split_at(left_len);
+    let (parent, children) = out.split_at_mut(PARENT_LEN);
+    // The left subtree is always complete, with no partial chunks.
+    let left_encoded_len = encoded_len(left_len as u64) as usize - HEADER_LEN;
+    let (left_out, right_out) = children.split_at_mut(left_encoded_len);
+    let left_output = encode_subtree(left_input, input_offset, left_out, key, flags, platform);
+    let right_offset = input_offset + left_len as u64;
+    let right_output = encode_subtree(right_input, right_offset, right_out, key, flags, platform);
+    let left_cv = left_output.chaining_value();
+    let right_cv = right_output.chaining_value();
+    parent[..OUT_LEN].copy_from_slice(&left_cv);
+    parent[OUT_LEN..].copy_from_slice(&right_cv);
+    crate::parent_node_output(&left_cv, &right_cv, key, flags, platform)
+}
+
+/// Decode a combined encoding that's entirely in memory, and return the input.
+///
+/// This is a convenience wrapper around [`Decoder`]. If the encoding doesn't match `hash`, the
+/// error is an [`InvalidData`](io::ErrorKind::InvalidData) error with a [`HashMismatch`] inside.
+/// If the encoding is truncated, the error is [`UnexpectedEof`](io::ErrorKind::UnexpectedEof).
+/// Any bytes after the end of the encoding are ignored.
+pub fn decode(encoded: &[u8], hash: &Hash, mode: Mode) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    io::Read::read_to_end(&mut Decoder::new(encoded, hash, mode), &mut output)?;
+    Ok(output)
+}
+
+// A subtree that hasn't been read yet, and the chaining value that it has to match.
+#[derive(Clone)]
+struct Subtree {
+    input_offset: u64,
+    len: u64,
+    chaining_value: CVBytes,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Failure {
+    Mismatch,
+    Other(io::ErrorKind),
+}
+
+/// A reader that decodes and verifies a combined encoding.
+///
+/// `Decoder` reads the encoding from the wrapped reader and returns the original input through
+/// its [`Read`] implementation. It doesn't return any bytes of a chunk until that chunk
+/// and all the parent nodes above it have been verified against the root hash. If verification
+/// fails, the read returns an [`InvalidData`](io::ErrorKind::InvalidData) error with a
+/// [`HashMismatch`] inside. A truncated encoding returns
+/// [`UnexpectedEof`](io::ErrorKind::UnexpectedEof).
+///
+/// The decoder can't resume partway through a parent node or a chunk, so after any error
+/// (including errors from the inner reader, like [`WouldBlock`](io::ErrorKind::WouldBlock)), all
+/// later reads return an error too.
+///
+/// See the [module level docs](index.html) for an example.
+pub struct Decoder<R> {
+    inner: R,
+    hash: Hash,
+    key: CVWords,
+    flags: u8,
+    platform: Platform,
+    // None until the header has been read.
+    input_len: Option<u64>,
+    // The right siblings of the nodes we've descended into, innermost last.
+    stack: ArrayVec<Subtree, MAX_DEPTH>,
+    buf: [u8; CHUNK_LEN],
+    buf_len: usize,
+    buf_pos: usize,
+    failure: Option<Failure>,
+}
+
+impl<R> Decoder<R> {
+    /// Wrap a reader of an encoding, to be verified against `hash`, which the encoder computed
+    /// with the same `mode`.
+    pub fn new(inner: R, hash: &Hash, mode: Mode) -> Self {
+        Self {
+            inner,
+            hash: *hash,
+            key: mode.key_words(),
+            flags: mode.flags_byte(),
+            platform: Platform::detect(),
+            input_len: None,
+            stack: ArrayVec::new(),
+            buf: [0; CHUNK_LEN],
+            buf_len: 0,
+            buf_pos: 0,
+            failure: None,
+        }
+    }
+
+    /// The length of the input, once the header has been read.
+    ///
+    /// This comes from the unverified header. A wrong length changes the shape of the tree, so it
+    /// leads to an error by the time the decoder reaches the final chunk, but until then it
+    /// shouldn't be trusted, for example to preallocate a buffer.
+    pub fn input_len(&self) -> Option<u64> {
+        self.input_len
+    }
+
+    /// Get a reference to the inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner reader. Reading from it directly will break decoding.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwrap the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: io::Read> Decoder<R> {
+    fn check(&mut self, output: &Output, expected: Option<&CVBytes>) -> io::Result<()> {
+        // Comparing Hashes is constant-time.
+        let matches = match expected {
+            Some(chaining_value) => Hash::from(output.chaining_value()) == *chaining_value,
+            None => output.root_hash() == self.hash,
+        };
+        if matches {
+            Ok(())
+        } else {
+            self.failure = Some(Failure::Mismatch);
+            Err(HashMismatch::new_error())
+        }
+    }
+
+    // Read and verify parent nodes down to the next chunk, then read and verify that chunk into
+    // the buffer. Return false when the whole tree has been read.
+    fn read_next_chunk(&mut self) -> io::Result<bool> {
+        // Only the first node we read, the root, is checked against the root hash.
+        let (mut subtree, mut is_root) = match self.input_len {
+            Some(_) => match self.stack.pop() {
+                Some(subtree) => (subtree, false),
+                None => return Ok(false),
+            },
+            None => {
+                let mut header = [0; HEADER_LEN];
+                self.inner.read_exact(&mut header)?;
+                let input_len = u64::from_le_bytes(header);
+                self.input_len = Some(input_len);
+                let root = Subtree {
+                    input_offset: 0,
+                    len: input_len,
+                    chaining_value: [0; OUT_LEN],
+                };
+                (root, true)
+            }
+        };
+        while subtree.len > CHUNK_LEN as u64 {
+            let mut parent = [0; PARENT_LEN];
+            self.inner.read_exact(&mut parent)?;
+            let left_cv: CVBytes = *arrayref::array_ref!(parent, 0, OUT_LEN);
+            let right_cv: CVBytes = *arrayref::array_ref!(parent, OUT_LEN, OUT_LEN);
+            let output = crate::parent_node_output(
+                &left_cv,
+                &right_cv,
+                &self.key,
+                self.flags,
+                self.platform,
+            );
+            self.check(&output, (!is_root).then_some(&subtree.chaining_value))?;
+            let left_len = left_subtree_len(subtree.len);
+            self.stack.push(Subtree {
+                input_offset: subtree.input_offset + left_len,
+                len: subtree.len - left_len,
+                chaining_value: right_cv,
+            });
+            subtree.len = left_len;
+            subtree.chaining_value = left_cv;
+            is_root = false;
+        }
+        let chunk = &mut self.buf[..subtree.len as usize];
+        self.inner.read_exact(chunk)?;
+        let chunk_counter = subtree.input_offset / CHUNK_LEN as u64;
+        let output = ChunkState::new(&self.key, chunk_counter, self.flags, self.platform)
+            .update(chunk)
+            .output();
+        self.check(&output, (!is_root).then_some(&subtree.chaining_value))?;
+        self.buf_len = subtree.len as usize;
+        self.buf_pos = 0;
+        Ok(true)
+    }
+}
+
+impl<R: io::Read> io::Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.failure {
+            Some(Failure::Mismatch) => return Err(HashMismatch::new_error()),
+            Some(Failure::Other(kind)) => {
+                return Err(io::Error::new(
+                    kind,
+                    "an earlier read from this Decoder failed",
+                ));
+            }
+            None => {}
+        }
+        while self.buf_pos == self.buf_len {
+            match self.read_next_chunk() {
+                Ok(true) => {}
+                Ok(false) => return Ok(0),
+                Err(e) => {
+                    if self.failure.is_none() {
+                        self.failure = Some(Failure::Other(e.kind()));
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        let take = cmp::min(buf.len(), self.buf_len - self.buf_pos);
+        buf[..take].copy_from_slice(&self.buf[self.buf_pos..][..take]);
+        self.buf_pos += take;
+        Ok(take)
+    }
+}
+
+// Don't derive(Debug), because the state may be secret.
+impl<R: fmt::Debug> fmt::Debug for Decoder<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Decoder")
+            .field("inner", &self.inner)
+            .field("input_len", &self.input_len)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Return the length of the outboard tree of an input of `input_len` bytes, with leaf groups of
+/// `group_len` bytes. See [`write_outboard`].
+///
+/// # Panics
+///
+/// Panics if `group_len` isn't a power of two multiple of [`CHUNK_LEN`].
+pub fn outboard_len(input_len: u64, group_len: u64) -> u64 {
+    assert_group_len(group_len);
+    let num_groups = cmp::max(1, input_len.div_ceil(group_len));
+    HEADER_LEN as u64 + (num_groups - 1) * PARENT_LEN as u64
+}
+
+fn assert_group_len(group_len: u64) {
+    assert!(
+        group_len.is_power_of_two() && group_len >= CHUNK_LEN as u64,
+        "the group length ({group_len}) must be a power of two multiple of {CHUNK_LEN}",
+    );
+}
+
+/// Read an input of `input_len` bytes and write its outboard tree, with leaf groups of
+/// `group_len` bytes, and return the root hash.
+///
+/// An outboard tree is the same as a combined encoding (see the [module level docs](index.html)),
+/// but without the input bytes. It starts with the 8-byte little-endian input length, followed by
+/// the parent nodes in pre-order. The input stays where it is, for example in a large immutable
+/// file, and [`verify_outboard_range`] checks any part of it using only that part and the
+/// outboard tree.
+///
+/// The leaves of the tree are groups of `group_len` bytes, which must be a power of two multiple
+/// of [`CHUNK_LEN`]. The parent nodes inside each group aren't stored. Larger groups make the
+/// outboard tree smaller, but ranges need to be verified in whole groups. For example, with 16 KiB
+/// groups, the outboard tree is 1/256th the size of the input. The group length isn't stored in
+/// the outboard tree, and the verifier has to use the same one. The root hash doesn't depend on
+/// the group length, and it's the same as [`Hasher::finalize`](crate::Hasher::finalize) would
+/// return for the same `mode`.
+///
+/// The input length determines the shape of the tree, so it has to be known up front, for example
+/// from the metadata of a file. Each parent node is written as soon as its children have been
+/// hashed, by seeking back to its place in the outboard tree, so memory use doesn't grow with the
+/// input. The outboard tree starts at the writer's current position, and the writer is left at
+/// the end of it. If the reader reaches EOF before `input_len` bytes, the error is
+/// [`UnexpectedEof`](io::ErrorKind::UnexpectedEof), and if it has more bytes than that, the error
+/// is [`InvalidInput`](io::ErrorKind::InvalidInput).
+///
+/// # Panics
+///
+/// Panics if `group_len` isn't a power of two multiple of [`CHUNK_LEN`].
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use blake3::hazmat::Mode;
+/// use blake3::verified::{verify_outboard_range, write_outboard};
+/// use std::io::Cursor;
+///
+/// const GROUP_LEN: u64 = 16 * 1024;
+/// let input = vec![42; 1_000_000];
+/// let mut outboard = Cursor::new(Vec::new());
+/// let hash = write_outboard(&input[..], 1_000_000, &mut outboard, GROUP_LEN, Mode::Hash)?;
+/// assert_eq!(hash, blake3::hash(&input));
+///
+/// // Verify the third and fourth groups, without the rest of the input.
+/// let outboard = outboard.into_inner();
+/// let range = &input[2 * GROUP_LEN as usize..4 * GROUP_LEN as usize];
+/// verify_outboard_range(&outboard, GROUP_LEN, 2 * GROUP_LEN, range, &hash, Mode::Hash)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_outboard(
+    reader: impl io::Read,
+    input_len: u64,
+    mut writer: impl io::Write + io::Seek,
+    group_len: u64,
+    mode: Mode,
+) -> io::Result<Hash> {
+    assert_group_len(group_len);
+    let start = writer.stream_position()?;
+    writer.write_all(&input_len.to_le_bytes())?;
+    let mut outboard_writer = OutboardWriter {
+        reader,
+        writer,
+        start,
+        group_len,
+        mode,
+    };
+    let hash = if input_len <= group_len {
+        outboard_writer.hash_group(0, input_len)?.finalize()
+    } else {
+        let mut outboard_pos = HEADER_LEN as u64;
+        let (left_cv, right_cv) = outboard_writer.write_parent(0, input_len, &mut outboard_pos)?;
+        debug_assert_eq!(outboard_pos, outboard_len(input_len, group_len));
+        merge_subtrees_root(&left_cv, &right_cv, mode)
+    };
+    let OutboardWriter {
+        mut reader,
+        mut writer,
+        ..
+    } = outboard_writer;
+    if io::copy(&mut (&mut reader).take(1), &mut io::sink())? != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the input is longer than input_len",
+        ));
+    }
+    writer.seek(io::SeekFrom::Start(
+        start + outboard_len(input_len, group_len),
+    ))?;
+    Ok(hash)
+}
+
+struct OutboardWriter<'a, R, W> {
+    reader: R,
+    writer: W,
+    // The writer's position at the start of the outboard tree.
+    start: u64,
+    group_len: u64,
+    mode: Mode<'a>,
+}
+
+impl<R: io::Read, W: io::Write + io::Seek> OutboardWriter<'_, R, W> {
+    // Read the group of `len` bytes at `offset`, and return its Hasher.
+    fn hash_group(&mut self, offset: u64, len: u64) -> io::Result<crate::Hasher> {
+        let mut hasher =
+            crate::Hasher::new_internal(&self.mode.key_words(), self.mode.flags_byte());
+        hasher.set_input_offset(offset);
+        if crate::io::copy_wide((&mut self.reader).take(len), &mut hasher)? < len {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the input is shorter than input_len",
+            ));
+        }
+        Ok(hasher)
+    }
+
+    // Read the subtree of `len` bytes at `offset`, which has more than one group, and write its
+    // parent nodes. Return the children of its top parent node. `outboard_pos` is the position of
+    // that parent node in the outboard tree, and it's advanced past the subtree's parent nodes.
+    fn write_parent(
+        &mut self,
+        offset: u64,
+        len: u64,
+        outboard_pos: &mut u64,
+    ) -> io::Result<(ChainingValue, ChainingValue)> {
+        debug_assert!(len > self.group_len);
+        let parent_pos = *outboard_pos;
+        *outboard_pos += PARENT_LEN as u64;
+        let left_len = left_subtree_len(len);
+        let left_cv = self.child_cv(offset, left_len, outboard_pos)?;
+        let right_cv = self.child_cv(offset + left_len, len - left_len, outboard_pos)?;
+        let mut parent = [0; PARENT_LEN];
+        parent[..OUT_LEN].copy_from_slice(&left_cv);
+        parent[OUT_LEN..].copy_from_slice(&right_cv);
+        self.writer
+            .seek(io::SeekFrom::Start(self.start + parent_pos))?;
+        self.writer.write_all(&parent)?;
+        Ok((left_cv, right_cv))
+    }
+
+    fn child_cv(
+        &mut self,
+        offset: u64,
+        len: u64,
+        outboard_pos: &mut u64,
+    ) -> io::Result<ChainingValue> {
+        if len <= self.group_len {
+            Ok(self.hash_group(offset, len)?.finalize_non_root())
+        } else {
+            let (left_cv, right_cv) = self.write_parent(offset, len, outboard_pos)?;
+            Ok(merge_subtrees_non_root(&left_cv, &right_cv, self.mode))
+        }
+    }
+}
+
+/// Check a range of an input against its root hash, using the outboard tree from
+/// [`write_outboard`].
+///
+/// `range` holds the input bytes starting at `range_start`. The range has to be made of whole
+/// groups: `range_start` must be a multiple of `group_len`, and the end of the range must be
+/// either a multiple of `group_len` or the end of the input. The range can't be empty, unless the
+/// whole input is empty. `group_len` and `mode` must be the same ones that the outboard tree was
+/// written with.
+///
+/// The outboard tree is read from memory, but only the parent nodes along the paths to the range
+/// are used. The input length in the outboard header is only verified if the range includes the
+/// final group.
+///
+/// # Panics
+///
+/// Panics if `group_len` isn't a power of two multiple of [`CHUNK_LEN`].
+pub fn verify_outboard_range(
+    outboard: &[u8],
+    group_len: u64,
+    range_start: u64,
+    range: &[u8],
+    hash: &Hash,
+    mode: Mode,
+) -> Result<(), OutboardError> {
+    assert_group_len(group_len);
+    let (input_len, range_end) = check_outboard_range(outboard, group_len, range_start, range)?;
+    let verifier = OutboardVerifier {
+        outboard,
+        group_len,
+        range_start,
+        range_end,
+        range,
+        hash,
+        mode,
+    };
+    let mut outboard_pos = HEADER_LEN;
+    verifier.verify_subtree(0, input_len, None, &mut outboard_pos)
+}
+
+/// Update an outboard tree from [`write_outboard`] after a range of the input has changed in
+/// place, and return the new root hash.
+///
+/// `range` holds the new input bytes starting at `range_start`. As with
+/// [`verify_outboard_range`], it has to be made of whole groups, so an edit to a few bytes needs
+/// the rest of the groups around it. The input length can't change. This hashes only the groups in
+/// the range, and it rewrites only the parent nodes on the paths from them to the root, so an
+/// update costs O(log n) parent compressions plus the edited groups, instead of rehashing the
+/// whole input.
+///
+/// The other parent nodes are trusted as they are. If the outboard tree came from somewhere
+/// untrusted, check it first with [`verify_outboard_range`], or compare the new root hash with one
+/// computed some other way.
+///
+/// # Panics
+///
+/// Panics if `group_len` isn't a power of two multiple of [`CHUNK_LEN`].
+///
+/// # Example
+///
+/// ```
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// use blake3::hazmat::Mode;
+/// use blake3::verified::{update_outboard, write_outboard};
+/// use std::io::Cursor;
+///
+/// const GROUP_LEN: u64 = 16 * 1024;
+/// let mut input = vec![42; 1_000_000];
+/// let mut outboard = Cursor::new(Vec::new());
+/// write_outboard(&input[..], 1_000_000, &mut outboard, GROUP_LEN, Mode::Hash)?;
+/// let mut outboard = outboard.into_inner();
+///
+/// // Change one byte, and rehash the group that it's in.
+/// input[500_000] = 0;
+/// let start = 500_000 / GROUP_LEN * GROUP_LEN;
+/// let group = &input[start as usize..(start + GROUP_LEN) as usize];
+/// let hash = update_outboard(&mut outboard, GROUP_LEN, start, group, Mode::Hash)?;
+/// assert_eq!(hash, blake3::hash(&input));
+/// # Ok(())
+/// # }
+/// ```
+pub fn update_outboard(
+    outboard: &mut [u8],
+    group_len: u64,
+    range_start: u64,
+    range: &[u8],
+    mode: Mode,
+) -> Result<Hash, OutboardError> {
+    assert_group_len(group_len);
+    let (input_len, range_end) = check_outboard_range(outboard, group_len, range_start, range)?;
+    let updater = OutboardUpdater {
+        group_len,
+        range_start,
+        range_end,
+        range,
+        mode,
+    };
+    if input_len <= group_len {
+        let mut hasher = crate::Hasher::new_internal(&mode.key_words(), mode.flags_byte());
+        return Ok(hasher.update(range).finalize());
+    }
+    let mut outboard_pos = HEADER_LEN;
+    let (left_cv, right_cv) = updater.update_parent(outboard, 0, input_len, &mut outboard_pos);
+    Ok(merge_subtrees_root(&left_cv, &right_cv, mode))
+}
+
+// Check that the outboard tree has the right length for its header, and that the range is made of
+// whole groups of the input. Return the input length and the end of the range.
+fn check_outboard_range(
+    outboard: &[u8],
+    group_len: u64,
+    range_start: u64,
+    range: &[u8],
+) -> Result<(u64, u64), OutboardError> {
+    let Some(header) = outboard.first_chunk::<HEADER_LEN>() else {
+        return Err(OutboardError(OutboardErrorInner::TooShort {
+            len: outboard.len(),
+        }));
+    };
+    let input_len = u64::from_le_bytes(*header);
+    let expected_len = outboard_len(input_len, group_len);
+    if outboard.len() as u64 != expected_len {
+        return Err(OutboardError(OutboardErrorInner::WrongLen {
+            input_len,
+            group_len,
+            expected: expected_len,
+            found: outboard.len(),
+        }));
+    }
+    let range_end = range_start.checked_add(range.len() as u64);
+    let offset_in_group = |offset: u64| offset % group_len;
+    match range_end {
+        Some(end)
+            if end <= input_len
+                && (offset_in_group(end) == 0 || end == input_len)
+                && offset_in_group(range_start) == 0
+                && (!range.is_empty() || input_len == 0) =>
+        {
+            Ok((input_len, end))
+        }
+        _ => Err(OutboardError(OutboardErrorInner::InvalidRange {
+            start: range_start,
+            len: range.len(),
+            input_len,
+        })),
+    }
+}
+
+struct OutboardVerifier<'a> {
+    outboard: &'a [u8],
+    group_len: u64,
+    range_start: u64,
+    range_end: u64,
+    range: &'a [u8],
+    hash: &'a Hash,
+    mode: Mode<'a>,
+}
+
+impl OutboardVerifier<'_> {
+    // Verify the subtree of `len` bytes at `offset`, which overlaps the range, against its
+    // expected chaining value, or against the root hash if `expected` is None. `outboard_pos` is
+    // the position of the subtree's parent nodes in the outboard, and it's advanced past them.
+    fn verify_subtree(
+        &self,
+        offset: u64,
+        len: u64,
+        expected: Option<&ChainingValue>,
+        outboard_pos: &mut usize,
+    ) -> Result<(), OutboardError> {
+        let mismatch = Err(OutboardError(OutboardErrorInner::Mismatch));
+        if len <= self.group_len {
+            // Ranges are made of whole groups, so this whole group is in the range.
+            let group = &self.range[(offset - self.range_start) as usize..][..len as usize];
+            let mut hasher =
+                crate::Hasher::new_internal(&self.mode.key_words(), self.mode.flags_byte());
+            hasher.set_input_offset(offset).update(group);
+            // Comparing Hashes is constant-time.
+            let matches = match expected {
+                Some(expected) => Hash::from(hasher.finalize_non_root()) == *expected,
+                None => hasher.finalize() == *self.hash,
+            };
+            return if matches { Ok(()) } else { mismatch };
+        }
+        let parent = &self.outboard[*outboard_pos..][..PARENT_LEN];
+        *outboard_pos += PARENT_LEN;
+        let left_cv: ChainingValue = *arrayref::array_ref!(parent, 0, OUT_LEN);
+        let right_cv: ChainingValue = *arrayref::array_ref!(parent, OUT_LEN, OUT_LEN);
+        let matches = match expected {
+            Some(expected) => {
+                Hash::from(merge_subtrees_non_root(&left_cv, &right_cv, self.mode)) == *expected
+            }
+            None => merge_subtrees_root(&left_cv, &right_cv, self.mode) == *self.hash,
+        };
+        if !matches {
+            return mismatch;
+        }
+        let left_len = left_subtree_len(len);
+        let children = [
+            (offset, left_len, left_cv),
+            (offset + left_len, len - left_len, right_cv),
+        ];
+        for (child_offset, child_len, child_cv) in children {
+            if child_offset < self.range_end && self.range_start < child_offset + child_len {
+                self.verify_subtree(child_offset, child_len, Some(&child_cv), outboard_pos)?;
+            } else {
+                // Skip the child's parent nodes, one fewer than its groups.
+                let child_groups = child_len.div_ceil(self.group_len);
+                *outboard_pos += (child_groups - 1) as usize * PARENT_LEN;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct OutboardUpdater<'a> {
+    group_len: u64,
+    range_start: u64,
+    range_end: u64,
+    range: &'a [u8],
+    mode: Mode<'a>,
+}
+
+impl OutboardUpdater<'_> {
+    // Recompute the children of the parent node of the subtree of `len` bytes at `offset`, which
+    // overlaps the range and has more than one group, and write them to the outboard. Return the
+    // parent node's children. `outboard_pos` is advanced past the subtree's parent nodes.
+    fn update_parent(
+        &self,
+        outboard: &mut [u8],
+        offset: u64,
+        len: u64,
+        outboard_pos: &mut usize,
+    ) -> (ChainingValue, ChainingValue) {
+        debug_assert!(len > self.group_len);
+        let parent_pos = *outboard_pos;
+        *outboard_pos += PARENT_LEN;
+        let left_len = left_subtree_len(len);
+        let children = [(offset, left_len), (offset + left_len, len - left_len)];
+        for (i, (child_offset, child_len)) in children.into_iter().enumerate() {
+            let cv_pos = parent_pos + i * OUT_LEN;
+            if !(child_offset < self.range_end && self.range_start < child_offset + child_len) {
+                // Keep the child's chaining value, and skip its parent nodes, one fewer than its
+                // groups.
+                let child_groups = child_len.div_ceil(self.group_len);
+                *outboard_pos += (child_groups - 1) as usize * PARENT_LEN;
+                continue;
+            }
+            let child_cv = if child_len <= self.group_len {
+                // Ranges are made of whole groups, so this whole group is in the range.
+                let start = (child_offset - self.range_start) as usize;
+                let group = &self.range[start..][..child_len as usize];
+                let mut hasher =
+                    crate::Hasher::new_internal(&self.mode.key_words(), self.mode.flags_byte());
+                hasher.set_input_offset(child_offset).update(group);
+                hasher.finalize_non_root()
+            } else {
+                let (left_cv, right_cv) =
+                    self.update_parent(outboard, child_offset, child_len, outboard_pos);
+                merge_subtrees_non_root(&left_cv, &right_cv, self.mode)
+            };
+            outboard[cv_pos..][..OUT_LEN].copy_from_slice(&child_cv);
+        }
+        let parent = &outboard[parent_pos..][..PARENT_LEN];
+        (
+            *arrayref::array_ref!(parent, 0, OUT_LEN),
+            *arrayref::array_ref!(parent, OUT_LEN, OUT_LEN),
+        )
+    }
+}
+
+/// The error type for [`verify_outboard_range`] and [`update_outboard`].
+///
+/// The `.to_string()` representation of this error currently describes what went wrong, for
+/// example which range was invalid. This is to help with logging and debugging, but it isn't a
+/// stable API detail, and it may change at any time.
+#[derive(Clone, Debug)]
+pub struct OutboardError(OutboardErrorInner);
+
+#[derive(Clone, Debug)]
+enum OutboardErrorInner {
+    TooShort {
+        len: usize,
+    },
+    WrongLen {
+        input_len: u64,
+        group_len: u64,
+        expected: u64,
+        found: usize,
+    },
+    InvalidRange {
+        start: u64,
+        len: usize,
+        input_len: u64,
+    },
+    Mismatch,
+}
+
+impl OutboardError {
+    /// Return true if the range or the outboard tree didn't match the root hash. The other errors
+    /// mean that the arguments were inconsistent, before any hashes were compared.
+    pub fn is_mismatch(&self) -> bool {
+        matches!(self.0, OutboardErrorInner::Mismatch)
+    }
+}
+
+impl fmt::Display for OutboardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            OutboardErrorInner::TooShort { len } => {
+                write!(
+                    f,
+                    "an outboard tree of {len} bytes is too short for the header"
+                )
+            }
+            OutboardErrorInner::WrongLen {
+                input_len,
+                group_len,
+                expected,
+                found,
+            } => write!(
+                f,
+                "an input of {input_len} bytes with {group_len}-byte groups has an outboard tree \
+                 of {expected} bytes (found {found})",
+            ),
+            OutboardErrorInner::InvalidRange {
+                start,
+                len,
+                input_len,
+            } => write!(
+                f,
+                "a range of {len} bytes at offset {start} isn't a whole number of groups in an \
+                 input of {input_len} bytes",
+            ),
+            OutboardErrorInner::Mismatch => write!(f, "BLAKE3 hash mismatch"),
+        }
+    }
+}
+
+impl std::error::Error for OutboardError {}